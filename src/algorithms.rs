@@ -0,0 +1,129 @@
+use std::ascii::AsciiExt;
+
+
+/// How safe an algorithm is to rely on for security.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Practically broken - e.g. collisions can be found, or keys recovered.
+    Broken,
+
+    /// Not broken, but deprecated or too small for new designs.
+    Legacy,
+
+    /// Fine to use.
+    Acceptable,
+}
+
+impl Status {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Status::Broken     => "broken",
+            Status::Legacy     => "legacy",
+            Status::Acceptable => "acceptable",
+        }
+    }
+}
+
+
+/// What we know about an algorithm, keyed by the name used in `Pattern`s.
+pub struct Algorithm {
+    pub name: &'static str,
+
+    /// The family that the algorithm belongs to, e.g. "SHA-2" for SHA-256.
+    pub family: &'static str,
+
+    /// What sort of algorithm this is, e.g. "hash" or "block cipher".
+    pub kind: &'static str,
+
+    pub status: Status,
+    pub references: &'static [&'static str],
+}
+
+
+macro_rules! alg {
+    ($name:expr, $family:expr, $kind:expr, $status:ident, [$($r:expr),*]) => {
+        Algorithm {
+            name:       $name,
+            family:     $family,
+            kind:       $kind,
+            status:     Status::$status,
+            references: &[$($r),*],
+        }
+    };
+}
+
+
+static ALGORITHMS: &'static [Algorithm] = &[
+    // Hashes
+    alg!("MD5",            "MD5",       "hash", Broken,     ["RFC 1321", "RFC 6151"]),
+    alg!("SHA-1",          "SHA-1",     "hash", Broken,     ["FIPS 180-4", "https://shattered.io/"]),
+    alg!("SHA-256",        "SHA-2",     "hash", Acceptable, ["FIPS 180-4"]),
+    alg!("SHA-512",        "SHA-2",     "hash", Acceptable, ["FIPS 180-4"]),
+    alg!("Keccak",         "SHA-3",     "hash", Acceptable, ["FIPS 202"]),
+    alg!("BLAKE2b",        "BLAKE2",    "hash", Acceptable, ["RFC 7693"]),
+
+    // Ciphers
+    alg!("DES",            "DES",       "block cipher",  Broken,     ["FIPS 46-3 (withdrawn)", "NIST SP 800-131A Rev. 2"]),
+    alg!("Blowfish",       "Blowfish",  "block cipher",  Legacy,     ["https://www.schneier.com/academic/blowfish/", "https://sweet32.info/"]),
+    alg!("Rijndael",       "AES",       "block cipher",  Acceptable, ["FIPS 197"]),
+    alg!("RC4",            "RC4",       "stream cipher", Broken,     ["RFC 7465"]),
+    alg!("ChaCha20",       "ChaCha20",  "stream cipher", Acceptable, ["RFC 8439"]),
+
+    // Public key
+    alg!("DH",             "DH",        "key exchange",  Acceptable, ["RFC 2631"]),
+    alg!("DH-1024",        "DH",        "key exchange",  Broken,     ["RFC 2409", "https://weakdh.org/", "NIST SP 800-131A Rev. 2"]),
+    alg!("DH-1536",        "DH",        "key exchange",  Legacy,     ["RFC 3526", "NIST SP 800-131A Rev. 2"]),
+    alg!("DH-2048",        "DH",        "key exchange",  Acceptable, ["RFC 3526"]),
+    alg!("DSA",            "DSA",       "signature",     Legacy,     ["FIPS 186-5"]),
+    alg!("RSA",            "RSA",       "public key",    Acceptable, ["RFC 8017"]),
+
+    // Post-quantum
+    alg!("ML-KEM/Kyber",     "ML-KEM",  "KEM",       Acceptable, ["FIPS 203"]),
+    alg!("ML-DSA/Dilithium", "ML-DSA",  "signature", Acceptable, ["FIPS 204"]),
+    alg!("Falcon",           "Falcon",  "signature", Acceptable, ["https://falcon-sign.info/"]),
+    alg!("SLH-DSA/SPHINCS+", "SLH-DSA", "signature", Acceptable, ["FIPS 205"]),
+
+    // DigestInfo prefixes used in PKCS #1 signatures
+    alg!("PKCS MD2",       "MD2",       "signature encoding", Broken,     ["RFC 8017", "RFC 6149"]),
+    alg!("PKCS MD5",       "MD5",       "signature encoding", Broken,     ["RFC 8017", "RFC 6151"]),
+    alg!("PKCS RIPEMD160", "RIPEMD",    "signature encoding", Legacy,     ["ISO/IEC 10118-3"]),
+    alg!("PKCS Tiger",     "Tiger",     "signature encoding", Legacy,     ["https://www.cs.technion.ac.il/~biham/Reports/Tiger/"]),
+    alg!("PKCS SHA256",    "SHA-2",     "signature encoding", Acceptable, ["RFC 8017"]),
+    alg!("PKCS SHA384",    "SHA-2",     "signature encoding", Acceptable, ["RFC 8017"]),
+    alg!("PKCS SHA512",    "SHA-2",     "signature encoding", Acceptable, ["RFC 8017"]),
+
+    // Checksums aren't meant to be secure, so they can't be broken.
+    alg!("CRC-16/CCITT-FALSE", "CRC",   "checksum", Acceptable, ["https://reveng.sourceforge.io/crc-catalogue/"]),
+    alg!("CRC-16/KERMIT",      "CRC",   "checksum", Acceptable, ["https://reveng.sourceforge.io/crc-catalogue/"]),
+    alg!("CRC-16/MODBUS",      "CRC",   "checksum", Acceptable, ["https://reveng.sourceforge.io/crc-catalogue/"]),
+    alg!("CRC32",              "CRC",   "checksum", Acceptable, ["ISO/IEC 3309"]),
+    alg!("CRC-32/BZIP2",       "CRC",   "checksum", Acceptable, ["https://reveng.sourceforge.io/crc-catalogue/"]),
+    alg!("CRC-32C",            "CRC",   "checksum", Acceptable, ["RFC 3720"]),
+    alg!("CRC-64/ECMA-182",    "CRC",   "checksum", Acceptable, ["ECMA-182"]),
+    alg!("CRC-64/XZ",          "CRC",   "checksum", Acceptable, ["https://tukaani.org/xz/xz-file-format.txt"]),
+];
+
+
+/// Returns what we know about the algorithm with the given name, ignoring case.
+pub fn lookup(name: &str) -> Option<&'static Algorithm> {
+    ALGORITHMS.iter().find(|a| a.name.eq_ignore_ascii_case(name))
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_lookup() {
+    let des = lookup("des").unwrap();
+    assert_eq!(des.name, "DES");
+    assert_eq!(des.status, Status::Broken);
+
+    assert_eq!(lookup("DH-2048").unwrap().family, "DH");
+    assert!(lookup("MurmurHash3").is_none());
+
+    // Every built-in cryptographic pattern should be described.
+    for pat in ::patterns::get_patterns() {
+        if pat.category == ::patterns::Category::Crypto {
+            assert!(lookup(pat.algorithm).is_some(), "no metadata for {}", pat.algorithm);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+/// Describes an encoding alphabet that we search for shuffled versions of.
+/// Since the standard alphabets are found by the patterns in
+/// `patterns::context`, only alphabets that differ from them are reported
+/// here.
+pub struct Alphabet {
+    pub name: &'static str,
+    pub standard: &'static [u8],
+}
+
+
+pub static ALPHABETS: &'static [Alphabet] = &[
+    Alphabet {
+        name:     "Base64",
+        standard: b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/",
+    },
+    Alphabet {
+        name:     "Base64 (URL-safe)",
+        standard: b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+    },
+    Alphabet {
+        name:     "Base32",
+        standard: b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567",
+    },
+    Alphabet {
+        name:     "Base58",
+        standard: b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz",
+    },
+];
+
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AlphabetMatch {
+    pub name: &'static str,
+    pub offset: usize,
+    pub len: usize,
+}
+
+
+/// Searches the given data for permutations of each of the known alphabets -
+/// i.e. runs of bytes that contain every character of the alphabet exactly
+/// once, but not in the standard order.
+pub fn find_custom_alphabets(data: &[u8]) -> Vec<AlphabetMatch> {
+    let mut ret = vec![];
+
+    for alphabet in ALPHABETS {
+        find_permutations(data, alphabet, &mut ret);
+    }
+
+    ret.sort_by(|a, b| a.offset.cmp(&b.offset));
+    ret
+}
+
+
+fn find_permutations(data: &[u8], alphabet: &Alphabet, out: &mut Vec<AlphabetMatch>) {
+    let size = alphabet.standard.len();
+    if data.len() < size {
+        return;
+    }
+
+    let mut valid = [false; 256];
+    for &b in alphabet.standard {
+        valid[b as usize] = true;
+    }
+
+    // We slide a window of the alphabet's size over the data, keeping track of
+    // the number of bytes in it that aren't in the alphabet, and the number of
+    // repeated bytes.  If both are zero, the window is a permutation.
+    let mut counts = [0usize; 256];
+    let mut invalid = 0;
+    let mut repeats = 0;
+
+    let mut start = 0;
+    for i in 0..data.len() {
+        let b = data[i] as usize;
+        if !valid[b] {
+            invalid += 1;
+        }
+        if counts[b] > 0 {
+            repeats += 1;
+        }
+        counts[b] += 1;
+
+        if i - start + 1 > size {
+            let old = data[start] as usize;
+            counts[old] -= 1;
+            if counts[old] > 0 {
+                repeats -= 1;
+            }
+            if !valid[old] {
+                invalid -= 1;
+            }
+            start += 1;
+        }
+
+        if i - start + 1 == size && invalid == 0 && repeats == 0 {
+            if &data[start..i + 1] != alphabet.standard {
+                out.push(AlphabetMatch {
+                    name:   alphabet.name,
+                    offset: start,
+                    len:    size,
+                });
+            }
+
+            // Start again after this window, so we don't report overlapping
+            // matches.
+            counts = [0; 256];
+            start = i + 1;
+        }
+    }
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_find_custom_alphabets() {
+    let mut data = b"xxxx".to_vec();
+    data.extend(b"ZYXWVUTSRQPONMLKJIHGFEDCBAzyxwvutsrqponmlkjihgfedcba9876543210+/");
+    data.extend(b"....");
+    data.extend(b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/");
+
+    assert_eq!(find_custom_alphabets(&data), vec![
+        AlphabetMatch { name: "Base64", offset: 4, len: 64 },
+    ]);
+}
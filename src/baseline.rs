@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use rustc_serialize::json::{Json, ToJson};
+
+
+/// A finding, identified by what was found and where rather than by its
+/// offset, so that it stays the same when unrelated code moves around.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub path: String,
+    pub algorithm: String,
+    pub varname: String,
+
+    /// A hash of the match and the bytes around it (see `match_hash`), so that
+    /// a finding isn't suppressed if the code around the table changes - e.g.
+    /// when a different implementation of the algorithm is linked in.
+    pub hash: String,
+}
+
+
+/// An entry in a baseline file.  Entries written by `--write-baseline` give
+/// every field, but hand-written ones can leave some out to suppress more -
+/// e.g. every finding for an algorithm in one file.
+#[derive(Clone, Debug, Default)]
+pub struct Entry {
+    pub path: Option<String>,
+    pub algorithm: Option<String>,
+    pub varname: Option<String>,
+    pub hash: Option<String>,
+
+    /// Why the finding is expected.
+    pub justification: Option<String>,
+}
+
+impl Entry {
+    fn matches(&self, f: &Finding) -> bool {
+        fn field(want: &Option<String>, have: &str) -> bool {
+            want.as_ref().map_or(true, |w| w == have)
+        }
+
+        field(&self.path, &f.path) &&
+            field(&self.algorithm, &f.algorithm) &&
+            field(&self.varname, &f.varname) &&
+            field(&self.hash, &f.hash)
+    }
+}
+
+impl ToJson for Entry {
+    fn to_json(&self) -> Json {
+        let mut obj = BTreeMap::new();
+        for &(key, ref value) in &[("path", &self.path),
+                                   ("algorithm", &self.algorithm),
+                                   ("varname", &self.varname),
+                                   ("hash", &self.hash),
+                                   ("justification", &self.justification)] {
+            if let Some(ref v) = **value {
+                obj.insert(key.to_string(), v.to_json());
+            }
+        }
+        Json::Object(obj)
+    }
+}
+
+
+/// A set of known findings that shouldn't be reported, stored as JSON:
+///
+/// ```json
+/// {
+///   "findings": [
+///     {
+///       "path": "build/libfoo.so",
+///       "algorithm": "Rijndael",
+///       "varname": "crypto::RIJNDAEL_TE0",
+///       "hash": "5b1f9b8e2c3a7d40",
+///       "justification": "Our own AES, reviewed in 2016"
+///     },
+///     {
+///       "path": "build/libbar.so",
+///       "algorithm": "CRC32",
+///       "justification": "Only used for framing"
+///     }
+///   ]
+/// }
+/// ```
+pub struct Baseline {
+    pub entries: Vec<Entry>,
+
+    /// The number of findings that were suppressed, and the number of entries
+    /// added since the baseline was loaded.
+    pub suppressed: usize,
+    pub added: usize,
+}
+
+impl Baseline {
+    pub fn new() -> Baseline {
+        Baseline {
+            entries:    vec![],
+            suppressed: 0,
+            added:      0,
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Baseline, String> {
+        let json = try!(Json::from_str(source).map_err(|e| e.to_string()));
+        let findings = try!(json.find("findings")
+            .and_then(|f| f.as_array())
+            .ok_or("expected an object with a \"findings\" array".to_string()));
+
+        let mut ret = Baseline::new();
+        for (i, f) in findings.iter().enumerate() {
+            let obj = try!(f.as_object().ok_or(format!("finding {}: expected an object", i)));
+
+            let mut entry = Entry::default();
+            for (key, value) in obj {
+                let value = try!(value.as_string()
+                    .ok_or(format!("finding {}: {} should be a string", i, key)))
+                    .to_string();
+                match &key[..] {
+                    "path"          => entry.path = Some(value),
+                    "algorithm"     => entry.algorithm = Some(value),
+                    "varname"       => entry.varname = Some(value),
+                    "hash"          => entry.hash = Some(value),
+                    "justification" => entry.justification = Some(value),
+                    _               => return Err(format!("finding {}: unknown key {}", i, key)),
+                }
+            }
+
+            // An entry with nothing to match on would hide everything.
+            if entry.path.is_none() && entry.algorithm.is_none() && entry.varname.is_none() && entry.hash.is_none() {
+                return Err(format!("finding {}: needs at least one of path, algorithm, varname or hash", i));
+            }
+            ret.entries.push(entry);
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns whether the finding is in the baseline, counting it as
+    /// suppressed if so.
+    pub fn suppress(&mut self, f: &Finding) -> bool {
+        let found = self.entries.iter().any(|e| e.matches(f));
+        if found {
+            self.suppressed += 1;
+        }
+        found
+    }
+
+    /// Adds the finding to the baseline, unless it's already there.
+    pub fn add(&mut self, f: &Finding) {
+        if self.entries.iter().any(|e| e.matches(f)) {
+            return;
+        }
+
+        self.entries.push(Entry {
+            path:          Some(f.path.clone()),
+            algorithm:     Some(f.algorithm.clone()),
+            varname:       Some(f.varname.clone()),
+            hash:          Some(f.hash.clone()),
+            justification: None,
+        });
+        self.added += 1;
+    }
+}
+
+impl ToJson for Baseline {
+    fn to_json(&self) -> Json {
+        let mut obj = BTreeMap::new();
+        obj.insert("findings".to_string(), Json::Array(self.entries.iter().map(|e| e.to_json()).collect()));
+        Json::Object(obj)
+    }
+}
+
+
+/// Returns a hash of the given bytes, as hex.  This isn't security-sensitive,
+/// so 64-bit FNV-1a is plenty.
+pub fn content_hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x00000100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+
+/// The number of bytes either side of a match that are included in its hash.
+pub const HASH_CONTEXT: usize = 64;
+
+
+/// Returns the hash of the match at `start..end` in `data`, which also covers
+/// up to `HASH_CONTEXT` bytes either side of it - the matched bytes alone are
+/// the same for every match of a pattern.
+pub fn match_hash(data: &[u8], start: usize, end: usize) -> String {
+    let from = start.saturating_sub(HASH_CONTEXT);
+    let to = if data.len() - end > HASH_CONTEXT { end + HASH_CONTEXT } else { data.len() };
+    content_hash(&data[from..to])
+}
+
+
+/// Loads the baseline in the given file.  If `create` is set, a file that
+/// doesn't exist yet is treated as an empty baseline.
+pub fn load<P: AsRef<Path>>(path: P, create: bool) -> Result<Baseline, String> {
+    let mut source = String::new();
+    match fs::File::open(path.as_ref()) {
+        Ok(mut f) => { try!(f.read_to_string(&mut source).map_err(|e| e.to_string())); },
+        Err(ref e) if create && e.kind() == io::ErrorKind::NotFound => return Ok(Baseline::new()),
+        Err(e) => return Err(e.to_string()),
+    }
+
+    Baseline::parse(&source)
+}
+
+
+/// Writes the baseline to the given file.
+pub fn save<P: AsRef<Path>>(path: P, baseline: &Baseline) -> io::Result<()> {
+    let mut f = try!(fs::File::create(path.as_ref()));
+    writeln!(f, "{}", baseline.to_json().pretty())
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_baseline() {
+    let mut baseline = Baseline::parse(r#"{
+        "findings": [
+            { "path": "libfoo.so", "algorithm": "Rijndael", "varname": "crypto::RIJNDAEL_TE0",
+              "hash": "0123456789abcdef" },
+            { "path": "libbar.so", "algorithm": "CRC32", "justification": "framing" }
+        ]
+    }"#).unwrap();
+
+    let finding = |path: &str, algorithm: &str, varname: &str, hash: &str| Finding {
+        path:      path.to_string(),
+        algorithm: algorithm.to_string(),
+        varname:   varname.to_string(),
+        hash:      hash.to_string(),
+    };
+
+    assert!(baseline.suppress(&finding("libfoo.so", "Rijndael", "crypto::RIJNDAEL_TE0", "0123456789abcdef")));
+    assert!(!baseline.suppress(&finding("libfoo.so", "Rijndael", "crypto::RIJNDAEL_TE0", "fedcba9876543210")));
+    assert!(baseline.suppress(&finding("libbar.so", "CRC32", "crc::CRC32", "0000000000000000")));
+    assert!(!baseline.suppress(&finding("libbaz.so", "CRC32", "crc::CRC32", "0000000000000000")));
+    assert_eq!(baseline.suppressed, 2);
+
+    baseline.add(&finding("libbar.so", "CRC32", "crc::CRC32", "0000000000000000"));
+    baseline.add(&finding("libbaz.so", "CRC32", "crc::CRC32", "0000000000000000"));
+    assert_eq!(baseline.added, 1);
+
+    // Writing it out and reading it back in keeps everything.
+    let reread = Baseline::parse(&baseline.to_json().to_string()).unwrap();
+    assert_eq!(reread.entries.len(), 3);
+    assert_eq!(reread.entries[1].justification, Some("framing".to_string()));
+    assert_eq!(reread.entries[1].varname, None);
+
+    assert_eq!(content_hash(b""), "cbf29ce484222325");
+    assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+
+    // The same table in different surroundings hashes differently.
+    let mut data = vec![0u8; 200];
+    data[100..104].copy_from_slice(b"\x63\x7c\x77\x7b");
+    assert_eq!(match_hash(&data, 100, 104), content_hash(&data[36..168]));
+    assert_eq!(match_hash(&data[90..110], 10, 14), content_hash(&data[90..110]));
+    let before = match_hash(&data, 100, 104);
+    data[50] = 1;
+    assert!(match_hash(&data, 100, 104) != before);
+
+    assert!(Baseline::parse(r#"{"findings": [{"justification": "everything"}]}"#).is_err());
+}
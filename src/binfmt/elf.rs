@@ -0,0 +1,465 @@
+use super::{entry_offset, Binary, Format, Import, MappedFile, Machine, Reader, Section, Segment};
+
+
+const ET_CORE: u16 = 4;
+
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOBITS: u32 = 8;
+const SHT_DYNSYM: u32 = 11;
+const SHF_EXECINSTR: u64 = 0x4;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 0x1;
+
+const NT_FILE: u32 = 0x46494c45;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+
+
+/// The offsets of the fields that we need, which differ between 32- and 64-bit
+/// ELF files.
+struct Layout {
+    is64: bool,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_phentsize: u64,
+    e_phnum: u64,
+    e_shentsize: u64,
+    e_shnum: u64,
+    e_shstrndx: u64,
+}
+
+
+static LAYOUT_32: Layout = Layout {
+    is64: false,
+    e_phoff: 0x1C, e_shoff: 0x20,
+    e_phentsize: 0x2A, e_phnum: 0x2C,
+    e_shentsize: 0x2E, e_shnum: 0x30, e_shstrndx: 0x32,
+};
+
+static LAYOUT_64: Layout = Layout {
+    is64: true,
+    e_phoff: 0x20, e_shoff: 0x28,
+    e_phentsize: 0x36, e_phnum: 0x38,
+    e_shentsize: 0x3A, e_shnum: 0x3C, e_shstrndx: 0x3E,
+};
+
+
+struct RawSection {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    address: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+
+struct Elf<'a> {
+    rdr: Reader<'a>,
+    layout: &'static Layout,
+}
+
+
+impl<'a> Elf<'a> {
+    /// Reads a word - 4 bytes for 32-bit files, and 8 bytes for 64-bit files.
+    fn word(&self, off: u64) -> Option<u64> {
+        if self.layout.is64 {
+            self.rdr.u64(off)
+        } else {
+            self.rdr.u32(off).map(|v| v as u64)
+        }
+    }
+
+    fn section_headers(&self) -> Option<Vec<RawSection>> {
+        let shoff = try_opt!(self.word(self.layout.e_shoff));
+        let shentsize = try_opt!(self.rdr.u16(self.layout.e_shentsize)) as u64;
+        let shnum = try_opt!(self.rdr.u16(self.layout.e_shnum)) as u64;
+
+        let entsize = if self.layout.is64 { 0x40 } else { 0x28 };
+
+        let mut ret = vec![];
+        for i in 0..shnum {
+            // Checking that the whole entry is there means that the offsets of
+            // its fields can't overflow.
+            let base = try_opt!(entry_offset(shoff, i, shentsize));
+            try_opt!(self.rdr.slice(base, entsize));
+
+            let sect = if self.layout.is64 {
+                RawSection {
+                    name:    try_opt!(self.rdr.u32(base)),
+                    kind:    try_opt!(self.rdr.u32(base + 0x04)),
+                    flags:   try_opt!(self.rdr.u64(base + 0x08)),
+                    address: try_opt!(self.rdr.u64(base + 0x10)),
+                    offset:  try_opt!(self.rdr.u64(base + 0x18)),
+                    size:    try_opt!(self.rdr.u64(base + 0x20)),
+                    link:    try_opt!(self.rdr.u32(base + 0x28)),
+                }
+            } else {
+                RawSection {
+                    name:    try_opt!(self.rdr.u32(base)),
+                    kind:    try_opt!(self.rdr.u32(base + 0x04)),
+                    flags:   try_opt!(self.rdr.u32(base + 0x08)) as u64,
+                    address: try_opt!(self.rdr.u32(base + 0x0C)) as u64,
+                    offset:  try_opt!(self.rdr.u32(base + 0x10)) as u64,
+                    size:    try_opt!(self.rdr.u32(base + 0x14)) as u64,
+                    link:    try_opt!(self.rdr.u32(base + 0x18)),
+                }
+            };
+            ret.push(sect);
+        }
+
+        Some(ret)
+    }
+
+    /// Returns the `PT_LOAD` segments, and the `PT_NOTE` segments as (offset,
+    /// size) pairs.
+    fn segments(&self) -> Option<(Vec<Segment>, Vec<(u64, u64)>)> {
+        let phoff = try_opt!(self.word(self.layout.e_phoff));
+        let phentsize = try_opt!(self.rdr.u16(self.layout.e_phentsize)) as u64;
+        let phnum = try_opt!(self.rdr.u16(self.layout.e_phnum)) as u64;
+
+        let entsize = if self.layout.is64 { 0x38 } else { 0x20 };
+
+        let mut ret = vec![];
+        let mut notes = vec![];
+        for i in 0..phnum {
+            let base = try_opt!(entry_offset(phoff, i, phentsize));
+            try_opt!(self.rdr.slice(base, entsize));
+
+            let (kind, flags, offset, address, size) = if self.layout.is64 {
+                (try_opt!(self.rdr.u32(base)),
+                 try_opt!(self.rdr.u32(base + 0x04)),
+                 try_opt!(self.rdr.u64(base + 0x08)),
+                 try_opt!(self.rdr.u64(base + 0x10)),
+                 try_opt!(self.rdr.u64(base + 0x20)))
+            } else {
+                (try_opt!(self.rdr.u32(base)),
+                 try_opt!(self.rdr.u32(base + 0x18)),
+                 try_opt!(self.rdr.u32(base + 0x04)) as u64,
+                 try_opt!(self.rdr.u32(base + 0x08)) as u64,
+                 try_opt!(self.rdr.u32(base + 0x10)) as u64)
+            };
+
+            if kind == PT_LOAD {
+                ret.push(Segment {
+                    offset:     offset,
+                    size:       size,
+                    address:    address,
+                    executable: flags & PF_X != 0,
+                });
+            } else if kind == PT_NOTE {
+                notes.push((offset, size));
+            }
+        }
+
+        Some((ret, notes))
+    }
+
+    /// Returns the files listed in the `NT_FILE` note in the given note
+    /// segment, which core dumps use to record the files that were mapped.
+    fn mapped_files(&self, offset: u64, size: u64) -> Vec<MappedFile> {
+        let align4 = |n: u64| (n + 3) & !3;
+        let word = if self.layout.is64 { 8 } else { 4 };
+
+        let mut ret = vec![];
+        let end = match offset.checked_add(size) {
+            Some(e) => e,
+            None    => return ret,
+        };
+
+        let mut off = offset;
+        while off.checked_add(12).map_or(false, |o| o <= end) {
+            let (namesz, descsz, kind) = match (self.rdr.u32(off), self.rdr.u32(off + 4), self.rdr.u32(off + 8)) {
+                (Some(n), Some(d), Some(k)) => (n as u64, d as u64, k),
+                _                           => break,
+            };
+            let desc = match off.checked_add(12 + align4(namesz)) {
+                Some(d) => d,
+                None    => break,
+            };
+            off = match desc.checked_add(align4(descsz)) {
+                Some(o) => o,
+                None    => break,
+            };
+
+            if kind != NT_FILE {
+                continue;
+            }
+
+            // The note is a count and page size, followed by a (start, end,
+            // page offset) for each file, and then all of the file names.
+            let (count, page_size) = match (self.word(desc), self.word(desc + word)) {
+                (Some(c), Some(p)) => (c, p),
+                _                  => break,
+            };
+
+            // If the names' offset doesn't overflow, then neither does the
+            // offset of any entry before them.
+            let mut name = match entry_offset(desc, count, 3 * word).and_then(|n| n.checked_add(2 * word)) {
+                Some(n) => n,
+                None    => break,
+            };
+            for i in 0..count {
+                let entry = desc + 2 * word + i * 3 * word;
+                let (start, end, page) = match (self.word(entry), self.word(entry + word), self.word(entry + 2 * word)) {
+                    (Some(s), Some(e), Some(p)) => (s, e, p),
+                    _                           => break,
+                };
+                let (path, offset) = match (self.rdr.cstr(name), page.checked_mul(page_size)) {
+                    (Some(p), Some(o)) => (p, o),
+                    _                  => break,
+                };
+                name += path.len() as u64 + 1;
+
+                ret.push(MappedFile {
+                    start:  start,
+                    end:    end,
+                    offset: offset,
+                    path:   path,
+                });
+            }
+        }
+
+        ret
+    }
+
+    /// Returns the names of the undefined symbols in the given symbol table -
+    /// i.e. the ones that are imported.
+    fn undefined_symbols(&self, symtab: &RawSection, strtab: &RawSection) -> Vec<String> {
+        let entsize = if self.layout.is64 { 24 } else { 16 };
+        let shndx_off = if self.layout.is64 { 6 } else { 14 };
+
+        let mut ret = vec![];
+        for i in 0..symtab.size / entsize {
+            let base = match entry_offset(symtab.offset, i, entsize) {
+                Some(b) if self.rdr.slice(b, entsize as usize).is_some() => b,
+                _                                                        => break,
+            };
+
+            let (name, shndx) = match (self.rdr.u32(base), self.rdr.u16(base + shndx_off)) {
+                (Some(n), Some(s)) => (n, s),
+                _                  => break,
+            };
+
+            if name == 0 || shndx != 0 {
+                continue;
+            }
+
+            if let Some(s) = strtab.offset.checked_add(name as u64).and_then(|o| self.rdr.cstr(o)) {
+                ret.push(s);
+            }
+        }
+
+        ret
+    }
+
+    /// Returns the `DT_NEEDED` entries in the given dynamic section.
+    fn needed(&self, dynamic: &RawSection, strtab: &RawSection) -> Vec<String> {
+        let entsize = if self.layout.is64 { 16 } else { 8 };
+        let valsize = entsize / 2;
+
+        let mut ret = vec![];
+        for i in 0..dynamic.size / entsize {
+            let base = match entry_offset(dynamic.offset, i, entsize) {
+                Some(b) if self.rdr.slice(b, entsize as usize).is_some() => b,
+                _                                                        => break,
+            };
+
+            let (tag, val) = match (self.word(base), self.word(base + valsize)) {
+                (Some(t), Some(v)) => (t, v),
+                _                  => break,
+            };
+
+            if tag == DT_NULL {
+                break;
+            }
+            if tag == DT_NEEDED {
+                if let Some(s) = strtab.offset.checked_add(val).and_then(|o| self.rdr.cstr(o)) {
+                    ret.push(s);
+                }
+            }
+        }
+
+        ret
+    }
+}
+
+
+pub fn parse(data: &[u8]) -> Option<Binary> {
+    let layout = match data.get(4) {
+        Some(&1) => &LAYOUT_32,
+        Some(&2) => &LAYOUT_64,
+        _        => return None,
+    };
+    let little_endian = match data.get(5) {
+        Some(&1) => true,
+        Some(&2) => false,
+        _        => return None,
+    };
+
+    let elf = Elf {
+        rdr: Reader { data: data, little_endian: little_endian },
+        layout: layout,
+    };
+
+    let core = try_opt!(elf.rdr.u16(0x10)) == ET_CORE;
+    let machine = match try_opt!(elf.rdr.u16(0x12)) {
+        3   => Machine::X86,
+        62  => Machine::X86_64,
+        40  => Machine::Arm,
+        183 => Machine::Aarch64,
+        243 if layout.is64 => Machine::RiscV64,
+        243 => Machine::RiscV32,
+        _   => Machine::Unknown,
+    };
+
+    // Stripped binaries may not have any section headers, so don't give up if
+    // we can't read them.
+    let raw_sections = elf.section_headers().unwrap_or(vec![]);
+    let shstrndx = elf.rdr.u16(layout.e_shstrndx).unwrap_or(0) as usize;
+
+    let mut sections = vec![];
+    let mut imports = vec![];
+    let mut needed = vec![];
+
+    for sect in raw_sections.iter() {
+        let name = raw_sections.get(shstrndx)
+            .and_then(|strs| strs.offset.checked_add(sect.name as u64))
+            .and_then(|off| elf.rdr.cstr(off))
+            .unwrap_or(String::new());
+
+        if sect.kind != SHT_NOBITS {
+            sections.push(Section {
+                name:       name,
+                offset:     sect.offset,
+                size:       sect.size,
+                address:    sect.address,
+                executable: sect.flags & SHF_EXECINSTR != 0,
+            });
+        }
+
+        let strtab = match raw_sections.get(sect.link as usize) {
+            Some(s) => s,
+            None    => continue,
+        };
+
+        if sect.kind == SHT_DYNSYM {
+            for name in elf.undefined_symbols(sect, strtab) {
+                imports.push(Import {
+                    library: None,
+                    name:    name,
+                });
+            }
+        } else if sect.kind == SHT_DYNAMIC {
+            needed.extend(elf.needed(sect, strtab));
+        }
+    }
+
+    let (segments, notes) = elf.segments().unwrap_or((vec![], vec![]));
+
+    let mut mapped_files = vec![];
+    if core {
+        for &(offset, size) in notes.iter() {
+            mapped_files.extend(elf.mapped_files(offset, size));
+        }
+    }
+
+    Some(Binary {
+        format:   Format::Elf,
+        machine:  machine,
+        sections: sections,
+        segments: segments,
+        needed:   needed,
+        imports:  imports,
+
+        core:         core,
+        mapped_files: mapped_files,
+    })
+}
+
+// ----------------------------------------------------------------------
+
+/// Builds a minimal 64-bit little-endian shared object, which imports
+/// `AES_encrypt` from libcrypto and defines `memcpy`.
+#[cfg(test)]
+fn test_elf() -> Vec<u8> {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    let mut data = vec![0u8; 0x280];
+    data[..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+    LittleEndian::write_u16(&mut data[0x10..], 3);
+    LittleEndian::write_u16(&mut data[0x12..], 62);
+    LittleEndian::write_u64(&mut data[0x28..], 0x140);
+    LittleEndian::write_u16(&mut data[0x3A..], 0x40);
+    LittleEndian::write_u16(&mut data[0x3C..], 5);
+    LittleEndian::write_u16(&mut data[0x3E..], 4);
+
+    let dynstr = b"\0libcrypto.so.3\0AES_encrypt\0memcpy\0";
+    data[0x40..0x40 + dynstr.len()].copy_from_slice(dynstr);
+
+    // .dynsym: the null symbol, then AES_encrypt (undefined) and memcpy.
+    LittleEndian::write_u32(&mut data[0x98..], 16);
+    LittleEndian::write_u32(&mut data[0xB0..], 28);
+    LittleEndian::write_u16(&mut data[0xB6..], 1);
+
+    // .dynamic: DT_NEEDED libcrypto.so.3, then DT_NULL.
+    LittleEndian::write_u64(&mut data[0xE0..], DT_NEEDED);
+    LittleEndian::write_u64(&mut data[0xE8..], 1);
+
+    let shstrtab = b"\0.dynstr\0.dynsym\0.dynamic\0.shstrtab\0";
+    data[0x100..0x100 + shstrtab.len()].copy_from_slice(shstrtab);
+
+    // Section headers, after the null one: (name, type, offset, size, link).
+    let sections = [(1, 3, 0x40, 0x40, 0), (9, SHT_DYNSYM, 0x80, 0x48, 1),
+                    (17, SHT_DYNAMIC, 0xE0, 0x20, 1), (26, 3, 0x100, 0x40, 0)];
+    for (i, &(name, kind, offset, size, link)) in sections.iter().enumerate() {
+        let base = 0x140 + (i + 1) * 0x40;
+        LittleEndian::write_u32(&mut data[base..], name);
+        LittleEndian::write_u32(&mut data[base + 0x04..], kind);
+        LittleEndian::write_u64(&mut data[base + 0x18..], offset);
+        LittleEndian::write_u64(&mut data[base + 0x20..], size);
+        LittleEndian::write_u32(&mut data[base + 0x28..], link);
+    }
+
+    data
+}
+
+#[test]
+fn test_parse() {
+    let bin = parse(&test_elf()).unwrap();
+
+    assert_eq!((bin.format, bin.machine, bin.core), (Format::Elf, Machine::X86_64, false));
+    assert_eq!(bin.sections.iter().map(|s| &s.name[..]).collect::<Vec<_>>(),
+               vec!["", ".dynstr", ".dynsym", ".dynamic", ".shstrtab"]);
+    assert_eq!(bin.needed, vec!["libcrypto.so.3"]);
+    assert_eq!(bin.imports.iter().map(|i| &i.name[..]).collect::<Vec<_>>(), vec!["AES_encrypt"]);
+}
+
+#[test]
+fn test_parse_malformed() {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    // Truncated files parse as far as they go.
+    let data = test_elf();
+    let bin = parse(&data[..0x30]).unwrap();
+    assert!(bin.sections.is_empty() && bin.imports.is_empty());
+    assert!(parse(&data[..0x10]).is_none());
+
+    // Offsets that overflow when added to are ignored, rather than panicking
+    // or wrapping around.
+    let mut data = test_elf();
+    LittleEndian::write_u64(&mut data[0x28..], !0 - 0x10);
+    assert!(parse(&data).unwrap().sections.is_empty());
+
+    let mut data = test_elf();
+    LittleEndian::write_u64(&mut data[0x140 + 2 * 0x40 + 0x18..], !0 - 8);
+    LittleEndian::write_u64(&mut data[0x140 + 3 * 0x40 + 0x18..], !0);
+    LittleEndian::write_u64(&mut data[0x140 + 4 * 0x40 + 0x18..], !0 - 4);
+    let bin = parse(&data).unwrap();
+    assert!(bin.imports.is_empty() && bin.needed.is_empty());
+    assert!(bin.sections.iter().all(|s| s.name.is_empty()));
+}
@@ -0,0 +1,183 @@
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+
+mod elf;
+mod pe;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Elf,
+    Pe,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Machine {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    RiscV32,
+    RiscV64,
+    Unknown,
+}
+
+
+/// A named section of the binary.
+#[derive(Debug)]
+pub struct Section {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+    pub address: u64,
+    pub executable: bool,
+}
+
+
+/// A range of the file that is mapped into memory when the binary is loaded -
+/// i.e. an ELF `PT_LOAD` segment, or a PE section.
+#[derive(Debug)]
+pub struct Segment {
+    pub offset: u64,
+    pub size: u64,
+    pub address: u64,
+    pub executable: bool,
+}
+
+
+/// A symbol that the binary imports from a shared library.
+#[derive(Debug)]
+pub struct Import {
+    /// The library that the symbol is imported from, if known.  ELF files
+    /// don't record this, only the list of libraries that are needed.
+    pub library: Option<String>,
+    pub name: String,
+}
+
+
+/// A file that was mapped into memory when a core dump was taken.
+#[derive(Debug)]
+pub struct MappedFile {
+    pub start: u64,
+    pub end: u64,
+
+    /// The offset in the file that is mapped at `start`.
+    pub offset: u64,
+    pub path: String,
+}
+
+
+/// The parts of an executable file that we're interested in.
+#[derive(Debug)]
+pub struct Binary {
+    pub format: Format,
+    pub machine: Machine,
+    pub sections: Vec<Section>,
+    pub segments: Vec<Segment>,
+
+    /// The shared libraries that this binary depends on.
+    pub needed: Vec<String>,
+    pub imports: Vec<Import>,
+
+    /// Whether this is a core dump rather than an executable, and if so, the
+    /// files that were mapped into the process.
+    pub core: bool,
+    pub mapped_files: Vec<MappedFile>,
+}
+
+
+impl Binary {
+    /// Returns the virtual address that the given file offset is loaded at, if
+    /// it's part of a segment.
+    pub fn address_of(&self, offset: u64) -> Option<u64> {
+        self.segments.iter()
+            .find(|s| offset >= s.offset && offset - s.offset < s.size)
+            .and_then(|s| s.address.checked_add(offset - s.offset))
+    }
+}
+
+
+/// Attempts to parse the given data as an ELF or PE file.  Returns `None` if
+/// the data is neither, or is too malformed to make sense of.
+pub fn parse(data: &[u8]) -> Option<Binary> {
+    if data.starts_with(b"\x7fELF") {
+        elf::parse(data)
+    } else if data.starts_with(b"MZ") {
+        pe::parse(data)
+    } else {
+        None
+    }
+}
+
+// ----------------------------------------------------------------------
+
+/// Returns the offset of entry `index` in a table of `size`-byte entries that
+/// starts at `base`, or `None` if that overflows.  All of these come straight
+/// from the file, so they can't be trusted.
+fn entry_offset(base: u64, index: u64, size: u64) -> Option<u64> {
+    index.checked_mul(size).and_then(|off| base.checked_add(off))
+}
+
+
+/// Helper for bounds-checked reads of integers from a buffer with a given
+/// endianness.
+struct Reader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+
+impl<'a> Reader<'a> {
+    fn slice(&self, off: u64, len: usize) -> Option<&'a [u8]> {
+        if off > self.data.len() as u64 {
+            return None;
+        }
+
+        let off = off as usize;
+        if off.checked_add(len).map_or(true, |end| end > self.data.len()) {
+            return None;
+        }
+
+        Some(&self.data[off..off + len])
+    }
+
+    fn u8(&self, off: u64) -> Option<u8> {
+        self.slice(off, 1).map(|b| b[0])
+    }
+
+    fn u16(&self, off: u64) -> Option<u16> {
+        self.slice(off, 2).map(|b| if self.little_endian {
+            LittleEndian::read_u16(b)
+        } else {
+            BigEndian::read_u16(b)
+        })
+    }
+
+    fn u32(&self, off: u64) -> Option<u32> {
+        self.slice(off, 4).map(|b| if self.little_endian {
+            LittleEndian::read_u32(b)
+        } else {
+            BigEndian::read_u32(b)
+        })
+    }
+
+    fn u64(&self, off: u64) -> Option<u64> {
+        self.slice(off, 8).map(|b| if self.little_endian {
+            LittleEndian::read_u64(b)
+        } else {
+            BigEndian::read_u64(b)
+        })
+    }
+
+    /// Reads a NUL-terminated string starting at the given offset.
+    fn cstr(&self, off: u64) -> Option<String> {
+        if off >= self.data.len() as u64 {
+            return None;
+        }
+        let off = off as usize;
+
+        let bytes = &self.data[off..];
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Some(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+}
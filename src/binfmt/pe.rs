@@ -0,0 +1,319 @@
+use std::collections::HashSet;
+
+use super::{Binary, Format, Import, Machine, Reader, Section, Segment};
+
+
+const IMAGE_SCN_CNT_CODE: u32 = 0x00000020;
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x20000000;
+
+const IMAGE_DIRECTORY_ENTRY_IMPORT: u64 = 1;
+
+
+struct RawSection {
+    name: String,
+    virtual_size: u32,
+    virtual_address: u32,
+    raw_size: u32,
+    raw_offset: u32,
+    characteristics: u32,
+}
+
+
+/// Converts a relative virtual address into an offset in the file.
+fn rva_to_offset(sections: &[RawSection], rva: u32) -> Option<u64> {
+    for sect in sections {
+        let size = if sect.virtual_size > sect.raw_size {
+            sect.virtual_size
+        } else {
+            sect.raw_size
+        };
+
+        if rva >= sect.virtual_address && rva - sect.virtual_address < size {
+            let delta = rva - sect.virtual_address;
+            if delta >= sect.raw_size {
+                // In the section, but not backed by the file.
+                return None;
+            }
+
+            return Some(sect.raw_offset as u64 + delta as u64);
+        }
+    }
+
+    None
+}
+
+
+pub fn parse(data: &[u8]) -> Option<Binary> {
+    let rdr = Reader { data: data, little_endian: true };
+
+    // Apart from the image base, every offset and address in the headers is a
+    // 16- or 32-bit field, so sums of them can't overflow a u64.
+    let pe_off = try_opt!(rdr.u32(0x3C)) as u64;
+    if try_opt!(rdr.slice(pe_off, 4)) != b"PE\0\0" {
+        return None;
+    }
+
+    let coff = pe_off + 4;
+    let machine = match try_opt!(rdr.u16(coff)) {
+        0x014c          => Machine::X86,
+        0x8664          => Machine::X86_64,
+        0x01c0 | 0x01c4 => Machine::Arm,
+        0xaa64          => Machine::Aarch64,
+        0x5032          => Machine::RiscV32,
+        0x5064          => Machine::RiscV64,
+        _               => Machine::Unknown,
+    };
+    let num_sections = try_opt!(rdr.u16(coff + 2)) as u64;
+    let opt_size = try_opt!(rdr.u16(coff + 16)) as u64;
+
+    let opt = coff + 20;
+    let is64 = match try_opt!(rdr.u16(opt)) {
+        0x10b => false,
+        0x20b => true,
+        _     => return None,
+    };
+
+    let image_base = if is64 {
+        try_opt!(rdr.u64(opt + 24))
+    } else {
+        try_opt!(rdr.u32(opt + 28)) as u64
+    };
+    let (num_dirs, dirs) = if is64 {
+        (try_opt!(rdr.u32(opt + 108)) as u64, opt + 112)
+    } else {
+        (try_opt!(rdr.u32(opt + 92)) as u64, opt + 96)
+    };
+
+    let mut raw_sections = vec![];
+    for i in 0..num_sections {
+        let base = opt + opt_size + i * 40;
+        let name = try_opt!(rdr.slice(base, 8));
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(8);
+
+        raw_sections.push(RawSection {
+            name:            String::from_utf8_lossy(&name[..name_len]).into_owned(),
+            virtual_size:    try_opt!(rdr.u32(base + 8)),
+            virtual_address: try_opt!(rdr.u32(base + 12)),
+            raw_size:        try_opt!(rdr.u32(base + 16)),
+            raw_offset:      try_opt!(rdr.u32(base + 20)),
+            characteristics: try_opt!(rdr.u32(base + 36)),
+        });
+    }
+
+    let mut sections = vec![];
+    let mut segments = vec![];
+    for sect in raw_sections.iter() {
+        let executable = sect.characteristics & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) != 0;
+        let address = try_opt!(image_base.checked_add(sect.virtual_address as u64));
+
+        sections.push(Section {
+            name:       sect.name.clone(),
+            offset:     sect.raw_offset as u64,
+            size:       sect.raw_size as u64,
+            address:    address,
+            executable: executable,
+        });
+        segments.push(Segment {
+            offset:     sect.raw_offset as u64,
+            size:       sect.raw_size as u64,
+            address:    address,
+            executable: executable,
+        });
+    }
+
+    let mut needed = vec![];
+    let mut imports = vec![];
+
+    if num_dirs > IMAGE_DIRECTORY_ENTRY_IMPORT {
+        let import_rva = rdr.u32(dirs + IMAGE_DIRECTORY_ENTRY_IMPORT * 8).unwrap_or(0);
+        let import_size = rdr.u32(dirs + IMAGE_DIRECTORY_ENTRY_IMPORT * 8 + 4).unwrap_or(0);
+
+        if import_rva != 0 {
+            parse_imports(&rdr, &raw_sections, import_rva, import_size, is64, &mut needed, &mut imports);
+        }
+    }
+
+    Some(Binary {
+        format:   Format::Pe,
+        machine:  machine,
+        sections: sections,
+        segments: segments,
+        needed:   needed,
+        imports:  imports,
+
+        core:         false,
+        mapped_files: vec![],
+    })
+}
+
+
+fn parse_imports(rdr: &Reader, sections: &[RawSection], import_rva: u32, import_size: u32, is64: bool,
+                 needed: &mut Vec<String>, imports: &mut Vec<Import>)
+{
+    let mut desc = match rva_to_offset(sections, import_rva) {
+        Some(off) => off,
+        None      => return,
+    };
+    let end = desc + import_size as u64;
+
+    // Descriptors can share a thunk table, and every thunk can name the same
+    // string, so a small file could otherwise produce a huge list of imports.
+    let max_imports = rdr.data.len() / 4;
+    let mut walked = HashSet::new();
+
+    while desc + 20 <= end {
+        let (lookup_rva, name_rva, iat_rva) = match (rdr.u32(desc), rdr.u32(desc + 12), rdr.u32(desc + 16)) {
+            (Some(l), Some(n), Some(i)) => (l, n, i),
+            _                           => return,
+        };
+        if lookup_rva == 0 && name_rva == 0 && iat_rva == 0 {
+            return;
+        }
+        desc += 20;
+
+        let library = match rva_to_offset(sections, name_rva).and_then(|off| rdr.cstr(off)) {
+            Some(l) => l,
+            None    => continue,
+        };
+
+        // Prefer the import lookup table, since the IAT may have been bound.
+        let thunks_rva = if lookup_rva != 0 { lookup_rva } else { iat_rva };
+        if !walked.insert(thunks_rva) {
+            continue;
+        }
+        let mut thunk = match rva_to_offset(sections, thunks_rva) {
+            Some(off) => off,
+            None      => continue,
+        };
+
+        loop {
+            let (entry, by_ordinal) = if is64 {
+                match rdr.u64(thunk) {
+                    Some(e) => (e & 0x7FFFFFFF, e & (1 << 63) != 0),
+                    None    => break,
+                }
+            } else {
+                match rdr.u32(thunk) {
+                    Some(e) => ((e & 0x7FFFFFFF) as u64, e & (1 << 31) != 0),
+                    None    => break,
+                }
+            };
+            if entry == 0 && !by_ordinal {
+                break;
+            }
+            if imports.len() >= max_imports {
+                return;
+            }
+            thunk += if is64 { 8 } else { 4 };
+
+            let name = if by_ordinal {
+                format!("#{}", entry & 0xFFFF)
+            } else {
+                // Skip the two-byte hint before the name.
+                match rva_to_offset(sections, entry as u32).and_then(|off| rdr.cstr(off + 2)) {
+                    Some(n) => n,
+                    None    => continue,
+                }
+            };
+
+            imports.push(Import {
+                library: Some(library.clone()),
+                name:    name,
+            });
+        }
+
+        needed.push(library);
+    }
+}
+
+// ----------------------------------------------------------------------
+
+/// Builds a minimal PE32+ file with one section, which imports `BCryptEncrypt`
+/// and ordinal 5 from bcrypt.dll.
+#[cfg(test)]
+fn test_pe() -> Vec<u8> {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    let mut data = vec![0u8; 0x400];
+    data[..2].copy_from_slice(b"MZ");
+    LittleEndian::write_u32(&mut data[0x3C..], 0x40);
+    data[0x40..0x44].copy_from_slice(b"PE\0\0");
+    LittleEndian::write_u16(&mut data[0x44..], 0x8664);
+    LittleEndian::write_u16(&mut data[0x46..], 1);
+    LittleEndian::write_u16(&mut data[0x54..], 0xF0);
+
+    // The optional header, with the image base and the import directory.
+    LittleEndian::write_u16(&mut data[0x58..], 0x20b);
+    LittleEndian::write_u64(&mut data[0x58 + 24..], 0x140000000);
+    LittleEndian::write_u32(&mut data[0x58 + 108..], 16);
+    LittleEndian::write_u32(&mut data[0x58 + 112 + 8..], 0x1000);
+    LittleEndian::write_u32(&mut data[0x58 + 112 + 12..], 40);
+
+    // .idata, mapping RVA 0x1000 to file offset 0x200.
+    data[0x148..0x14E].copy_from_slice(b".idata");
+    LittleEndian::write_u32(&mut data[0x148 + 8..], 0x200);
+    LittleEndian::write_u32(&mut data[0x148 + 12..], 0x1000);
+    LittleEndian::write_u32(&mut data[0x148 + 16..], 0x200);
+    LittleEndian::write_u32(&mut data[0x148 + 20..], 0x200);
+    LittleEndian::write_u32(&mut data[0x148 + 36..], 0x40000040);
+
+    // One import descriptor, then the terminating empty one.
+    LittleEndian::write_u32(&mut data[0x200..], 0x1040);
+    LittleEndian::write_u32(&mut data[0x200 + 12..], 0x1080);
+    LittleEndian::write_u32(&mut data[0x200 + 16..], 0x1040);
+
+    LittleEndian::write_u64(&mut data[0x240..], 0x10A0);
+    LittleEndian::write_u64(&mut data[0x248..], (1 << 63) | 5);
+    data[0x280..0x28B].copy_from_slice(b"bcrypt.dll\0");
+    data[0x2A2..0x2B0].copy_from_slice(b"BCryptEncrypt\0");
+
+    data
+}
+
+#[test]
+fn test_parse() {
+    let bin = parse(&test_pe()).unwrap();
+
+    assert_eq!((bin.format, bin.machine), (Format::Pe, Machine::X86_64));
+    assert_eq!(bin.sections[0].name, ".idata");
+    assert_eq!(bin.address_of(0x210), Some(0x140001010));
+    assert_eq!(bin.needed, vec!["bcrypt.dll"]);
+    assert_eq!(bin.imports.iter().map(|i| (i.library.as_ref().unwrap(), &i.name[..])).collect::<Vec<_>>(),
+               vec![(&"bcrypt.dll".to_string(), "BCryptEncrypt"), (&"bcrypt.dll".to_string(), "#5")]);
+}
+
+#[test]
+fn test_parse_malformed() {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    let data = test_pe();
+    assert!(parse(&data[..0x100]).is_none());
+
+    let mut data = test_pe();
+    LittleEndian::write_u32(&mut data[0x3C..], !0);
+    assert!(parse(&data).is_none());
+
+    // An image base that overflows when the section is added to it.
+    let mut data = test_pe();
+    LittleEndian::write_u64(&mut data[0x58 + 24..], !0 - 0x10);
+    assert!(parse(&data).is_none());
+}
+
+#[test]
+fn test_parse_repeated_imports() {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    // A second descriptor with the same thunks only adds its library once.
+    let mut data = test_pe();
+    LittleEndian::write_u32(&mut data[0x58 + 112 + 12..], 60);
+    let desc = data[0x200..0x214].to_vec();
+    data[0x214..0x228].copy_from_slice(&desc);
+    let bin = parse(&data).unwrap();
+    assert_eq!(bin.needed, vec!["bcrypt.dll"]);
+    assert_eq!(bin.imports.len(), 2);
+
+    // Descriptors past the end of the import directory are ignored.
+    let mut data = test_pe();
+    LittleEndian::write_u32(&mut data[0x58 + 112 + 12..], 19);
+    assert!(parse(&data).unwrap().imports.is_empty());
+}
@@ -0,0 +1,110 @@
+use binfmt;
+
+use super::{slice, Entry};
+
+
+/// Returns whether the data is an ELF core dump.
+pub fn is_core(data: &[u8]) -> bool {
+    if !data.starts_with(b"\x7fELF") || data.len() < 0x12 {
+        return false;
+    }
+
+    // e_type, in the file's byte order
+    let kind = if data[5] == 2 {
+        (data[0x10] as u16) << 8 | data[0x11] as u16
+    } else {
+        (data[0x11] as u16) << 8 | data[0x10] as u16
+    };
+    kind == 4
+}
+
+
+/// Returns the memory segments in an ELF core dump, based at their virtual
+/// addresses.  Segments are named after the file that was mapped there, if
+/// any, and the offset in that file that the segment starts at - so that
+/// `0x7f0000001000:/lib/libc.so.6+0x1000` holds the contents of the file from
+/// 0x1000 onwards.
+pub fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    let bin = try_opt!(binfmt::parse(data));
+
+    let mut ret = vec![];
+    for seg in bin.segments.iter() {
+        // Segments that weren't dumped (e.g. read-only file mappings) have no
+        // data in the file.
+        if seg.size == 0 {
+            continue;
+        }
+
+        let contents = match slice(data, seg.offset as usize, seg.size as usize) {
+            Some(c) => c,
+            None    => {
+                warn!("core segment at 0x{:x} is truncated, skipping", seg.address);
+                continue;
+            },
+        };
+
+        let owner = bin.mapped_files.iter()
+            .find(|f| seg.address >= f.start && seg.address < f.end)
+            .and_then(|f| f.offset.checked_add(seg.address - f.start).map(|off| format!("{}+0x{:x}", f.path, off)))
+            .unwrap_or("[anon]".to_string());
+
+        let mut entry = Entry::new(format!("0x{:x}:{}", seg.address, owner), contents, None);
+        entry.base = seg.address;
+        ret.push(entry);
+    }
+
+    Some(ret)
+}
+
+// ----------------------------------------------------------------------
+
+/// Builds a 64-bit little-endian core dump with two segments: one from page 1
+/// of `/bin/x`, which is mapped from 0x400000, and one anonymous.
+#[cfg(test)]
+fn test_core() -> Vec<u8> {
+    use byteorder::{ByteOrder, LittleEndian};
+
+    let mut data = vec![0u8; 0x150];
+    data[..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+    LittleEndian::write_u16(&mut data[0x10..], 4);
+    LittleEndian::write_u16(&mut data[0x12..], 62);
+    LittleEndian::write_u64(&mut data[0x20..], 0x40);
+    LittleEndian::write_u16(&mut data[0x36..], 0x38);
+    LittleEndian::write_u16(&mut data[0x38..], 3);
+
+    // The program headers: the note, then the two loads.
+    for &(base, kind, offset, address, size) in [(0x40, 4, 0xE8, 0, 0x44),
+                                                  (0x78, 1, 0x130, 0x401000, 0x10),
+                                                  (0xB0, 1, 0x140, 0x500000, 0x10)].iter() {
+        LittleEndian::write_u32(&mut data[base..], kind);
+        LittleEndian::write_u64(&mut data[base + 0x08..], offset);
+        LittleEndian::write_u64(&mut data[base + 0x10..], address);
+        LittleEndian::write_u64(&mut data[base + 0x20..], size);
+    }
+
+    // The NT_FILE note: the count and page size, one (start, end, page) entry
+    // and its name.
+    LittleEndian::write_u32(&mut data[0xE8..], 5);
+    LittleEndian::write_u32(&mut data[0xEC..], 47);
+    LittleEndian::write_u32(&mut data[0xF0..], 0x46494c45);
+    data[0xF4..0xF9].copy_from_slice(b"CORE\0");
+    for (i, &word) in [1, 0x1000, 0x400000, 0x402000, 1].iter().enumerate() {
+        LittleEndian::write_u64(&mut data[0xFC + i * 8..], word);
+    }
+    data[0x124..0x12B].copy_from_slice(b"/bin/x\0");
+
+    data[0x130..0x150].copy_from_slice(b"0123456789abcdefANONANONANONANON");
+    data
+}
+
+#[test]
+fn test_entries() {
+    let data = test_core();
+    assert!(is_core(&data));
+
+    let segments = entries(&data).unwrap();
+    assert_eq!(segments.iter().map(|e| (&e.name[..], e.base)).collect::<Vec<_>>(),
+               vec![("0x401000:/bin/x+0x2000", 0x401000), ("0x500000:[anon]", 0x500000)]);
+    assert_eq!(&*segments[0].data, b"0123456789abcdef");
+    assert_eq!(&*segments[1].data, b"ANONANONANONANON");
+}
@@ -0,0 +1,62 @@
+use super::{cstr, parse_num, slice, Entry};
+
+
+const S_IFMT: u64 = 0o170000;
+const S_IFREG: u64 = 0o100000;
+
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+
+/// Returns the regular files in a cpio archive, in either the "new" (SVR4)
+/// format used by initramfs and RPM, or the old portable ASCII format.
+pub fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    let mut ret = vec![];
+    let mut off = 0;
+
+    loop {
+        let magic = try_opt!(slice(data, off, 6));
+
+        // (mode, name size, file size, header size, whether to pad to 4 bytes)
+        let (mode, namesize, filesize, header, padded) = if magic == b"070701" || magic == b"070702" {
+            let h = try_opt!(slice(data, off, 110));
+            (try_opt!(parse_num(&h[14..22], 16)),
+             try_opt!(parse_num(&h[94..102], 16)) as usize,
+             try_opt!(parse_num(&h[54..62], 16)) as usize,
+             110, true)
+        } else if magic == b"070707" {
+            let h = try_opt!(slice(data, off, 76));
+            (try_opt!(parse_num(&h[18..24], 8)),
+             try_opt!(parse_num(&h[59..65], 8)) as usize,
+             try_opt!(parse_num(&h[65..76], 8)) as usize,
+             76, false)
+        } else {
+            return None;
+        };
+
+        let name_start = off + header;
+        let name = cstr(try_opt!(slice(data, name_start, namesize)));
+
+        let mut start = name_start + namesize;
+        if padded {
+            start = align4(start);
+        }
+        let body = try_opt!(slice(data, start, filesize));
+
+        if name == "TRAILER!!!" {
+            break;
+        }
+        if mode & S_IFMT == S_IFREG {
+            ret.push(Entry::new(name, body, None));
+        }
+
+        off = start + filesize;
+        if padded {
+            off = align4(off);
+        }
+    }
+
+    Some(ret)
+}
@@ -0,0 +1,268 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+
+use super::{cstr, decompress, slice, Codec, Entry, Limits};
+
+
+const MAGIC: u32 = 0x28cd3d45;
+const SIGNATURE: &'static [u8] = b"Compressed ROMFS";
+const PAGE_SIZE: usize = 4096;
+
+const FLAG_EXT_BLOCK_POINTERS: u32 = 0x800;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+// Directories are trees, but a corrupted image could contain loops, which are
+// caught by remembering the directories that have been read.  This still
+// limits how deeply distinct directories can be nested.
+const MAX_DEPTH: usize = 64;
+
+
+/// Finds the superblock, which is either at the start of the image or after
+/// 512 bytes of padding, and returns its offset and whether it's little
+/// endian.
+fn find_superblock(data: &[u8]) -> Option<(usize, bool)> {
+    for &off in [0, 512].iter() {
+        let header = match slice(data, off, 32) {
+            Some(h) => h,
+            None    => continue,
+        };
+        if &header[16..32] != SIGNATURE {
+            continue;
+        }
+
+        if LittleEndian::read_u32(header) == MAGIC {
+            return Some((off, true));
+        } else if BigEndian::read_u32(header) == MAGIC {
+            return Some((off, false));
+        }
+    }
+
+    None
+}
+
+
+pub fn is_cramfs(data: &[u8]) -> bool {
+    find_superblock(data).is_some()
+}
+
+
+struct Inode {
+    mode: u32,
+    size: usize,
+    name_len: usize,
+    offset: usize,
+}
+
+
+struct CramFs<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+
+/// The state of a walk over the directory tree.
+struct Walk {
+    /// The offsets of the directory listings that have already been read.
+    visited: BTreeSet<usize>,
+
+    /// The files read so far, and their total size - which is limited like a
+    /// single decompressed member, since they're all held at once.
+    files: Vec<Entry<'static>>,
+    total: u64,
+    full: bool,
+}
+
+
+impl<'a> CramFs<'a> {
+    fn u32(&self, off: usize) -> Option<u32> {
+        slice(self.data, off, 4).map(|b| if self.little_endian {
+            LittleEndian::read_u32(b)
+        } else {
+            BigEndian::read_u32(b)
+        })
+    }
+
+    /// Reads an inode, which is three words of bitfields - and since they're
+    /// bitfields, the order of the fields is also reversed on big-endian
+    /// images.
+    fn inode(&self, off: usize) -> Option<Inode> {
+        let (w0, w1, w2) = (try_opt!(self.u32(off)), try_opt!(self.u32(off + 4)), try_opt!(self.u32(off + 8)));
+
+        Some(if self.little_endian {
+            Inode {
+                mode:     w0 & 0xFFFF,
+                size:     (w1 & 0xFFFFFF) as usize,
+                name_len: ((w2 & 0x3F) * 4) as usize,
+                offset:   ((w2 >> 6) * 4) as usize,
+            }
+        } else {
+            Inode {
+                mode:     w0 >> 16,
+                size:     (w1 >> 8) as usize,
+                name_len: ((w2 >> 26) * 4) as usize,
+                offset:   ((w2 & 0x3FFFFFF) * 4) as usize,
+            }
+        })
+    }
+
+    fn walk_dir(&self, dir: &Inode, prefix: &str, limits: &Limits, depth: usize, walk: &mut Walk) {
+        if depth > MAX_DEPTH {
+            warn!("{}: directories nested too deeply", prefix);
+            return;
+        }
+
+        // Empty directories all have an offset of zero.
+        if dir.size > 0 && !walk.visited.insert(dir.offset) {
+            warn!("{}: directory listed more than once", prefix);
+            return;
+        }
+
+        let mut off = dir.offset;
+        while off < dir.offset + dir.size && !walk.full {
+            let inode = match self.inode(off) {
+                Some(i) => i,
+                None    => return,
+            };
+            let name = match slice(self.data, off + 12, inode.name_len) {
+                Some(n) => cstr(n),
+                None    => return,
+            };
+            off += 12 + inode.name_len;
+
+            let path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+
+            match inode.mode & S_IFMT {
+                S_IFDIR => self.walk_dir(&inode, &path, limits, depth + 1, walk),
+                S_IFREG if inode.size > 0 => {
+                    if walk.total + inode.size as u64 > limits.max_size {
+                        warn!("{}: files larger than {} bytes in total, skipping the rest", path, limits.max_size);
+                        walk.full = true;
+                        return;
+                    }
+
+                    match self.read_file(&inode, limits) {
+                        Some(contents) => {
+                            walk.total += contents.len() as u64;
+                            walk.files.push(Entry {
+                                name:  path,
+                                data:  Cow::Owned(contents),
+                                codec: None,
+                                base:  0,
+                            });
+                        },
+                        None => warn!("{}: could not read file", path),
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Reads a file, which is stored as a table of pointers to the end of each
+    /// page, followed by the zlib-compressed pages.
+    fn read_file(&self, inode: &Inode, limits: &Limits) -> Option<Vec<u8>> {
+        if inode.size as u64 > limits.max_size {
+            return None;
+        }
+
+        let num_blocks = (inode.size + PAGE_SIZE - 1) / PAGE_SIZE;
+        let mut start = inode.offset + num_blocks * 4;
+        let mut ret = Vec::with_capacity(inode.size);
+
+        for i in 0..num_blocks {
+            let end = try_opt!(self.u32(inode.offset + i * 4)) as usize;
+            if end < start {
+                return None;
+            }
+
+            if end == start {
+                // A hole - i.e. a page of zeroes.
+                ret.extend(vec![0u8; PAGE_SIZE]);
+            } else {
+                let block = try_opt!(slice(self.data, start, end - start));
+                match decompress(Codec::Zlib, block, PAGE_SIZE as u64) {
+                    Ok(Some(page)) => ret.extend(page),
+                    _              => return None,
+                }
+            }
+
+            start = end;
+        }
+
+        ret.truncate(inode.size);
+        Some(ret)
+    }
+}
+
+
+/// Returns the regular files in a CramFS image.
+pub fn entries(data: &[u8], limits: &Limits) -> Option<Vec<Entry<'static>>> {
+    let (sb, little_endian) = try_opt!(find_superblock(data));
+    let fs = CramFs { data: data, little_endian: little_endian };
+
+    if try_opt!(fs.u32(sb + 8)) & FLAG_EXT_BLOCK_POINTERS != 0 {
+        warn!("CramFS images with extended block pointers are not supported");
+        return Some(vec![]);
+    }
+
+    let root = try_opt!(fs.inode(sb + 64));
+
+    let mut walk = Walk {
+        visited: BTreeSet::new(),
+        files:   vec![],
+        total:   0,
+        full:    false,
+    };
+    fs.walk_dir(&root, "", limits, 0, &mut walk);
+    Some(walk.files)
+}
+
+// ----------------------------------------------------------------------
+
+/// Builds a little-endian image with two three-byte files, `a` and `b`, and
+/// two directories that both list the root directory again.  The files share
+/// a single page, which is a hole.
+#[cfg(test)]
+fn test_image() -> Vec<u8> {
+    let mut data = vec![0u8; 148];
+    LittleEndian::write_u32(&mut data[0..], MAGIC);
+    LittleEndian::write_u32(&mut data[4..], 148);
+    data[16..32].copy_from_slice(SIGNATURE);
+
+    {
+        let mut inode = |off: usize, mode: u32, size: u32, name: &[u8], offset: u32| {
+            LittleEndian::write_u32(&mut data[off..], mode);
+            LittleEndian::write_u32(&mut data[off + 4..], size);
+            LittleEndian::write_u32(&mut data[off + 8..], (name.len() as u32 / 4) | (offset / 4) << 6);
+            data[off + 12..off + 12 + name.len()].copy_from_slice(name);
+        };
+
+        inode(64,  S_IFDIR | 0o755, 64, b"", 76);
+        inode(76,  S_IFREG | 0o644, 3, b"a\0\0\0", 140);
+        inode(92,  S_IFREG | 0o644, 3, b"b\0\0\0", 140);
+        inode(108, S_IFDIR | 0o755, 64, b"loop", 76);
+        inode(124, S_IFDIR | 0o755, 64, b"up\0\0", 76);
+    }
+
+    // The pointer to the end of the only page, which is also its start.
+    LittleEndian::write_u32(&mut data[140..], 144);
+    data
+}
+
+#[test]
+fn test_entries() {
+    let data = test_image();
+    assert!(is_cramfs(&data));
+
+    let files = entries(&data, &Limits::default()).unwrap();
+    assert_eq!(files.iter().map(|e| &e.name[..]).collect::<Vec<_>>(), vec!["a", "b"]);
+    assert_eq!(&*files[0].data, &[0, 0, 0][..]);
+
+    let limits = Limits { max_size: 5, ..Limits::default() };
+    assert_eq!(entries(&data, &limits).unwrap().len(), 1);
+}
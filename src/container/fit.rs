@@ -0,0 +1,202 @@
+use byteorder::{ByteOrder, BigEndian};
+
+use super::{cstr, slice, Codec, Entry};
+
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+
+fn be32(data: &[u8], off: usize) -> Option<u32> {
+    slice(data, off, 4).map(BigEndian::read_u32)
+}
+
+
+/// The properties of a node under `/images` that we care about.
+#[derive(Default)]
+struct Image<'a> {
+    data: Option<&'a [u8]>,
+    data_offset: Option<usize>,
+    data_position: Option<usize>,
+    data_size: Option<usize>,
+    load: Option<u64>,
+    compression: Option<String>,
+}
+
+
+/// Returns the images in a U-Boot FIT image, which is a flattened device tree
+/// with the images under the `/images` node.  Data can either be embedded in
+/// the tree or (with `mkimage -E`) stored after it.
+pub fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    let total_size = try_opt!(be32(data, 4)) as usize;
+    let off_struct = try_opt!(be32(data, 8)) as usize;
+    let off_strings = try_opt!(be32(data, 12)) as usize;
+
+    let mut ret = vec![];
+    let mut path: Vec<String> = vec![];
+    let mut image = Image::default();
+    let mut off = off_struct;
+
+    loop {
+        let token = try_opt!(be32(data, off));
+        off += 4;
+
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = cstr(try_opt!(slice(data, off, data.len().saturating_sub(off))));
+                off = align4(off + name.len() + 1);
+                path.push(name);
+            },
+            FDT_END_NODE => {
+                // Nodes directly under "/images" - the root node is unnamed.
+                if path.len() == 3 && path[1] == "images" {
+                    let name = path[2].clone();
+                    let image = ::std::mem::replace(&mut image, Image::default());
+                    if let Some(entry) = image_entry(data, total_size, name, image) {
+                        ret.push(entry);
+                    }
+                }
+                path.pop();
+            },
+            FDT_PROP => {
+                let len = try_opt!(be32(data, off)) as usize;
+                let name_off = try_opt!(be32(data, off + 4)) as usize;
+                let value = try_opt!(slice(data, off + 8, len));
+                off = align4(off + 8 + len);
+
+                if !(path.len() == 3 && path[1] == "images") {
+                    continue;
+                }
+
+                let name = cstr(try_opt!(slice(data, off_strings + name_off, data.len().saturating_sub(off_strings + name_off))));
+                let cell = if len == 4 {
+                    Some(BigEndian::read_u32(value) as u64)
+                } else if len == 8 {
+                    Some(BigEndian::read_u64(value))
+                } else {
+                    None
+                };
+
+                match &name[..] {
+                    "data"          => image.data = Some(value),
+                    "data-offset"   => image.data_offset = cell.map(|v| v as usize),
+                    "data-position" => image.data_position = cell.map(|v| v as usize),
+                    "data-size"     => image.data_size = cell.map(|v| v as usize),
+                    "load"          => image.load = cell,
+                    "compression"   => image.compression = Some(cstr(value)),
+                    _               => {},
+                }
+            },
+            FDT_NOP => {},
+            FDT_END => break,
+            _       => return None,
+        }
+    }
+
+    Some(ret)
+}
+
+
+fn image_entry<'a>(data: &'a [u8], total_size: usize, name: String, image: Image<'a>) -> Option<Entry<'a>> {
+    let contents = match (image.data, image.data_position, image.data_offset, image.data_size) {
+        (Some(d), _, _, _)                  => d,
+        (None, Some(pos), _, Some(size))    => try_opt!(slice(data, pos, size)),
+
+        // External data offsets are relative to the end of the tree.
+        (None, None, Some(off), Some(size)) => try_opt!(slice(data, align4(total_size) + off, size)),
+        _                                   => return None,
+    };
+
+    let codec = match image.compression.as_ref().map(|c| &c[..]) {
+        None | Some("none") => None,
+        Some("gzip")        => Some(Codec::Gzip),
+        Some("bzip2")       => Some(Codec::Bzip2),
+        Some("lzma")        => Some(Codec::Lzma),
+        Some(c)             => {
+            warn!("{}: unsupported FIT compression type {}", name, c);
+            return None;
+        },
+    };
+
+    let mut entry = Entry::new(name, contents, codec);
+    entry.base = image.load.unwrap_or(0);
+    Some(entry)
+}
+
+// ----------------------------------------------------------------------
+
+/// Builds a FIT image with one gzipped image, `kernel`, with its data embedded
+/// in the tree.
+#[cfg(test)]
+fn test_fit() -> Vec<u8> {
+    fn token(data: &mut Vec<u8>, token: u32) {
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, token);
+        data.extend(&buf);
+    }
+
+    fn pad(data: &mut Vec<u8>) {
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+    }
+
+    fn node(data: &mut Vec<u8>, name: &str) {
+        token(data, FDT_BEGIN_NODE);
+        data.extend(name.as_bytes());
+        data.push(0);
+        pad(data);
+    }
+
+    fn prop(data: &mut Vec<u8>, name_off: u32, value: &[u8]) {
+        token(data, FDT_PROP);
+        token(data, value.len() as u32);
+        token(data, name_off);
+        data.extend(value);
+        pad(data);
+    }
+
+    let mut data = vec![0u8; 40];
+    node(&mut data, "");
+    node(&mut data, "images");
+    node(&mut data, "kernel");
+    prop(&mut data, 0, b"abcd");
+    prop(&mut data, 5, &[0x80, 0, 0x80, 0]);
+    prop(&mut data, 10, b"gzip\0");
+    token(&mut data, FDT_END_NODE);
+    token(&mut data, FDT_END_NODE);
+    token(&mut data, FDT_END_NODE);
+    token(&mut data, FDT_END);
+
+    let strings = data.len();
+    data.extend(b"data\0load\0compression\0");
+
+    let total = data.len() as u32;
+    BigEndian::write_u32(&mut data[0..], 0xd00dfeed);
+    BigEndian::write_u32(&mut data[4..], total);
+    BigEndian::write_u32(&mut data[8..], 40);
+    BigEndian::write_u32(&mut data[12..], strings as u32);
+    data
+}
+
+#[test]
+fn test_entries() {
+    let data = test_fit();
+
+    let images = entries(&data).unwrap();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].name, "kernel");
+    assert_eq!(images[0].base, 0x80008000);
+    assert_eq!(images[0].codec, Some(Codec::Gzip));
+    assert_eq!(&*images[0].data, b"abcd");
+
+    assert!(entries(&data[..60]).is_none());
+}
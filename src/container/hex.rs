@@ -0,0 +1,209 @@
+use std::borrow::Cow;
+
+use super::Entry;
+
+
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'...b'9' => Some(c - b'0'),
+        b'a'...b'f' => Some(c - b'a' + 10),
+        b'A'...b'F' => Some(c - b'A' + 10),
+        _           => None,
+    }
+}
+
+
+fn decode_hex(s: &[u8]) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+
+    let mut ret = Vec::with_capacity(s.len() / 2);
+    for pair in s.chunks(2) {
+        ret.push(try_opt!(hex_digit(pair[0])) << 4 | try_opt!(hex_digit(pair[1])));
+    }
+    Some(ret)
+}
+
+
+fn trim(line: &[u8]) -> &[u8] {
+    let start = line.iter().position(|&b| !(b as char).is_whitespace()).unwrap_or(line.len());
+    let end = line.iter().rposition(|&b| !(b as char).is_whitespace()).map_or(start, |i| i + 1);
+    &line[start..end]
+}
+
+
+fn first_line(data: &[u8]) -> &[u8] {
+    let end = data.iter().position(|&b| b == b'\n').unwrap_or(data.len());
+    trim(&data[..end])
+}
+
+
+pub fn is_ihex(data: &[u8]) -> bool {
+    let line = first_line(data);
+    line.len() >= 11 && line[0] == b':' && decode_hex(&line[1..]).is_some()
+}
+
+
+pub fn is_srec(data: &[u8]) -> bool {
+    let line = first_line(data);
+    line.len() >= 10 && line[0] == b'S' && (line[1] as char).is_digit(10) && decode_hex(&line[2..]).is_some()
+}
+
+
+/// Merges records that are contiguous in memory into a single entry each,
+/// named by (and based at) their load address.
+fn merge(mut records: Vec<(u64, Vec<u8>)>) -> Vec<Entry<'static>> {
+    records.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut ret: Vec<Entry> = vec![];
+    for (address, bytes) in records {
+        if let Some(last) = ret.last_mut() {
+            if last.base + last.data.len() as u64 == address {
+                last.data.to_mut().extend(bytes);
+                continue;
+            }
+        }
+
+        ret.push(Entry {
+            name:  format!("0x{:08x}", address),
+            data:  Cow::Owned(bytes),
+            codec: None,
+            base:  address,
+        });
+    }
+
+    ret
+}
+
+
+/// Decodes an Intel HEX file into the contiguous regions of memory that it
+/// describes.
+pub fn ihex_entries(data: &[u8]) -> Option<Vec<Entry<'static>>> {
+    let mut records = vec![];
+    let mut upper = 0u64;
+
+    for line in data.split(|&b| b == b'\n') {
+        let line = trim(line);
+        if line.is_empty() {
+            continue;
+        }
+        if line[0] != b':' {
+            return None;
+        }
+
+        // Length, address (2), type, data, checksum
+        let rec = try_opt!(decode_hex(&line[1..]));
+        if rec.len() < 5 || rec.len() != rec[0] as usize + 5 {
+            return None;
+        }
+        if rec.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+            return None;
+        }
+
+        let address = (rec[1] as u64) << 8 | rec[2] as u64;
+        let payload = &rec[4..rec.len() - 1];
+
+        match rec[3] {
+            // Data
+            0x00 => records.push((upper + address, payload.to_vec())),
+
+            // End of file
+            0x01 => break,
+
+            // Extended segment address, and extended linear address
+            0x02 | 0x04 => {
+                if payload.len() != 2 {
+                    return None;
+                }
+                let value = (payload[0] as u64) << 8 | payload[1] as u64;
+                upper = if rec[3] == 0x02 { value << 4 } else { value << 16 };
+            },
+
+            // Start addresses
+            _ => {},
+        }
+    }
+
+    Some(merge(records))
+}
+
+
+/// Decodes a Motorola S-record file into the contiguous regions of memory that
+/// it describes.
+pub fn srec_entries(data: &[u8]) -> Option<Vec<Entry<'static>>> {
+    let mut records = vec![];
+
+    for line in data.split(|&b| b == b'\n') {
+        let line = trim(line);
+        if line.is_empty() {
+            continue;
+        }
+        if line.len() < 4 || line[0] != b'S' {
+            return None;
+        }
+
+        // Count, address, data, checksum
+        let rec = try_opt!(decode_hex(&line[2..]));
+        if rec.len() < 2 || rec.len() != rec[0] as usize + 1 {
+            return None;
+        }
+        if rec.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0xFF {
+            return None;
+        }
+
+        let address_len = match line[1] {
+            b'1' => 2,
+            b'2' => 3,
+            b'3' => 4,
+
+            // Header, record counts and start addresses
+            b'0' | b'5' | b'6' | b'7' | b'8' | b'9' => continue,
+            _ => return None,
+        };
+        if rec.len() < address_len + 2 {
+            return None;
+        }
+
+        let address = rec[1..address_len + 1].iter().fold(0u64, |a, &b| a << 8 | b as u64);
+        records.push((address, rec[address_len + 1..rec.len() - 1].to_vec()));
+    }
+
+    Some(merge(records))
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_ihex() {
+    let data = b":020000040800F2\n\
+                 :0400000001020304F2\n\
+                 :0400040005060708DE\n\
+                 :04010000AABBCCDDED\n\
+                 :00000001FF\n";
+
+    assert!(is_ihex(data));
+    let entries = ihex_entries(data).unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].base, 0x08000000);
+    assert_eq!(&*entries[0].data, &[1, 2, 3, 4, 5, 6, 7, 8][..]);
+    assert_eq!(entries[1].name, "0x08000100");
+    assert_eq!(&*entries[1].data, &[0xAA, 0xBB, 0xCC, 0xDD][..]);
+}
+
+#[test]
+fn test_srec() {
+    let data = b"S00600004844521B\n\
+                 S107000001020304EE\n\
+                 S30908001000AABBCCDDD0\n\
+                 S9030000FC\n";
+
+    assert!(is_srec(data));
+    let entries = srec_entries(data).unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].base, 0);
+    assert_eq!(&*entries[0].data, &[1, 2, 3, 4][..]);
+    assert_eq!(entries[1].base, 0x08001000);
+}
@@ -0,0 +1,76 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use super::{slice, Entry};
+
+
+const MAGIC: u32 = 0x4C694D45;
+const HEADER_SIZE: usize = 32;
+
+
+/// Returns the ranges of physical memory in a LiME memory image, based at
+/// their physical addresses.  Each range has a header giving its (inclusive)
+/// start and end address.
+pub fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    let mut ret = vec![];
+    let mut off = 0;
+
+    while off < data.len() {
+        let header = try_opt!(slice(data, off, HEADER_SIZE));
+        if LittleEndian::read_u32(&header[0..4]) != MAGIC {
+            return None;
+        }
+
+        let start = LittleEndian::read_u64(&header[8..16]);
+        let end = LittleEndian::read_u64(&header[16..24]);
+        if end < start {
+            return None;
+        }
+
+        // A range can't be larger than the image, which also rules out one
+        // covering every address, whose length doesn't fit in a u64.
+        let len = try_opt!((end - start).checked_add(1));
+        if len > data.len() as u64 {
+            return None;
+        }
+        let len = len as usize;
+        let contents = try_opt!(slice(data, off + HEADER_SIZE, len));
+
+        let mut entry = Entry::new(format!("0x{:x}-0x{:x}", start, end), contents, None);
+        entry.base = start;
+        ret.push(entry);
+
+        off += HEADER_SIZE + len;
+    }
+
+    Some(ret)
+}
+
+// ----------------------------------------------------------------------
+
+#[cfg(test)]
+fn test_header(start: u64, end: u64) -> Vec<u8> {
+    let mut header = vec![0u8; HEADER_SIZE];
+    LittleEndian::write_u32(&mut header[0..], MAGIC);
+    LittleEndian::write_u32(&mut header[4..], 1);
+    LittleEndian::write_u64(&mut header[8..], start);
+    LittleEndian::write_u64(&mut header[16..], end);
+    header
+}
+
+#[test]
+fn test_entries() {
+    let mut data = test_header(0x1000, 0x1003);
+    data.extend(b"abcd");
+    data.extend(test_header(0x8000, 0x8001));
+    data.extend(b"ef");
+
+    let ranges = entries(&data).unwrap();
+    assert_eq!(ranges.iter().map(|e| (&e.name[..], e.base)).collect::<Vec<_>>(),
+               vec![("0x1000-0x1003", 0x1000), ("0x8000-0x8001", 0x8000)]);
+    assert_eq!(&*ranges[0].data, b"abcd");
+    assert_eq!(&*ranges[1].data, b"ef");
+
+    assert!(entries(&data[..data.len() - 1]).is_none());
+    assert!(entries(&test_header(0x2000, 0x1000)).is_none());
+    assert!(entries(&test_header(0, !0)).is_none());
+}
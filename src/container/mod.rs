@@ -0,0 +1,413 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::io::{self, Read};
+
+use byteorder::{ByteOrder, LittleEndian};
+use bzip2::read::BzDecoder;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use xz2::read::XzDecoder;
+use xz2::stream::Stream;
+
+mod coredump;
+mod cpio;
+mod cramfs;
+mod fit;
+mod hex;
+mod lime;
+mod sevenzip;
+mod tar;
+mod trx;
+mod uimage;
+mod zip;
+
+
+/// Limits on how much work we do unpacking containers, so that a malicious or
+/// unexpectedly large input can't exhaust memory.
+pub struct Limits {
+    /// The maximum decompressed size of a single member, in bytes.
+    pub max_size: u64,
+
+    /// The maximum depth of nested containers to unpack.
+    pub max_depth: usize,
+
+    /// The maximum total size of the decompressed data held at once, in
+    /// bytes.  A nested container is unpacked while everything it came from
+    /// is still in memory, so this is shared between all of the levels.
+    pub max_total: u64,
+}
+
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_size: 256 * 1024 * 1024,
+            max_depth: 8,
+            max_total: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+
+/// Compression formats that we can decode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Xz,
+    Bzip2,
+
+    /// Raw DEFLATE, as used in ZIP files.
+    Deflate,
+
+    /// DEFLATE with a zlib header, as used in CramFS.
+    Zlib,
+
+    /// The legacy `.lzma` format, as used in uImages and TRX partitions.
+    Lzma,
+}
+
+
+/// A member of an archive.
+pub struct Entry<'a> {
+    pub name: String,
+    pub data: Cow<'a, [u8]>,
+
+    /// How `data` is compressed, if at all.
+    pub codec: Option<Codec>,
+
+    /// The address that the data is loaded at, for firmware images.
+    pub base: u64,
+}
+
+
+impl<'a> Entry<'a> {
+    fn new(name: String, data: &'a [u8], codec: Option<Codec>) -> Entry<'a> {
+        Entry {
+            name:  name,
+            data:  Cow::Borrowed(data),
+            codec: codec,
+            base:  0,
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Compressed(Codec),
+    Zip,
+    Tar,
+    Cpio,
+    IntelHex,
+    Srec,
+    UImage,
+    Fit,
+    Trx,
+    CramFs,
+    Core,
+    Lime,
+    SevenZip,
+    SquashFs,
+}
+
+
+fn detect(data: &[u8]) -> Option<Kind> {
+    if data.starts_with(b"\x1f\x8b") {
+        Some(Kind::Compressed(Codec::Gzip))
+    } else if data.starts_with(b"\xfd7zXZ\x00") {
+        Some(Kind::Compressed(Codec::Xz))
+    } else if data.starts_with(b"BZh") {
+        Some(Kind::Compressed(Codec::Bzip2))
+    } else if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        Some(Kind::Zip)
+    } else if data.starts_with(b"070701") || data.starts_with(b"070702") || data.starts_with(b"070707") {
+        Some(Kind::Cpio)
+    } else if data.starts_with(b"7z\xbc\xaf\x27\x1c") {
+        Some(Kind::SevenZip)
+    } else if coredump::is_core(data) {
+        Some(Kind::Core)
+    } else if data.starts_with(b"EMiL") {
+        Some(Kind::Lime)
+    } else if data.starts_with(b"hsqs") || data.starts_with(b"sqsh") {
+        Some(Kind::SquashFs)
+    } else if data.starts_with(b"\x27\x05\x19\x56") {
+        Some(Kind::UImage)
+    } else if data.starts_with(b"\xd0\x0d\xfe\xed") {
+        Some(Kind::Fit)
+    } else if data.starts_with(b"HDR0") {
+        Some(Kind::Trx)
+    } else if cramfs::is_cramfs(data) {
+        Some(Kind::CramFs)
+    } else if is_lzma(data) {
+        Some(Kind::Compressed(Codec::Lzma))
+    } else if hex::is_ihex(data) {
+        Some(Kind::IntelHex)
+    } else if hex::is_srec(data) {
+        Some(Kind::Srec)
+    } else if data.len() >= 512 && &data[257..262] == b"ustar" {
+        Some(Kind::Tar)
+    } else {
+        None
+    }
+}
+
+
+/// The legacy LZMA format doesn't have a magic number, so check for the usual
+/// properties byte, a power-of-two dictionary size, and a plausible (or
+/// unknown) uncompressed size.
+fn is_lzma(data: &[u8]) -> bool {
+    if data.len() < 13 || data[0] != 0x5D {
+        return false;
+    }
+
+    let dict_size = LittleEndian::read_u32(&data[1..5]);
+    let size = LittleEndian::read_u64(&data[5..13]);
+
+    dict_size >= 4096 && dict_size.count_ones() == 1 && (size == !0 || size < 1 << 32)
+}
+
+
+/// Decompresses the given data.  Returns `Ok(None)` if the output would be
+/// larger than `limit` bytes.
+pub fn decompress(codec: Codec, data: &[u8], limit: u64) -> io::Result<Option<Vec<u8>>> {
+    let reader: Box<Read> = match codec {
+        Codec::Gzip    => Box::new(try!(GzDecoder::new(data))),
+        Codec::Xz      => Box::new(XzDecoder::new(data)),
+        Codec::Bzip2   => Box::new(BzDecoder::new(data)),
+        Codec::Deflate => Box::new(DeflateDecoder::new(data)),
+        Codec::Zlib    => Box::new(ZlibDecoder::new(data)),
+        Codec::Lzma    => {
+            let stream = try!(Stream::new_lzma_decoder(u64::max_value()));
+            Box::new(XzDecoder::new_stream(data, stream))
+        },
+    };
+
+    // Read one byte past the limit, so we can tell if it was exceeded.
+    let mut out = vec![];
+    try!(reader.take(limit + 1).read_to_end(&mut out));
+
+    if out.len() as u64 > limit {
+        Ok(None)
+    } else {
+        Ok(Some(out))
+    }
+}
+
+
+/// Calls `f` with the given data, or - if it's a compressed file, an archive, a
+/// firmware image or a memory dump - with each file (or region of memory)
+/// contained within it, recursively.  The names passed to `f` show how the
+/// data was found, e.g. `firmware.zip!/lib/libcrypto.so`, and the base is the
+/// address that the data is loaded at, if known, or zero.
+pub fn walk<F>(name: &str, data: &[u8], limits: &Limits, f: &mut F)
+where F: FnMut(&str, &[u8], u64)
+{
+    walk_depth(name, data, 0, limits, 0, 0, f)
+}
+
+
+/// Walks the given data, where `held` is the total size of the decompressed
+/// data that it came from, which is still in memory.
+fn walk_depth<F>(name: &str, data: &[u8], base: u64, limits: &Limits, depth: usize, held: u64, f: &mut F)
+where F: FnMut(&str, &[u8], u64)
+{
+    let kind = match detect(data) {
+        Some(k) => k,
+        None    => return f(name, data, base),
+    };
+
+    if depth >= limits.max_depth {
+        warn!("{}: containers nested too deeply, scanning as-is", name);
+        return f(name, data, base);
+    }
+
+    debug!("{}: unpacking {:?}", name, kind);
+
+    let limit = remaining(limits, held);
+    let entries = match kind {
+        Kind::Compressed(codec) => {
+            let inner = format!("{}!/{}", name, inner_name(name));
+            match decompress(codec, data, limit) {
+                Ok(Some(out)) => return walk_depth(&inner, &out, base, limits, depth + 1, held + out.len() as u64, f),
+                Ok(None)      => warn!("{}: decompressed data is larger than {} bytes, scanning as-is", name, limit),
+                Err(e)        => warn!("{}: could not decompress: {}", name, e),
+            }
+            return f(name, data, base);
+        },
+        Kind::Zip      => zip::entries(data),
+        Kind::Tar      => tar::entries(data),
+        Kind::Cpio     => cpio::entries(data),
+        Kind::IntelHex => hex::ihex_entries(data),
+        Kind::Srec     => hex::srec_entries(data),
+        Kind::UImage   => uimage::entries(data),
+        Kind::Fit      => fit::entries(data),
+        Kind::Trx      => trx::entries(data),
+        Kind::CramFs   => cramfs::entries(data, &Limits { max_size: limit, ..*limits }),
+        Kind::Core     => coredump::entries(data),
+        Kind::Lime     => lime::entries(data),
+        Kind::SevenZip => sevenzip::entries(data, limit),
+        Kind::SquashFs => {
+            warn!("{}: SquashFS images are not supported - extract them with unsquashfs first", name);
+            return f(name, data, base);
+        },
+    };
+
+    let entries = match entries {
+        // Formats like FIT are also used for things that aren't containers
+        // (i.e. device trees), so scan the data itself if nothing was found.
+        Some(ref e) if e.is_empty() => return f(name, data, base),
+        Some(e) => e,
+        None    => {
+            warn!("{}: malformed {:?} file, scanning as-is", name, kind);
+            return f(name, data, base);
+        },
+    };
+
+    // Some formats have to unpack all of their entries up front.
+    let held = entries.iter().fold(held, |total, entry| match entry.data {
+        Cow::Owned(ref data) => total + data.len() as u64,
+        Cow::Borrowed(_)     => total,
+    });
+    let limit = remaining(limits, held);
+
+    for entry in entries {
+        let member = format!("{}!/{}", name, entry.name.trim_left_matches("./").trim_left_matches('/'));
+
+        match entry.codec {
+            None => walk_depth(&member, &entry.data, entry.base, limits, depth + 1, held, f),
+            Some(codec) => match decompress(codec, &entry.data, limit) {
+                Ok(Some(out)) => walk_depth(&member, &out, entry.base, limits, depth + 1, held + out.len() as u64, f),
+                Ok(None)      => warn!("{}: decompressed data is larger than {} bytes, skipping", member, limit),
+                Err(e)        => warn!("{}: could not decompress: {}", member, e),
+            },
+        }
+    }
+}
+
+
+/// Returns how much can be decompressed when `held` bytes already have been.
+fn remaining(limits: &Limits, held: u64) -> u64 {
+    cmp::min(limits.max_size, limits.max_total.saturating_sub(held))
+}
+
+
+/// Returns the name to use for the decompressed contents of the given file -
+/// its name without the compression extension.
+fn inner_name(name: &str) -> String {
+    let base = match name.rfind('/') {
+        Some(i) => &name[i + 1..],
+        None    => name,
+    };
+
+    for &(ext, replacement) in [(".tgz", ".tar"), (".txz", ".tar"), (".tbz2", ".tar"), (".tbz", ".tar"),
+                                (".gz", ""), (".xz", ""), (".bz2", ""), (".lzma", "")].iter() {
+        if base.len() > ext.len() && base.ends_with(ext) {
+            return format!("{}{}", &base[..base.len() - ext.len()], replacement);
+        }
+    }
+
+    base.to_string()
+}
+
+
+/// Returns `len` bytes of the data starting at `off`, if they're in bounds.
+fn slice(data: &[u8], off: usize, len: usize) -> Option<&[u8]> {
+    if off.checked_add(len).map_or(true, |end| end > data.len()) {
+        return None;
+    }
+
+    Some(&data[off..off + len])
+}
+
+
+/// Returns the NUL-terminated string at the start of the given bytes.
+fn cstr(data: &[u8]) -> String {
+    let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..len]).into_owned()
+}
+
+
+/// Parses an ASCII number in the given radix, ignoring surrounding NULs and
+/// spaces.
+fn parse_num(data: &[u8], radix: u32) -> Option<u64> {
+    let s = String::from_utf8_lossy(data);
+    let s = s.trim_matches(|c| c == '\0' || c == ' ');
+    if s.is_empty() {
+        return Some(0);
+    }
+
+    u64::from_str_radix(s, radix).ok()
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_inner_name() {
+    assert_eq!(inner_name("firmware.bin.gz"), "firmware.bin");
+    assert_eq!(inner_name("dir/rootfs.tgz"), "rootfs.tar");
+    assert_eq!(inner_name("a.zip!/b.xz"), "b");
+    assert_eq!(inner_name("blob"), "blob");
+}
+
+#[test]
+fn test_walk_nested() {
+    // A tar containing "a.txt" and a cpio (newc) archive containing "b.txt".
+    fn tar_header(name: &str, size: usize) -> Vec<u8> {
+        let mut h = vec![0u8; 512];
+        h[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}", size);
+        h[124..135].copy_from_slice(size.as_bytes());
+        h[156] = b'0';
+        h[257..263].copy_from_slice(b"ustar\0");
+        h
+    }
+
+    let mut cpio = vec![];
+    for &(name, body) in [("b.txt", &b"world"[..]), ("TRAILER!!!", &b""[..])].iter() {
+        let mode = if body.is_empty() { 0 } else { 0o100644 };
+        cpio.extend(format!("070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+                            0, mode, 0, 0, 1, 0, body.len(), 0, 0, 0, 0, name.len() + 1, 0).bytes());
+        cpio.extend(name.bytes());
+        cpio.push(0);
+        while cpio.len() % 4 != 0 { cpio.push(0); }
+        cpio.extend(body.iter().cloned());
+        while cpio.len() % 4 != 0 { cpio.push(0); }
+    }
+
+    let mut tar = vec![];
+    for &(name, ref body) in [("./a.txt", b"hello".to_vec()), ("inner.cpio", cpio)].iter() {
+        tar.extend(tar_header(name, body.len()));
+        tar.extend(body.iter().cloned());
+        while tar.len() % 512 != 0 { tar.push(0); }
+    }
+    tar.extend(vec![0u8; 1024]);
+
+    let mut found = vec![];
+    walk("test.tar", &tar, &Limits::default(), &mut |name, data, _| {
+        found.push((name.to_string(), data.to_vec()));
+    });
+
+    assert_eq!(found, vec![
+        ("test.tar!/a.txt".to_string(), b"hello".to_vec()),
+        ("test.tar!/inner.cpio!/b.txt".to_string(), b"world".to_vec()),
+    ]);
+}
+
+#[test]
+fn test_walk_total_limit() {
+    // A 7z archive inside another, which is still in memory while the inner
+    // one is unpacked.
+    let inner = sevenzip::test_archive(&[("a.txt", b"hello")]);
+    let outer = sevenzip::test_archive(&[("inner.7z", &inner)]);
+
+    let walk_with = |max_total: u64| {
+        let limits = Limits { max_total: max_total, ..Limits::default() };
+        let mut found = vec![];
+        walk("test.7z", &outer, &limits, &mut |name, _, _| found.push(name.to_string()));
+        found
+    };
+
+    assert_eq!(walk_with(inner.len() as u64 + 5), vec!["test.7z!/inner.7z!/a.txt"]);
+
+    // There's only room for the inner archive, so it's scanned as-is.
+    assert_eq!(walk_with(inner.len() as u64 + 4), vec!["test.7z!/inner.7z"]);
+}
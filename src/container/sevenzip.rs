@@ -0,0 +1,906 @@
+use std::borrow::Cow;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use patterns::crc::CRC32;
+use super::{decompress, slice, Codec, Entry};
+
+
+const SIGNATURE_HEADER_SIZE: usize = 32;
+
+// Property IDs used in the (possibly encoded) header.
+const ID_END: u8 = 0x00;
+const ID_HEADER: u8 = 0x01;
+const ID_ARCHIVE_PROPERTIES: u8 = 0x02;
+const ID_ADDITIONAL_STREAMS_INFO: u8 = 0x03;
+const ID_MAIN_STREAMS_INFO: u8 = 0x04;
+const ID_FILES_INFO: u8 = 0x05;
+const ID_PACK_INFO: u8 = 0x06;
+const ID_UNPACK_INFO: u8 = 0x07;
+const ID_SUBSTREAMS_INFO: u8 = 0x08;
+const ID_SIZE: u8 = 0x09;
+const ID_CRC: u8 = 0x0A;
+const ID_FOLDER: u8 = 0x0B;
+const ID_CODERS_UNPACK_SIZE: u8 = 0x0C;
+const ID_NUM_UNPACK_STREAM: u8 = 0x0D;
+const ID_EMPTY_STREAM: u8 = 0x0E;
+const ID_NAME: u8 = 0x11;
+const ID_ENCODED_HEADER: u8 = 0x17;
+
+// Coder IDs.
+const METHOD_COPY: &'static [u8] = &[0x00];
+const METHOD_LZMA: &'static [u8] = &[0x03, 0x01, 0x01];
+const METHOD_LZMA2: &'static [u8] = &[0x21];
+const METHOD_BCJ_X86: &'static [u8] = &[0x03, 0x03, 0x01, 0x03];
+const METHOD_DEFLATE: &'static [u8] = &[0x04, 0x01, 0x08];
+const METHOD_BZIP2: &'static [u8] = &[0x04, 0x02, 0x02];
+const METHOD_AES: &'static [u8] = &[0x06, 0xF1, 0x07, 0x01];
+
+// Real archives use at most a handful of coders per folder (e.g. BCJ2 and
+// three LZMA coders); anything more is corrupt.
+const MAX_CODERS: usize = 16;
+
+// Encoded headers can't sensibly be nested, but nothing stops an archive from
+// claiming that they are.
+const MAX_HEADER_NESTING: usize = 4;
+
+
+/// Reads the variable-length numbers and bit fields that 7z headers are made
+/// of.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data: data, pos: 0 }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let b = try_opt!(self.data.get(self.pos));
+        self.pos += 1;
+        Some(*b)
+    }
+
+    fn bytes(&mut self, len: u64) -> Option<&'a [u8]> {
+        if len > (self.data.len() - self.pos) as u64 {
+            return None;
+        }
+
+        let ret = &self.data[self.pos..self.pos + len as usize];
+        self.pos += len as usize;
+        Some(ret)
+    }
+
+    /// Reads a number, which is stored with the count of extra bytes as the
+    /// leading one bits of the first byte, and its remaining bits as the most
+    /// significant part of the value.
+    fn number(&mut self) -> Option<u64> {
+        let first = try_opt!(self.byte());
+        let mut value = 0u64;
+
+        for i in 0..8 {
+            let mask = 0x80 >> i;
+            if first & mask == 0 {
+                let high = (first & (mask - 1)) as u64;
+                return Some(value | high << (8 * i));
+            }
+            value |= (try_opt!(self.byte()) as u64) << (8 * i);
+        }
+
+        Some(value)
+    }
+
+    /// Reads a count of things that each take up at least one byte, so that a
+    /// corrupt count can't make us allocate more than the header's size.
+    fn count(&mut self) -> Option<usize> {
+        let n = try_opt!(self.number());
+        if n > self.data.len() as u64 {
+            return None;
+        }
+
+        Some(n as usize)
+    }
+
+    /// Reads `n` bits, most significant first.
+    fn bits(&mut self, n: usize) -> Option<Vec<bool>> {
+        let bytes = try_opt!(self.bytes((n as u64 + 7) / 8));
+        Some((0..n).map(|i| bytes[i / 8] & (0x80 >> (i % 8)) != 0).collect())
+    }
+
+    /// Reads a set of bits saying which of `n` items are present, which is
+    /// preceded by a byte saying whether they all are.
+    fn defined(&mut self, n: usize) -> Option<Vec<bool>> {
+        if try_opt!(self.byte()) != 0 {
+            Some(vec![true; n])
+        } else {
+            self.bits(n)
+        }
+    }
+
+    /// Skips the CRCs of `n` items, returning which of them had one.
+    fn digests(&mut self, n: usize) -> Option<Vec<bool>> {
+        let defined = try_opt!(self.defined(n));
+        let count = defined.iter().filter(|&&d| d).count();
+        try_opt!(self.bytes(count as u64 * 4));
+        Some(defined)
+    }
+
+    /// Skips a property that we don't use, which is preceded by its size.
+    fn skip(&mut self) -> Option<()> {
+        let size = try_opt!(self.number());
+        try_opt!(self.bytes(size));
+        Some(())
+    }
+}
+
+
+struct Coder {
+    method: Vec<u8>,
+    props: Vec<u8>,
+    num_in: usize,
+    num_out: usize,
+}
+
+
+/// A folder is a set of coders whose output is one or more files, stored one
+/// after another.
+struct Folder {
+    coders: Vec<Coder>,
+
+    /// Pairs of (input stream, output stream) that are connected.
+    bind_pairs: Vec<(u64, u64)>,
+
+    /// The input streams that are read from packed streams, in order.
+    packed: Vec<u64>,
+
+    /// The size of each coder's output stream.
+    sizes: Vec<u64>,
+}
+
+
+impl Folder {
+    /// Returns the index of the output stream that isn't used as the input of
+    /// another coder, i.e. the folder's contents.
+    fn main_stream(&self) -> Option<usize> {
+        (0..self.sizes.len()).find(|&i| !self.bind_pairs.iter().any(|&(_, out)| out == i as u64))
+    }
+
+    fn unpack_size(&self) -> u64 {
+        self.main_stream().map_or(0, |i| self.sizes[i])
+    }
+}
+
+
+struct StreamsInfo {
+    pack_pos: u64,
+    pack_sizes: Vec<u64>,
+    folders: Vec<Folder>,
+
+    /// The sizes of the files in each folder.
+    substreams: Vec<Vec<u64>>,
+}
+
+
+struct File {
+    name: String,
+    has_stream: bool,
+}
+
+
+fn parse_folder(r: &mut Reader) -> Option<Folder> {
+    let num_coders = try_opt!(r.count());
+    if num_coders == 0 || num_coders > MAX_CODERS {
+        return None;
+    }
+
+    let mut coders = Vec::with_capacity(num_coders);
+    for _ in 0..num_coders {
+        let flags = try_opt!(r.byte());
+        if flags & 0x80 != 0 {
+            // Alternative methods, which nothing ever wrote.
+            return None;
+        }
+
+        let method = try_opt!(r.bytes((flags & 0x0F) as u64)).to_vec();
+        let (num_in, num_out) = if flags & 0x10 != 0 {
+            (try_opt!(r.count()), try_opt!(r.count()))
+        } else {
+            (1, 1)
+        };
+        if num_in > MAX_CODERS || num_out > MAX_CODERS {
+            return None;
+        }
+
+        let props = if flags & 0x20 != 0 {
+            let size = try_opt!(r.number());
+            try_opt!(r.bytes(size)).to_vec()
+        } else {
+            vec![]
+        };
+
+        coders.push(Coder { method: method, props: props, num_in: num_in, num_out: num_out });
+    }
+
+    let total_in = coders.iter().fold(0, |n, c| n + c.num_in);
+    let total_out = coders.iter().fold(0, |n, c| n + c.num_out);
+
+    let num_bind_pairs = try_opt!(total_out.checked_sub(1));
+    let mut bind_pairs = Vec::with_capacity(num_bind_pairs);
+    for _ in 0..num_bind_pairs {
+        bind_pairs.push((try_opt!(r.number()), try_opt!(r.number())));
+    }
+
+    let num_packed = try_opt!(total_in.checked_sub(num_bind_pairs));
+    let packed = if num_packed == 1 {
+        let unbound = (0..total_in as u64).find(|&i| !bind_pairs.iter().any(|&(input, _)| input == i));
+        vec![try_opt!(unbound)]
+    } else {
+        let mut packed = Vec::with_capacity(num_packed);
+        for _ in 0..num_packed {
+            packed.push(try_opt!(r.number()));
+        }
+        packed
+    };
+
+    Some(Folder {
+        coders: coders,
+        bind_pairs: bind_pairs,
+        packed: packed,
+        sizes: vec![],
+    })
+}
+
+
+fn parse_unpack_info(r: &mut Reader) -> Option<(Vec<Folder>, Vec<bool>)> {
+    if try_opt!(r.byte()) != ID_FOLDER {
+        return None;
+    }
+
+    let num_folders = try_opt!(r.count());
+    if try_opt!(r.byte()) != 0 {
+        // The folders are stored in one of the additional streams.
+        return None;
+    }
+
+    let mut folders = Vec::with_capacity(num_folders);
+    for _ in 0..num_folders {
+        folders.push(try_opt!(parse_folder(r)));
+    }
+
+    if try_opt!(r.byte()) != ID_CODERS_UNPACK_SIZE {
+        return None;
+    }
+    for folder in folders.iter_mut() {
+        let num_out = folder.coders.iter().fold(0, |n, c| n + c.num_out);
+        for _ in 0..num_out {
+            folder.sizes.push(try_opt!(r.number()));
+        }
+    }
+
+    let mut crcs = vec![false; num_folders];
+    loop {
+        match try_opt!(r.byte()) {
+            ID_END => break,
+            ID_CRC => crcs = try_opt!(r.digests(num_folders)),
+            _      => try_opt!(r.skip()),
+        }
+    }
+
+    Some((folders, crcs))
+}
+
+
+fn parse_substreams(r: &mut Reader, folders: &[Folder], crcs: &[bool]) -> Option<Vec<Vec<u64>>> {
+    let mut counts = vec![1; folders.len()];
+    let mut id = try_opt!(r.byte());
+
+    if id == ID_NUM_UNPACK_STREAM {
+        for count in counts.iter_mut() {
+            *count = try_opt!(r.count());
+        }
+        id = try_opt!(r.byte());
+    }
+
+    // Only the sizes of all but the last file in each folder are stored, as
+    // the last one takes up the rest of the folder.
+    let has_sizes = id == ID_SIZE;
+    let mut substreams = Vec::with_capacity(folders.len());
+    for (folder, &count) in folders.iter().zip(counts.iter()) {
+        if count == 0 {
+            substreams.push(vec![]);
+            continue;
+        }
+        if count > 1 && !has_sizes {
+            return None;
+        }
+
+        let mut sizes = Vec::with_capacity(count);
+        let mut rest = folder.unpack_size();
+        for _ in 1..count {
+            let size = try_opt!(r.number());
+            rest = try_opt!(rest.checked_sub(size));
+            sizes.push(size);
+        }
+        sizes.push(rest);
+        substreams.push(sizes);
+    }
+    if has_sizes {
+        id = try_opt!(r.byte());
+    }
+
+    // Files that are alone in a folder with a CRC don't get another one.
+    let num_digests = counts.iter().zip(crcs.iter())
+        .fold(0, |n, (&count, &crc)| if count == 1 && crc { n } else { n + count });
+
+    loop {
+        match id {
+            ID_END => break,
+            ID_CRC => { try_opt!(r.digests(num_digests)); },
+            _      => try_opt!(r.skip()),
+        }
+        id = try_opt!(r.byte());
+    }
+
+    Some(substreams)
+}
+
+
+fn parse_streams(r: &mut Reader) -> Option<StreamsInfo> {
+    let mut streams = StreamsInfo {
+        pack_pos: 0,
+        pack_sizes: vec![],
+        folders: vec![],
+        substreams: vec![],
+    };
+    let mut crcs = vec![];
+    let mut has_substreams = false;
+
+    loop {
+        match try_opt!(r.byte()) {
+            ID_END => break,
+            ID_PACK_INFO => {
+                streams.pack_pos = try_opt!(r.number());
+                let num_packs = try_opt!(r.count());
+                loop {
+                    match try_opt!(r.byte()) {
+                        ID_END  => break,
+                        ID_SIZE => {
+                            for _ in 0..num_packs {
+                                streams.pack_sizes.push(try_opt!(r.number()));
+                            }
+                        },
+                        ID_CRC  => { try_opt!(r.digests(num_packs)); },
+                        _       => try_opt!(r.skip()),
+                    }
+                }
+            },
+            ID_UNPACK_INFO => {
+                let (folders, folder_crcs) = try_opt!(parse_unpack_info(r));
+                streams.folders = folders;
+                crcs = folder_crcs;
+            },
+            ID_SUBSTREAMS_INFO => {
+                streams.substreams = try_opt!(parse_substreams(r, &streams.folders, &crcs));
+                has_substreams = true;
+            },
+            _ => return None,
+        }
+    }
+
+    // Without any substream information, each folder holds a single file.
+    if !has_substreams {
+        streams.substreams = streams.folders.iter().map(|f| vec![f.unpack_size()]).collect();
+    }
+
+    Some(streams)
+}
+
+
+fn parse_files(r: &mut Reader) -> Option<Vec<File>> {
+    let num_files = try_opt!(r.count());
+    let mut files: Vec<File> = (0..num_files).map(|i| File { name: format!("{}", i), has_stream: true }).collect();
+
+    loop {
+        let id = try_opt!(r.byte());
+        if id == ID_END {
+            break;
+        }
+
+        let size = try_opt!(r.number());
+        let mut prop = Reader::new(try_opt!(r.bytes(size)));
+
+        match id {
+            ID_EMPTY_STREAM => {
+                let empty = try_opt!(prop.bits(num_files));
+                for (file, empty) in files.iter_mut().zip(empty) {
+                    file.has_stream = !empty;
+                }
+            },
+            ID_NAME => {
+                if try_opt!(prop.byte()) != 0 {
+                    // The names are stored in one of the additional streams.
+                    continue;
+                }
+
+                // NUL-terminated UTF-16LE strings.
+                let rest = &prop.data[prop.pos..];
+                let chars: Vec<u16> = rest.chunks(2)
+                    .filter(|c| c.len() == 2)
+                    .map(LittleEndian::read_u16)
+                    .collect();
+                for (file, name) in files.iter_mut().zip(chars.split(|&c| c == 0)) {
+                    file.name = String::from_utf16_lossy(name);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    Some(files)
+}
+
+
+/// Parses the header proper, after the `ID_HEADER` byte.
+fn parse_header(r: &mut Reader) -> Option<(Option<StreamsInfo>, Vec<File>)> {
+    let mut streams = None;
+    let mut files = vec![];
+
+    loop {
+        match try_opt!(r.byte()) {
+            ID_END => break,
+            ID_ARCHIVE_PROPERTIES => {
+                while try_opt!(r.byte()) != 0 {
+                    try_opt!(r.skip());
+                }
+            },
+            ID_ADDITIONAL_STREAMS_INFO => { try_opt!(parse_streams(r)); },
+            ID_MAIN_STREAMS_INFO => streams = Some(try_opt!(parse_streams(r))),
+            ID_FILES_INFO => files = try_opt!(parse_files(r)),
+            _ => return None,
+        }
+    }
+
+    Some((streams, files))
+}
+
+
+/// Returns a readable name for a coder that we can't decode.
+fn method_name(method: &[u8]) -> String {
+    if method == METHOD_AES {
+        "AES encryption".to_string()
+    } else if method == [0x03, 0x03, 0x01, 0x1B] {
+        "BCJ2".to_string()
+    } else {
+        let hex: Vec<String> = method.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("method {}", hex.join(""))
+    }
+}
+
+
+/// Undoes the x86 branch converter, which turns the relative addresses of
+/// CALL and JMP instructions into absolute ones so they compress better.  This
+/// follows `x86_Convert` in the LZMA SDK.
+fn bcj_x86_decode(data: &mut [u8]) {
+    const ALLOWED: [bool; 8] = [true, true, true, false, true, false, false, false];
+    const BIT_NUMBER: [usize; 8] = [0, 1, 2, 2, 3, 3, 3, 3];
+
+    fn is_ms_byte(b: u8) -> bool {
+        b == 0 || b == 0xFF
+    }
+
+    if data.len() < 5 {
+        return;
+    }
+
+    let mut pos = 0;
+    let mut prev_pos = usize::max_value();
+    let mut prev_mask = 0u32;
+
+    loop {
+        while pos < data.len() - 4 && data[pos] & 0xFE != 0xE8 {
+            pos += 1;
+        }
+        if pos >= data.len() - 4 {
+            break;
+        }
+
+        let distance = pos.wrapping_sub(prev_pos);
+        if distance > 3 {
+            prev_mask = 0;
+        } else {
+            prev_mask = (prev_mask << (distance - 1)) & 7;
+            if prev_mask != 0 {
+                let b = data[pos + 4 - BIT_NUMBER[prev_mask as usize]];
+                if !ALLOWED[prev_mask as usize] || is_ms_byte(b) {
+                    prev_pos = pos;
+                    prev_mask = ((prev_mask << 1) & 7) | 1;
+                    pos += 1;
+                    continue;
+                }
+            }
+        }
+        prev_pos = pos;
+
+        if !is_ms_byte(data[pos + 4]) {
+            prev_mask = ((prev_mask << 1) & 7) | 1;
+            pos += 1;
+            continue;
+        }
+
+        let mut src = LittleEndian::read_u32(&data[pos + 1..pos + 5]);
+        let mut dest;
+        loop {
+            dest = src.wrapping_sub((pos + 5) as u32);
+            if prev_mask == 0 {
+                break;
+            }
+
+            let index = BIT_NUMBER[prev_mask as usize] * 8;
+            if !is_ms_byte((dest >> (24 - index)) as u8) {
+                break;
+            }
+            src = dest ^ ((1 << (32 - index)) - 1);
+        }
+
+        // Sign-extend from 25 bits.
+        let dest = if dest & 0x0100_0000 != 0 { dest | 0xFF00_0000 } else { dest & 0x00FF_FFFF };
+        LittleEndian::write_u32(&mut data[pos + 1..pos + 5], dest);
+        pos += 5;
+    }
+}
+
+
+/// Wraps raw LZMA2 data in a minimal `.xz` stream, which is the only way that
+/// liblzma will decode it: one block without a check, an index and a footer.
+fn xz_wrap(dict_size: u8, data: &[u8], size: u64) -> Vec<u8> {
+    fn push_u32(out: &mut Vec<u8>, value: u32) {
+        let mut buf = [0u8; 4];
+        LittleEndian::write_u32(&mut buf, value);
+        out.extend_from_slice(&buf);
+    }
+
+    fn push_crc(out: &mut Vec<u8>, start: usize) {
+        let crc = CRC32.checksum(&out[start..]) as u32;
+        push_u32(out, crc);
+    }
+
+    fn push_vli(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn pad(out: &mut Vec<u8>, start: usize) {
+        while (out.len() - start) % 4 != 0 {
+            out.push(0);
+        }
+    }
+
+    // The stream header, with flags saying there's no check.
+    let mut out = b"\xfd7zXZ\x00\x00\x00".to_vec();
+    push_crc(&mut out, 6);
+
+    // A 12-byte block header with a single LZMA2 filter and no sizes.
+    let block = out.len();
+    out.extend_from_slice(&[0x02, 0x00, 0x21, 0x01, dict_size, 0x00, 0x00, 0x00]);
+    push_crc(&mut out, block);
+    out.extend_from_slice(data);
+    pad(&mut out, block);
+
+    let index = out.len();
+    out.extend_from_slice(&[0x00, 0x01]);
+    push_vli(&mut out, 12 + data.len() as u64);
+    push_vli(&mut out, size);
+    pad(&mut out, index);
+    push_crc(&mut out, index);
+
+    // The footer's CRC comes before the index size and flags that it covers.
+    let mut footer = vec![];
+    push_u32(&mut footer, ((out.len() - index) / 4 - 1) as u32);
+    footer.extend_from_slice(&[0x00, 0x00]);
+    push_u32(&mut out, CRC32.checksum(&footer) as u32);
+    out.extend_from_slice(&footer);
+    out.extend_from_slice(b"YZ");
+
+    out
+}
+
+
+/// Runs a single coder over its input, which should produce `size` bytes.
+fn run_coder(coder: &Coder, input: Cow<[u8]>, size: u64) -> Result<Vec<u8>, String> {
+    let method = &coder.method[..];
+
+    let out = if method == METHOD_COPY {
+        input.into_owned()
+    } else if method == METHOD_BCJ_X86 {
+        let mut out = input.into_owned();
+        bcj_x86_decode(&mut out);
+        out
+    } else {
+        let (codec, data) = if method == METHOD_LZMA {
+            if coder.props.len() != 5 {
+                return Err("invalid LZMA properties".to_string());
+            }
+            let mut data = coder.props.clone();
+            let mut size_bytes = [0u8; 8];
+            LittleEndian::write_u64(&mut size_bytes, size);
+            data.extend_from_slice(&size_bytes);
+            data.extend_from_slice(&input);
+            (Codec::Lzma, data)
+        } else if method == METHOD_LZMA2 {
+            match coder.props.first() {
+                Some(&dict_size) => (Codec::Xz, xz_wrap(dict_size, &input, size)),
+                None             => return Err("invalid LZMA2 properties".to_string()),
+            }
+        } else if method == METHOD_DEFLATE {
+            (Codec::Deflate, input.into_owned())
+        } else if method == METHOD_BZIP2 {
+            (Codec::Bzip2, input.into_owned())
+        } else {
+            return Err(format!("{} is not supported", method_name(method)));
+        };
+
+        match decompress(codec, &data, size) {
+            Ok(Some(out)) => out,
+            Ok(None)      => return Err("decompressed data is larger than expected".to_string()),
+            Err(e)        => return Err(format!("could not decompress: {}", e)),
+        }
+    };
+
+    if out.len() as u64 != size {
+        return Err("decompressed data is smaller than expected".to_string());
+    }
+
+    Ok(out)
+}
+
+
+/// Unpacks a folder, whose coders must each have one input and one output, so
+/// that they form a chain from a packed stream to the folder's contents.
+fn unpack_folder(folder: &Folder, packs: &[&[u8]], limit: u64) -> Result<Vec<u8>, String> {
+    if let Some(coder) = folder.coders.iter().find(|c| c.num_in != 1 || c.num_out != 1) {
+        return Err(format!("{} is not supported", method_name(&coder.method)));
+    }
+    if folder.sizes.iter().any(|&size| size > limit) {
+        return Err(format!("decompressed data is larger than {} bytes", limit));
+    }
+
+    // With simple coders, each coder's input and output streams have the
+    // same index as the coder.  Work back from the folder's output to find
+    // the chain of coders, and the packed stream that it starts from.
+    let mut chain = vec![];
+    let mut stream = try!(folder.main_stream().ok_or("no output stream".to_string()));
+    let mut packed = None;
+    while packed.is_none() {
+        if chain.len() >= folder.coders.len() {
+            return Err("coders form a loop".to_string());
+        }
+        chain.push(stream);
+
+        match folder.bind_pairs.iter().find(|&&(input, _)| input == stream as u64) {
+            Some(&(_, out)) if out < folder.coders.len() as u64 => stream = out as usize,
+            Some(_) => return Err("invalid bind pair".to_string()),
+            None    => {
+                let index = folder.packed.iter().position(|&input| input == stream as u64);
+                packed = Some(*try!(index.and_then(|i| packs.get(i)).ok_or("missing packed stream".to_string())));
+            },
+        }
+    }
+
+    let mut data = Cow::Borrowed(packed.unwrap());
+    for &i in chain.iter().rev() {
+        data = Cow::Owned(try!(run_coder(&folder.coders[i], data, folder.sizes[i])));
+    }
+
+    Ok(data.into_owned())
+}
+
+
+/// Returns the packed streams that each folder reads from.
+fn folder_packs<'a>(data: &'a [u8], streams: &StreamsInfo) -> Option<Vec<Vec<&'a [u8]>>> {
+    let mut offset = try_opt!((SIGNATURE_HEADER_SIZE as u64).checked_add(streams.pack_pos));
+    let mut packs = streams.pack_sizes.iter().map(|&size| {
+        let start = offset;
+        offset = offset.saturating_add(size);
+        if offset > data.len() as u64 {
+            None
+        } else {
+            Some(&data[start as usize..offset as usize])
+        }
+    });
+
+    let mut ret = Vec::with_capacity(streams.folders.len());
+    for folder in streams.folders.iter() {
+        let mut folder_packs = Vec::with_capacity(folder.packed.len());
+        for _ in 0..folder.packed.len() {
+            folder_packs.push(try_opt!(try_opt!(packs.next())));
+        }
+        ret.push(folder_packs);
+    }
+
+    Some(ret)
+}
+
+
+/// Reads the archive's header, decoding it first if it's compressed.
+fn read_header(data: &[u8], limit: u64) -> Option<(Option<StreamsInfo>, Vec<File>)> {
+    let start = try_opt!(slice(data, 0, SIGNATURE_HEADER_SIZE));
+    let offset = LittleEndian::read_u64(&start[12..20]);
+    let size = LittleEndian::read_u64(&start[20..28]);
+    if size == 0 {
+        // An empty archive.
+        return Some((None, vec![]));
+    }
+    if offset > data.len() as u64 || size > data.len() as u64 {
+        return None;
+    }
+
+    let mut header = Cow::Borrowed(try_opt!(slice(data, SIGNATURE_HEADER_SIZE + offset as usize, size as usize)));
+    for _ in 0..MAX_HEADER_NESTING {
+        let decoded = {
+            let mut r = Reader::new(&header);
+            match try_opt!(r.byte()) {
+                ID_HEADER         => return parse_header(&mut r),
+                ID_ENCODED_HEADER => {
+                    let streams = try_opt!(parse_streams(&mut r));
+                    let packs = try_opt!(folder_packs(data, &streams));
+                    let folder = try_opt!(streams.folders.first());
+                    match unpack_folder(folder, &packs[0], limit) {
+                        Ok(out) => out,
+                        Err(e)  => {
+                            warn!("7z header: {}", e);
+                            return None;
+                        },
+                    }
+                },
+                _ => return None,
+            }
+        };
+        header = Cow::Owned(decoded);
+    }
+
+    None
+}
+
+
+/// Returns the files in a 7z archive, unpacking at most `limit` bytes in
+/// total.
+pub fn entries(data: &[u8], limit: u64) -> Option<Vec<Entry<'static>>> {
+    let (streams, files) = try_opt!(read_header(data, limit));
+    let streams = match streams {
+        Some(s) => s,
+        None    => return Some(vec![]),
+    };
+    let packs = try_opt!(folder_packs(data, &streams));
+
+    // Files without any contents (i.e. directories and empty files) don't
+    // have a stream, and the rest are stored in order.  Archives without a
+    // list of files are unusual, but could still be scanned.
+    let mut files = files.into_iter().filter(|f| f.has_stream).map(|f| f.name);
+    let mut entries = vec![];
+    let mut total = 0u64;
+
+    for ((folder, sizes), packs) in streams.folders.iter().zip(streams.substreams.iter()).zip(packs.iter()) {
+        let mut names = Vec::with_capacity(sizes.len());
+        for _ in 0..sizes.len() {
+            let index = entries.len() + names.len();
+            names.push(files.next().unwrap_or_else(|| format!("{}", index)));
+        }
+        if names.is_empty() {
+            continue;
+        }
+
+        let size = folder.unpack_size();
+        if total.saturating_add(size) > limit {
+            warn!("{}: files larger than {} bytes in total, skipping the rest", names[0], limit);
+            break;
+        }
+
+        let contents = match unpack_folder(folder, packs, limit - total) {
+            Ok(c)  => c,
+            Err(e) => {
+                warn!("{}: {}, skipping {} file(s)", names[0], e, names.len());
+                continue;
+            },
+        };
+        total += contents.len() as u64;
+
+        let mut offset = 0;
+        for (name, &size) in names.into_iter().zip(sizes.iter()) {
+            let end = offset + size as usize;
+            entries.push(Entry {
+                name:  name.replace('\\', "/"),
+                data:  Cow::Owned(contents[offset..end].to_vec()),
+                codec: None,
+                base:  0,
+            });
+            offset = end;
+        }
+    }
+
+    Some(entries)
+}
+
+// ----------------------------------------------------------------------
+
+/// Builds an archive that stores each of the given files in its own folder,
+/// without compression.  Empty files have no stream.
+#[cfg(test)]
+pub fn test_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let streamed: Vec<&[u8]> = files.iter().map(|&(_, body)| body).filter(|body| !body.is_empty()).collect();
+
+    // All of the numbers used here fit in a single byte.
+    let mut header = vec![ID_HEADER, ID_MAIN_STREAMS_INFO, ID_PACK_INFO, 0, streamed.len() as u8, ID_SIZE];
+    header.extend(streamed.iter().map(|body| body.len() as u8));
+    header.extend_from_slice(&[ID_END, ID_UNPACK_INFO, ID_FOLDER, streamed.len() as u8, 0]);
+    for _ in streamed.iter() {
+        header.extend_from_slice(&[1, 0x01, 0x00]);
+    }
+    header.push(ID_CODERS_UNPACK_SIZE);
+    header.extend(streamed.iter().map(|body| body.len() as u8));
+    header.extend_from_slice(&[ID_END, ID_END]);
+
+    header.extend_from_slice(&[ID_FILES_INFO, files.len() as u8]);
+    if streamed.len() != files.len() {
+        let mut empty = vec![0u8; (files.len() + 7) / 8];
+        for (i, &(_, body)) in files.iter().enumerate() {
+            if body.is_empty() {
+                empty[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+        header.extend_from_slice(&[ID_EMPTY_STREAM, empty.len() as u8]);
+        header.extend(empty);
+    }
+    let mut names = vec![0u8];
+    for &(name, _) in files.iter() {
+        for c in name.encode_utf16().chain(Some(0)) {
+            names.extend_from_slice(&[c as u8, (c >> 8) as u8]);
+        }
+    }
+    header.extend_from_slice(&[ID_NAME, names.len() as u8]);
+    header.extend(names);
+    header.extend_from_slice(&[ID_END, ID_END]);
+
+    let mut data = b"7z\xbc\xaf\x27\x1c\x00\x04".to_vec();
+    data.extend_from_slice(&[0u8; 24]);
+    let packed = streamed.iter().fold(0, |n, body| n + body.len());
+    LittleEndian::write_u64(&mut data[12..20], packed as u64);
+    LittleEndian::write_u64(&mut data[20..28], header.len() as u64);
+    for body in streamed {
+        data.extend_from_slice(body);
+    }
+    data.extend(header);
+    data
+}
+
+#[test]
+fn test_entries() {
+    let data = test_archive(&[("dir\\a.txt", b"hello"), ("empty", b""), ("b.bin", b"world!")]);
+    let files = entries(&data, 1024).unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].name, "dir/a.txt");
+    assert_eq!(&files[0].data[..], b"hello");
+    assert_eq!(files[1].name, "b.bin");
+    assert_eq!(&files[1].data[..], b"world!");
+
+    // Only the first file fits.
+    assert_eq!(entries(&data, 10).unwrap().len(), 1);
+
+    // Truncated archives are rejected.
+    assert!(entries(&data[..data.len() - 1], 1024).is_none());
+}
+
+#[test]
+fn test_bcj_x86_decode() {
+    // Two CALLs and a JMP, as converted by liblzma's x86 filter.
+    let mut data = vec![0x90, 0xe8, 0x16, 0x00, 0x00, 0x00, 0x90, 0xe8, 0xfc, 0xff, 0xff, 0xff, 0x90,
+                        0x90, 0x90, 0x90, 0xe9, 0x15, 0x01, 0x00, 0x00, 0x90, 0x90, 0x90, 0x90, 0x90];
+    bcj_x86_decode(&mut data);
+    assert_eq!(data, vec![0x90, 0xe8, 0x10, 0x00, 0x00, 0x00, 0x90, 0xe8, 0xf0, 0xff, 0xff, 0xff, 0x90,
+                          0x90, 0x90, 0x90, 0xe9, 0x00, 0x01, 0x00, 0x00, 0x90, 0x90, 0x90, 0x90, 0x90]);
+}
@@ -0,0 +1,64 @@
+use super::{cstr, parse_num, Entry};
+
+
+const BLOCK: usize = 512;
+
+
+/// Parses the size field of a tar header, which is either octal or - for
+/// large files in GNU tar - a base-256 number with the high bit set.
+fn parse_size(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        let mut n = (field[0] & 0x7F) as u64;
+        for &b in field[1..].iter() {
+            n = try_opt!(n.checked_mul(256)) | b as u64;
+        }
+        Some(n)
+    } else {
+        parse_num(field, 8)
+    }
+}
+
+
+/// Returns the regular files in a (ustar or GNU) tar archive.
+pub fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    let mut ret = vec![];
+    let mut off = 0;
+    let mut long_name = None;
+
+    while off + BLOCK <= data.len() {
+        let header = &data[off..off + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let size = try_opt!(parse_size(&header[124..136])) as usize;
+        let start = off + BLOCK;
+        if start.checked_add(size).map_or(true, |end| end > data.len()) {
+            return None;
+        }
+        let body = &data[start..start + size];
+
+        let name = match long_name.take() {
+            Some(n) => n,
+            None => {
+                let name = cstr(&header[0..100]);
+                let prefix = cstr(&header[345..500]);
+                if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) }
+            },
+        };
+
+        match header[156] {
+            // Regular files
+            b'0' | b'\0' | b'7' => ret.push(Entry::new(name, body, None)),
+
+            // GNU long name - applies to the next entry.
+            b'L' => long_name = Some(cstr(body)),
+
+            _ => {},
+        }
+
+        off = start + (size + BLOCK - 1) / BLOCK * BLOCK;
+    }
+
+    Some(ret)
+}
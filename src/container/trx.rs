@@ -0,0 +1,59 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use super::{slice, Entry};
+
+
+/// Returns the partitions in a TRX image, as used by Broadcom-based routers.
+/// Version 1 has three partition offsets (usually loader, kernel and root
+/// filesystem), and version 2 has four.
+pub fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    let header = try_opt!(slice(data, 0, 28));
+
+    let len = LittleEndian::read_u32(&header[4..8]) as usize;
+    let version = LittleEndian::read_u32(&header[12..16]) >> 16;
+    let count = if version >= 2 { 4 } else { 3 };
+
+    let mut offsets = vec![];
+    for i in 0..count {
+        let off = LittleEndian::read_u32(try_opt!(slice(data, 16 + i * 4, 4))) as usize;
+        if off != 0 {
+            offsets.push(off);
+        }
+    }
+
+    let end = if len > data.len() { data.len() } else { len };
+
+    let mut ret = vec![];
+    for (i, &start) in offsets.iter().enumerate() {
+        let stop = offsets.get(i + 1).cloned().unwrap_or(end);
+        if start >= stop || stop > data.len() {
+            return None;
+        }
+
+        ret.push(Entry::new(format!("partition{}", i), &data[start..stop], None));
+    }
+
+    Some(ret)
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_entries() {
+    let mut data = vec![0u8; 40];
+    data[..4].copy_from_slice(b"HDR0");
+    LittleEndian::write_u32(&mut data[4..], 40);
+    LittleEndian::write_u32(&mut data[12..], 1 << 16);
+    LittleEndian::write_u32(&mut data[16..], 28);
+    LittleEndian::write_u32(&mut data[20..], 32);
+    data[28..40].copy_from_slice(b"loadkernelfs");
+
+    let images = entries(&data).unwrap();
+    assert_eq!(images.iter().map(|e| &e.name[..]).collect::<Vec<_>>(), vec!["partition0", "partition1"]);
+    assert_eq!(&*images[0].data, b"load");
+    assert_eq!(&*images[1].data, b"kernelfs");
+
+    // Partitions must be in order.
+    LittleEndian::write_u32(&mut data[20..], 20);
+    assert!(entries(&data).is_none());
+}
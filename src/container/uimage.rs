@@ -0,0 +1,105 @@
+use byteorder::{ByteOrder, BigEndian};
+
+use super::{cstr, slice, Codec, Entry};
+
+
+const HEADER_SIZE: usize = 64;
+
+const IH_TYPE_MULTI: u8 = 4;
+
+
+/// Returns the image(s) in a legacy U-Boot uImage.  Single images are based at
+/// their load address.
+pub fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    let header = try_opt!(slice(data, 0, HEADER_SIZE));
+
+    let size = BigEndian::read_u32(&header[12..16]) as usize;
+    let load = BigEndian::read_u32(&header[16..20]) as u64;
+    let kind = header[30];
+    let name = match cstr(&header[32..64]) {
+        ref n if n.is_empty() => "image".to_string(),
+        n                     => n.replace('/', "_"),
+    };
+
+    let codec = match header[31] {
+        0 => None,
+        1 => Some(Codec::Gzip),
+        2 => Some(Codec::Bzip2),
+        3 => Some(Codec::Lzma),
+        c => {
+            warn!("{}: unsupported uImage compression type {}", name, c);
+            return Some(vec![]);
+        },
+    };
+
+    let payload = try_opt!(slice(data, HEADER_SIZE, size));
+
+    if kind != IH_TYPE_MULTI {
+        let mut entry = Entry::new(name, payload, codec);
+        entry.base = load;
+        return Some(vec![entry]);
+    }
+
+    // Multi-file images start with a zero-terminated list of sizes, and each
+    // image is padded to four bytes.
+    let mut sizes = vec![];
+    let mut off = 0;
+    loop {
+        let size = BigEndian::read_u32(try_opt!(slice(payload, off, 4))) as usize;
+        off += 4;
+        if size == 0 {
+            break;
+        }
+        sizes.push(size);
+    }
+
+    let mut ret = vec![];
+    for (i, size) in sizes.into_iter().enumerate() {
+        let image = try_opt!(slice(payload, off, size));
+        ret.push(Entry::new(format!("{}/{}", name, i), image, codec));
+        off += (size + 3) & !3;
+    }
+
+    Some(ret)
+}
+
+// ----------------------------------------------------------------------
+
+#[cfg(test)]
+fn test_header(size: usize, kind: u8, compression: u8, name: &[u8]) -> Vec<u8> {
+    let mut data = vec![0u8; HEADER_SIZE];
+    BigEndian::write_u32(&mut data[0..], 0x27051956);
+    BigEndian::write_u32(&mut data[12..], size as u32);
+    BigEndian::write_u32(&mut data[16..], 0x80008000);
+    data[30] = kind;
+    data[31] = compression;
+    data[32..32 + name.len()].copy_from_slice(name);
+    data
+}
+
+#[test]
+fn test_entries() {
+    let mut data = test_header(4, 2, 1, b"Linux/kernel");
+    data.extend(b"abcd");
+
+    let images = entries(&data).unwrap();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].name, "Linux_kernel");
+    assert_eq!(images[0].base, 0x80008000);
+    assert_eq!(images[0].codec, Some(Codec::Gzip));
+    assert_eq!(&*images[0].data, b"abcd");
+
+    assert!(entries(&data[..HEADER_SIZE + 2]).is_none());
+}
+
+#[test]
+fn test_multi_entries() {
+    let mut data = test_header(18, IH_TYPE_MULTI, 0, b"");
+    data.extend(&[0, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 0]);
+    data.extend(b"abc\0de");
+
+    let images = entries(&data).unwrap();
+    assert_eq!(images.iter().map(|e| &e.name[..]).collect::<Vec<_>>(), vec!["image/0", "image/1"]);
+    assert_eq!(&*images[0].data, b"abc");
+    assert_eq!(&*images[1].data, b"de");
+}
@@ -0,0 +1,98 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use super::{slice, Codec, Entry};
+
+
+const EOCD_SIG: u32 = 0x06054b50;
+const CENTRAL_SIG: u32 = 0x02014b50;
+const LOCAL_SIG: u32 = 0x04034b50;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+
+fn u16_at(data: &[u8], off: usize) -> Option<u16> {
+    slice(data, off, 2).map(LittleEndian::read_u16)
+}
+
+fn u32_at(data: &[u8], off: usize) -> Option<u32> {
+    slice(data, off, 4).map(LittleEndian::read_u32)
+}
+
+
+/// Finds the end of central directory record, which is at the end of the file
+/// but may be followed by a comment of up to 64KiB.
+fn find_eocd(data: &[u8]) -> Option<usize> {
+    if data.len() < 22 {
+        return None;
+    }
+
+    let last = data.len() - 22;
+    let first = last.saturating_sub(0xFFFF);
+    (first..last + 1).rev().find(|&off| u32_at(data, off) == Some(EOCD_SIG))
+}
+
+
+/// Returns the files in a ZIP archive (including JARs, APKs, etc.), using the
+/// central directory.
+pub fn entries(data: &[u8]) -> Option<Vec<Entry>> {
+    let eocd = try_opt!(find_eocd(data));
+    let count = try_opt!(u16_at(data, eocd + 10)) as usize;
+    let mut off = try_opt!(u32_at(data, eocd + 16)) as usize;
+
+    let mut ret = vec![];
+    for _ in 0..count {
+        if try_opt!(u32_at(data, off)) != CENTRAL_SIG {
+            return None;
+        }
+
+        let method = try_opt!(u16_at(data, off + 10));
+        let comp_size = try_opt!(u32_at(data, off + 20)) as usize;
+        let name_len = try_opt!(u16_at(data, off + 28)) as usize;
+        let extra_len = try_opt!(u16_at(data, off + 30)) as usize;
+        let comment_len = try_opt!(u16_at(data, off + 32)) as usize;
+        let local = try_opt!(u32_at(data, off + 42)) as usize;
+        let name = String::from_utf8_lossy(try_opt!(slice(data, off + 46, name_len))).into_owned();
+
+        off += 46 + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') {
+            continue;
+        }
+        if comp_size == 0xFFFFFFFF || local == 0xFFFFFFFF {
+            warn!("{}: ZIP64 members are not supported, skipping", name);
+            continue;
+        }
+
+        // The local header's name and extra field lengths can differ from the
+        // central directory's, so we need to read them to find the data.
+        if u32_at(data, local) != Some(LOCAL_SIG) {
+            warn!("{}: bad local header, skipping", name);
+            continue;
+        }
+        let local_name_len = try_opt!(u16_at(data, local + 26)) as usize;
+        let local_extra_len = try_opt!(u16_at(data, local + 28)) as usize;
+        let start = local + 30 + local_name_len + local_extra_len;
+
+        let body = match slice(data, start, comp_size) {
+            Some(b) => b,
+            None    => {
+                warn!("{}: truncated, skipping", name);
+                continue;
+            },
+        };
+
+        let codec = match method {
+            METHOD_STORED  => None,
+            METHOD_DEFLATE => Some(Codec::Deflate),
+            _ => {
+                warn!("{}: unsupported compression method {}, skipping", name, method);
+                continue;
+            },
+        };
+
+        ret.push(Entry::new(name, body, codec));
+    }
+
+    Some(ret)
+}
@@ -0,0 +1,165 @@
+use alphabet::{self, AlphabetMatch};
+use binfmt::Binary;
+use imports::{self, CryptoImport};
+use instructions::{self, Region};
+use scanner::{Match, Scanner};
+
+
+/// What's known about the data being searched, other than its contents.
+pub struct Source<'a> {
+    /// The name of the data - a file, a member of an archive or a mapping in a
+    /// process.
+    pub path: &'a str,
+
+    /// The address that the data is loaded at.
+    pub base: u64,
+
+    /// The data parsed as an executable, if it is one.
+    pub binary: Option<&'a Binary>,
+}
+
+
+/// Something found by a detector.
+pub enum Finding<'a> {
+    /// A table of constants.
+    Table(Match<'a>),
+
+    /// A custom encoding alphabet.
+    Alphabet(AlphabetMatch),
+
+    /// An imported cryptographic API.
+    Import(CryptoImport),
+
+    /// Code that uses cryptographic instructions.
+    Instructions(Region),
+
+    /// Anything else, for detectors that don't fit the other kinds.
+    Other {
+        offset: usize,
+        len: usize,
+        algorithm: String,
+        desc: String,
+    },
+}
+
+
+/// A way of finding cryptography in some data.
+pub trait Detector {
+    /// The name used to enable or disable the detector.
+    fn name(&self) -> &'static str;
+
+    /// A short description, for listing the detectors.
+    fn description(&self) -> &'static str;
+
+    /// Searches the data.
+    fn detect<'a>(&'a self, data: &[u8], source: &Source) -> Vec<Finding<'a>>;
+}
+
+
+impl Detector for Scanner {
+    fn name(&self) -> &'static str {
+        "tables"
+    }
+
+    fn description(&self) -> &'static str {
+        "Tables of constants, in both byte orders"
+    }
+
+    fn detect<'a>(&'a self, data: &[u8], _source: &Source) -> Vec<Finding<'a>> {
+        self.scan_bytes(data).into_iter().map(Finding::Table).collect()
+    }
+}
+
+
+pub struct Alphabets;
+
+impl Detector for Alphabets {
+    fn name(&self) -> &'static str {
+        "alphabets"
+    }
+
+    fn description(&self) -> &'static str {
+        "Custom Base64, Base32 and Base58 alphabets"
+    }
+
+    fn detect<'a>(&'a self, data: &[u8], _source: &Source) -> Vec<Finding<'a>> {
+        alphabet::find_custom_alphabets(data).into_iter().map(Finding::Alphabet).collect()
+    }
+}
+
+
+pub struct Imports;
+
+impl Detector for Imports {
+    fn name(&self) -> &'static str {
+        "imports"
+    }
+
+    fn description(&self) -> &'static str {
+        "Imported cryptographic APIs in ELF and PE files"
+    }
+
+    fn detect<'a>(&'a self, _data: &[u8], source: &Source) -> Vec<Finding<'a>> {
+        match source.binary {
+            Some(bin) => imports::crypto_imports(bin).into_iter().map(Finding::Import).collect(),
+            None      => vec![],
+        }
+    }
+}
+
+
+pub struct Instructions;
+
+impl Detector for Instructions {
+    fn name(&self) -> &'static str {
+        "instructions"
+    }
+
+    fn description(&self) -> &'static str {
+        "Hardware cryptographic instructions (AES-NI, SHA-NI, ARMv8 Crypto, ...)"
+    }
+
+    fn detect<'a>(&'a self, data: &[u8], source: &Source) -> Vec<Finding<'a>> {
+        instructions::find_regions(data, source.binary).into_iter().map(Finding::Instructions).collect()
+    }
+}
+
+
+pub static ALPHABETS: Alphabets = Alphabets;
+pub static IMPORTS: Imports = Imports;
+pub static INSTRUCTIONS: Instructions = Instructions;
+
+
+/// Returns all of the built-in detectors, with the given scanner for tables.
+pub fn builtin(scanner: &Scanner) -> Vec<&Detector> {
+    vec![scanner, &ALPHABETS, &IMPORTS, &INSTRUCTIONS]
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_builtin() {
+    let scanner = Scanner::new(::patterns::get_patterns());
+    let detectors = builtin(&scanner);
+
+    let names = detectors.iter().map(|d| d.name()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["tables", "alphabets", "imports", "instructions"]);
+
+    let data = scanner.patterns().iter()
+        .find(|p| p.algorithm == "SHA-256")
+        .unwrap()
+        .bytes.as_byte_vec(::endian::Endianness::BigEndian);
+    let source = Source { path: "test", base: 0, binary: None };
+
+    let findings = detectors[0].detect(&data, &source);
+    assert_eq!(findings.len(), 1);
+    match findings[0] {
+        Finding::Table(ref m) => {
+            assert_eq!((m.pattern.algorithm, m.offset), ("SHA-256", 0));
+            assert_eq!(m.endianness, ::endian::Endianness::BigEndian);
+        },
+        _                     => panic!("expected a table"),
+    }
+
+    assert!(detectors[2].detect(&data, &source).is_empty());
+}
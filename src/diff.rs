@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::json::{Json, ToJson};
+
+
+/// The tables found in each file, keyed by path and then by algorithm and
+/// variable name, with the number of times each was found.  Offsets are left
+/// out, so that code moving around inside a file doesn't count as a change.
+pub type Inventory = BTreeMap<String, BTreeMap<(String, String), usize>>;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Added,
+    Removed,
+
+    /// A table that was removed from one file and added to another.
+    Moved,
+
+    /// A table that's found a different number of times.
+    Count,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Kind::Added   => "added",
+            Kind::Removed => "removed",
+            Kind::Moved   => "moved",
+            Kind::Count   => "count",
+        }
+    }
+}
+
+
+/// A difference between two inventories.  Changes without a variable name are
+/// for an algorithm as a whole, e.g. the first DES table appearing in a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub kind: Kind,
+    pub path: String,
+
+    /// For moved tables, the file that the table was in before.
+    pub from: Option<String>,
+
+    pub algorithm: String,
+    pub varname: Option<String>,
+    pub old_count: usize,
+    pub new_count: usize,
+}
+
+impl ToJson for Change {
+    fn to_json(&self) -> Json {
+        let mut obj = BTreeMap::new();
+        obj.insert("kind".to_string(),      self.kind.name().to_json());
+        obj.insert("path".to_string(),      self.path.to_json());
+        obj.insert("algorithm".to_string(), self.algorithm.to_json());
+        obj.insert("old_count".to_string(), self.old_count.to_json());
+        obj.insert("new_count".to_string(), self.new_count.to_json());
+        if let Some(ref from) = self.from {
+            obj.insert("from".to_string(), from.to_json());
+        }
+        if let Some(ref varname) = self.varname {
+            obj.insert("varname".to_string(), varname.to_json());
+        }
+        Json::Object(obj)
+    }
+}
+
+
+/// Returns the files to compare under the given path, keyed by their path
+/// relative to it.  A path that isn't a directory is keyed by `name`, so that
+/// two single files with different names can still be compared.
+pub fn files(root: &Path, name: &str) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut ret = vec![];
+    if try!(fs::metadata(root)).is_dir() {
+        try!(files_in(root, "", &mut ret));
+    } else {
+        ret.push((name.to_string(), root.to_path_buf()));
+    }
+    ret.sort();
+    Ok(ret)
+}
+
+
+fn files_in(dir: &Path, prefix: &str, out: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for entry in try!(fs::read_dir(dir)) {
+        let entry = try!(entry);
+        let path = entry.path();
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+
+        // Broken symlinks and the like are skipped, rather than stopping the
+        // whole comparison.
+        match fs::metadata(&path) {
+            Ok(ref m) if m.is_dir()  => try!(files_in(&path, &format!("{}/", name), out)),
+            Ok(ref m) if m.is_file() => out.push((name, path)),
+            Ok(_)                    => {},
+            Err(e)                   => warn!("Skipping {}: {}", path.display(), e),
+        }
+    }
+    Ok(())
+}
+
+
+/// Returns the algorithms in a file's inventory, and the number of tables
+/// found for each.
+fn algorithms(tables: &BTreeMap<(String, String), usize>) -> BTreeMap<&str, usize> {
+    let mut ret = BTreeMap::new();
+    for (&(ref algorithm, _), &count) in tables {
+        *ret.entry(&algorithm[..]).or_insert(0) += count;
+    }
+    ret
+}
+
+
+/// Compares two inventories, returning the changes sorted by path.
+pub fn compare(old: &Inventory, new: &Inventory) -> Vec<Change> {
+    let empty = BTreeMap::new();
+    let paths = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+
+    let mut changes = vec![];
+    for path in paths {
+        let before = old.get(path).unwrap_or(&empty);
+        let after = new.get(path).unwrap_or(&empty);
+
+        let (old_algs, new_algs) = (algorithms(before), algorithms(after));
+        for (&algorithm, &count) in &new_algs {
+            if !old_algs.contains_key(algorithm) {
+                changes.push(Change {
+                    kind:      Kind::Added,
+                    path:      path.clone(),
+                    from:      None,
+                    algorithm: algorithm.to_string(),
+                    varname:   None,
+                    old_count: 0,
+                    new_count: count,
+                });
+            }
+        }
+        for (&algorithm, &count) in &old_algs {
+            if !new_algs.contains_key(algorithm) {
+                changes.push(Change {
+                    kind:      Kind::Removed,
+                    path:      path.clone(),
+                    from:      None,
+                    algorithm: algorithm.to_string(),
+                    varname:   None,
+                    old_count: count,
+                    new_count: 0,
+                });
+            }
+        }
+
+        let keys = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+        for key in keys {
+            let old_count = before.get(key).cloned().unwrap_or(0);
+            let new_count = after.get(key).cloned().unwrap_or(0);
+            let kind = match (old_count, new_count) {
+                (0, _)             => Kind::Added,
+                (_, 0)             => Kind::Removed,
+                (a, b) if a != b   => Kind::Count,
+                _                  => continue,
+            };
+
+            changes.push(Change {
+                kind:      kind,
+                path:      path.clone(),
+                from:      None,
+                algorithm: key.0.clone(),
+                varname:   Some(key.1.clone()),
+                old_count: old_count,
+                new_count: new_count,
+            });
+        }
+    }
+
+    // A table that's removed from exactly one file and added to exactly one
+    // other, the same number of times, has moved.  The removal is folded into
+    // the addition.  Anything less clear-cut, such as a table that was in one
+    // file and is now in two, is left as separate changes.
+    let mut moves = vec![];
+    {
+        let mut tables = BTreeMap::new();
+        for (i, c) in changes.iter().enumerate() {
+            let varname = match c.varname {
+                Some(ref v) => v,
+                None        => continue,
+            };
+            let entry = tables.entry((&c.algorithm, varname)).or_insert((vec![], vec![]));
+            match c.kind {
+                Kind::Added   => entry.0.push(i),
+                Kind::Removed => entry.1.push(i),
+                _             => {},
+            }
+        }
+
+        for &(ref added, ref removed) in tables.values() {
+            if added.len() == 1 && removed.len() == 1 &&
+                changes[added[0]].new_count == changes[removed[0]].old_count
+            {
+                moves.push((added[0], removed[0]));
+            }
+        }
+    }
+
+    for &(added, removed) in &moves {
+        changes[added].kind = Kind::Moved;
+        changes[added].from = Some(changes[removed].path.clone());
+        changes[added].old_count = changes[removed].old_count;
+    }
+
+    let removed = moves.iter().map(|&(_, r)| r).collect::<BTreeSet<_>>();
+    changes.into_iter()
+        .enumerate()
+        .filter(|&(i, _)| !removed.contains(&i))
+        .map(|(_, c)| c)
+        .collect()
+}
+
+
+/// Formats a change for the text output, without its path.
+pub fn describe(c: &Change) -> String {
+    let table = match c.varname {
+        Some(ref v) => format!("{} ({})", c.algorithm, v),
+        None        => format!("algorithm {}", c.algorithm),
+    };
+
+    match c.kind {
+        Kind::Added   => format!("+ {} x{}", table, c.new_count),
+        Kind::Removed => format!("- {} x{}", table, c.old_count),
+        Kind::Moved   => format!("> {} x{}, moved from {}", table, c.new_count, c.from.as_ref().unwrap()),
+        Kind::Count   => format!("~ {} x{} -> x{}", table, c.old_count, c.new_count),
+    }
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_compare() {
+    fn inventory(entries: &[(&str, &str, &str, usize)]) -> Inventory {
+        let mut ret = Inventory::new();
+        for &(path, algorithm, varname, count) in entries {
+            ret.entry(path.to_string()).or_insert(BTreeMap::new())
+                .insert((algorithm.to_string(), varname.to_string()), count);
+        }
+        ret
+    }
+
+    let old = inventory(&[
+        ("bin/app",    "MD5",      "hash::MD5_T",        1),
+        ("bin/app",    "CRC32",    "crc::CRC32",         1),
+        ("lib/foo.so", "Rijndael", "crypto::RIJNDAEL_TE0", 1),
+        ("lib/foo.so", "SHA-256",  "hash::SHA256_K",     1),
+    ]);
+    let new = inventory(&[
+        ("bin/app",    "CRC32",    "crc::CRC32",         2),
+        ("bin/app",    "SHA-256",  "hash::SHA256_K",     1),
+        ("lib/bar.so", "Rijndael", "crypto::RIJNDAEL_TE0", 1),
+        ("lib/foo.so", "SHA-256",  "hash::SHA256_K",     1),
+    ]);
+
+    let changes = compare(&old, &new);
+    let described = changes.iter()
+        .map(|c| format!("{}: {}", c.path, describe(c)))
+        .collect::<Vec<_>>();
+
+    assert_eq!(described, vec![
+        "bin/app: + algorithm SHA-256 x1",
+        "bin/app: - algorithm MD5 x1",
+        "bin/app: ~ CRC32 (crc::CRC32) x1 -> x2",
+        "bin/app: - MD5 (hash::MD5_T) x1",
+        "bin/app: + SHA-256 (hash::SHA256_K) x1",
+        "lib/bar.so: + algorithm Rijndael x1",
+        "lib/bar.so: > Rijndael (crypto::RIJNDAEL_TE0) x1, moved from lib/foo.so",
+        "lib/foo.so: - algorithm Rijndael x1",
+    ]);
+
+    assert!(compare(&old, &old).is_empty());
+
+    // A table that's now in two files, or found a different number of times,
+    // hasn't simply moved.
+    let new = inventory(&[
+        ("bin/app",    "MD5",      "hash::MD5_T",        1),
+        ("bin/app",    "CRC32",    "crc::CRC32",         1),
+        ("lib/bar.so", "Rijndael", "crypto::RIJNDAEL_TE0", 1),
+        ("lib/baz.so", "Rijndael", "crypto::RIJNDAEL_TE0", 1),
+        ("lib/foo.so", "SHA-256",  "hash::SHA256_K",     1),
+    ]);
+    assert!(compare(&old, &new).iter().all(|c| c.kind != Kind::Moved));
+
+    let new = inventory(&[
+        ("bin/app",    "MD5",      "hash::MD5_T",        1),
+        ("bin/app",    "CRC32",    "crc::CRC32",         1),
+        ("lib/bar.so", "Rijndael", "crypto::RIJNDAEL_TE0", 2),
+        ("lib/foo.so", "SHA-256",  "hash::SHA256_K",     1),
+    ]);
+    assert!(compare(&old, &new).iter().all(|c| c.kind != Kind::Moved));
+}
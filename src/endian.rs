@@ -57,18 +57,32 @@ pub struct EndianWrapper<'a, T: AsBytes + 'a>(pub &'a [T]);
 
 // ----------------------------------------------------------------------
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Endianness {
     LittleEndian,
     BigEndian,
 }
 
+impl Endianness {
+    /// Returns the short name used in output, "LE" or "BE".
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Endianness::LittleEndian => "LE",
+            Endianness::BigEndian    => "BE",
+        }
+    }
+}
+
 /// AsByteVec is a trait for things that can convert themselves to vectors of
 /// bytes with a given endianness.
 pub trait AsByteVec {
     /// This function converts this item into a vector of bytes, using the
     /// given endianness.
     fn as_byte_vec(&self, e: Endianness) -> Vec<u8>;
+
+    /// The size in bytes of each element, which is what the endianness
+    /// applies to.
+    fn element_width(&self) -> usize;
 }
 
 impl<'a, T: AsBytes + 'a> AsByteVec for EndianWrapper<'a, T> {
@@ -92,6 +106,10 @@ impl<'a, T: AsBytes + 'a> AsByteVec for EndianWrapper<'a, T> {
 
         v
     }
+
+    fn element_width(&self) -> usize {
+        size_of::<T>()
+    }
 }
 
 // Useful implementations for things behind references.
@@ -99,6 +117,10 @@ impl<'a, T: AsByteVec> AsByteVec for &'a T {
     fn as_byte_vec(&self, e: Endianness) -> Vec<u8> {
         (**self).as_byte_vec(e)
     }
+
+    fn element_width(&self) -> usize {
+        (**self).element_width()
+    }
 }
 
 // Useful implementations for things behind mutable references.
@@ -106,6 +128,10 @@ impl<'a, T: AsByteVec> AsByteVec for &'a mut T {
     fn as_byte_vec(&self, e: Endianness) -> Vec<u8> {
         (**self).as_byte_vec(e)
     }
+
+    fn element_width(&self) -> usize {
+        (**self).element_width()
+    }
 }
 
 // ----------------------------------------------------------------------
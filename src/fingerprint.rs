@@ -0,0 +1,465 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use aho_corasick::{Automaton, AcAutomaton};
+
+use scanner::Match;
+
+
+/// How far apart (in bytes) two tables can be and still count as stored next
+/// to each other, to allow for alignment.
+const LAYOUT_SLACK: usize = 32;
+
+
+/// Describes how to recognize a particular cryptographic library.
+pub struct Library {
+    pub name: &'static str,
+
+    /// Strings that are immediately followed by the library's version number
+    /// (e.g. "OpenSSL " in "OpenSSL 1.0.2k  26 Jan 2017").  A prefix can
+    /// include the start of the version (e.g. "go1."), which is kept as part
+    /// of it.
+    pub version_prefixes: &'static [&'static str],
+
+    /// Other strings that are characteristic of this library - error
+    /// messages, source paths, RTTI names and so on.
+    pub markers: &'static [&'static str],
+
+    /// Algorithms (as in `Pattern::algorithm`) whose tables this library
+    /// embeds.  Finding these tables isn't enough to identify a library, but
+    /// it is reported as supporting evidence.
+    pub algorithms: &'static [&'static str],
+
+    /// Tables (as in `Pattern::varname`) that this library stores one after
+    /// another, in this order.  Other libraries embed the same tables, but
+    /// finding them laid out like this is stronger evidence than the tables
+    /// alone.
+    pub layouts: &'static [&'static [&'static str]],
+
+    /// If true, a version string alone isn't enough to identify this library;
+    /// at least one marker must also be found.
+    pub version_needs_marker: bool,
+
+    /// Libraries that embed this library's strings.  If any of these are
+    /// identified, this library isn't reported (e.g. BoringSSL's version
+    /// string contains "OpenSSL 1.1.1").
+    pub superseded_by: &'static [&'static str],
+}
+
+
+pub static LIBRARIES: &'static [Library] = &[
+    Library {
+        name:                 "OpenSSL",
+        version_prefixes:     &["OpenSSL "],
+        markers:              &["appro@openssl.org", "crypto/evp/", "ssl/statem/", "OPENSSL_ia32cap"],
+        algorithms:           &["Rijndael", "Blowfish", "DES", "SHA-256", "SHA-512", "Keccak"],
+        layouts:              &[
+            // 0.9.8 keeps Te4; later versions go straight on to Td0.
+            &["crypto::RIJNDAEL_TE0", "crypto::RIJNDAEL_TE1", "crypto::RIJNDAEL_TE2", "crypto::RIJNDAEL_TE3",
+              "crypto::RIJNDAEL_TE4"],
+            &["crypto::RIJNDAEL_TE0", "crypto::RIJNDAEL_TE1", "crypto::RIJNDAEL_TE2", "crypto::RIJNDAEL_TE3",
+              "crypto::RIJNDAEL_TD0"],
+        ],
+        version_needs_marker: false,
+        superseded_by:        &["LibreSSL", "BoringSSL"],
+    },
+    Library {
+        name:                 "LibreSSL",
+        version_prefixes:     &["LibreSSL "],
+        markers:              &["LIBRESSL_"],
+        algorithms:           &["Rijndael", "Blowfish", "DES", "SHA-256", "SHA-512"],
+        layouts:              &[],
+        version_needs_marker: false,
+        superseded_by:        &[],
+    },
+    Library {
+        name:                 "BoringSSL",
+        version_prefixes:     &[],
+        markers:              &["BoringSSL", "BORINGSSL_"],
+        algorithms:           &["Rijndael", "DES", "SHA-256", "SHA-512"],
+        layouts:              &[],
+        version_needs_marker: false,
+        superseded_by:        &[],
+    },
+    Library {
+        name:                 "mbedTLS",
+        version_prefixes:     &["mbed TLS ", "Mbed TLS ", "PolarSSL "],
+        markers:              &["MBEDTLS_ERR_", "mbedtls_", "POLARSSL_ERR_"],
+        algorithms:           &["Rijndael", "Blowfish", "DES", "SHA-256", "SHA-512", "MD5"],
+        layouts:              &[
+            // FSb, then FT0 - FT3.
+            &["crypto::RIJNDAEL_SBOX", "crypto::RIJNDAEL_TE0", "crypto::RIJNDAEL_TE1", "crypto::RIJNDAEL_TE2",
+              "crypto::RIJNDAEL_TE3"],
+        ],
+        version_needs_marker: false,
+        superseded_by:        &[],
+    },
+    Library {
+        name:                 "wolfSSL",
+        version_prefixes:     &["wolfSSL "],
+        markers:              &["wolfSSL_", "wolfcrypt", "CyaSSL"],
+        algorithms:           &["Rijndael", "Blowfish", "DES", "SHA-256", "SHA-512"],
+        layouts:              &[],
+        version_needs_marker: false,
+        superseded_by:        &[],
+    },
+    Library {
+        name:                 "libsodium",
+        version_prefixes:     &[],
+        markers:              &["libsodium", "sodium_init", "crypto_box_curve25519xsalsa20poly1305"],
+        algorithms:           &["SHA-256", "SHA-512"],
+        layouts:              &[],
+        version_needs_marker: false,
+        superseded_by:        &[],
+    },
+    Library {
+        name:                 "libgcrypt",
+        version_prefixes:     &["Libgcrypt "],
+        markers:              &["libgcrypt", "gcry_"],
+        algorithms:           &["Rijndael", "Blowfish", "DES", "SHA-256", "SHA-512", "MD5"],
+        layouts:              &[],
+        version_needs_marker: false,
+        superseded_by:        &[],
+    },
+    Library {
+        name:                 "Crypto++",
+        version_prefixes:     &[],
+        markers:              &["CryptoPP", "Crypto++"],
+        algorithms:           &["Rijndael", "Blowfish", "DES", "SHA-256", "SHA-512", "MD5"],
+        layouts:              &[],
+        version_needs_marker: false,
+        superseded_by:        &[],
+    },
+    Library {
+        name:                 "Botan",
+        version_prefixes:     &["Botan "],
+        markers:              &["N5Botan"],
+        algorithms:           &["Rijndael", "Blowfish", "DES", "SHA-256", "SHA-512"],
+        layouts:              &[],
+        version_needs_marker: false,
+        superseded_by:        &[],
+    },
+    Library {
+        name:                 "Go crypto",
+        version_prefixes:     &["go1."],
+        markers:              &["go.buildid", "runtime.goexit", "crypto/internal/boring"],
+        algorithms:           &["Rijndael", "DES", "SHA-256", "SHA-512"],
+        layouts:              &[
+            // te0 - te3, then sbox0 and sbox1.  The linker sorts symbols by
+            // size and then name, which can also put td0 - td3 first.
+            &["crypto::RIJNDAEL_TE0", "crypto::RIJNDAEL_TE1", "crypto::RIJNDAEL_TE2", "crypto::RIJNDAEL_TE3",
+              "crypto::RIJNDAEL_SBOX", "crypto::RIJNDAEL_INV_SBOX"],
+            &["crypto::RIJNDAEL_TD0", "crypto::RIJNDAEL_TD1", "crypto::RIJNDAEL_TD2", "crypto::RIJNDAEL_TD3",
+              "crypto::RIJNDAEL_TE0", "crypto::RIJNDAEL_TE1", "crypto::RIJNDAEL_TE2", "crypto::RIJNDAEL_TE3"],
+        ],
+        version_needs_marker: true,
+        superseded_by:        &[],
+    },
+];
+
+
+/// A version number, which sorts by its numeric components and then by the
+/// rest of it, so that "3.0.9" comes before "3.0.10" and "1.0.2" before
+/// "1.0.2k".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version(pub String);
+
+
+impl Version {
+    /// Splits the version into the numbers at the start of it and the suffix.
+    fn key(&self) -> (Vec<u64>, &str) {
+        let len = self.0.find(|c: char| !(c.is_digit(10) || c == '.')).unwrap_or(self.0.len());
+        let numbers = self.0[..len].split('.')
+            .filter(|n| !n.is_empty())
+            .map(|n| n.parse().unwrap_or(u64::max_value()))
+            .collect();
+        (numbers, &self.0[len..])
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        match self.key().cmp(&other.key()) {
+            Ordering::Equal => self.0.cmp(&other.0),
+            ord             => ord,
+        }
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+
+/// A library that was identified in some data.
+#[derive(Debug)]
+pub struct Identification {
+    pub library: &'static str,
+    pub versions: BTreeSet<Version>,
+    pub evidence: Vec<String>,
+}
+
+
+impl Identification {
+    /// Returns the version (or range of versions) that were found.
+    pub fn version_range(&self) -> String {
+        let first = self.versions.iter().next();
+        let last = self.versions.iter().next_back();
+
+        match (first, last) {
+            (Some(f), Some(l)) if f == l => f.to_string(),
+            (Some(f), Some(l))           => format!("{} - {}", f, l),
+            _                            => "unknown".to_string(),
+        }
+    }
+}
+
+
+enum Needle {
+    Version(usize, &'static str),
+    Marker(usize, &'static str),
+}
+
+
+/// Identifies the cryptographic libraries in `LIBRARIES`.  The strings that
+/// are searched for are only put together once, and then used for each file.
+pub struct Fingerprinter {
+    needles: Vec<Needle>,
+    automaton: AcAutomaton<Vec<u8>>,
+}
+
+
+impl Fingerprinter {
+    pub fn new() -> Fingerprinter {
+        let mut needles = vec![];
+        let mut strings = vec![];
+
+        for (i, lib) in LIBRARIES.iter().enumerate() {
+            for prefix in lib.version_prefixes {
+                needles.push(Needle::Version(i, *prefix));
+                strings.push(prefix.as_bytes().to_vec());
+            }
+            for marker in lib.markers {
+                needles.push(Needle::Marker(i, *marker));
+                strings.push(marker.as_bytes().to_vec());
+            }
+        }
+
+        Fingerprinter {
+            needles:   needles,
+            automaton: AcAutomaton::new(strings),
+        }
+    }
+
+    /// Attempts to identify the cryptographic libraries that are present in
+    /// the given data.  `tables` are the tables that were found in the same
+    /// data.
+    pub fn identify(&self, data: &[u8], tables: &[Match]) -> Vec<Identification> {
+        let mut found: Vec<Identification> = LIBRARIES.iter().map(|lib| Identification {
+            library:  lib.name,
+            versions: BTreeSet::new(),
+            evidence: vec![],
+        }).collect();
+        let mut has_marker = vec![false; LIBRARIES.len()];
+
+        for mm in self.automaton.stream_find(data) {
+            // Reading from a slice can't fail.
+            let mtch = mm.unwrap();
+
+            let (libi, evidence) = match self.needles[mtch.pati] {
+                Needle::Version(i, prefix) => {
+                    // Any digits in the prefix are part of the version.
+                    let len = prefix.find(|c: char| c.is_digit(10)).unwrap_or(prefix.len());
+                    match parse_version(&data[mtch.start + len..]) {
+                        Some(v) => {
+                            let ev = format!("version string \"{}{}\"", &prefix[..len], v);
+                            found[i].versions.insert(Version(v));
+                            (i, ev)
+                        },
+                        None => continue,
+                    }
+                },
+                Needle::Marker(i, marker) => {
+                    has_marker[i] = true;
+                    (i, format!("string \"{}\"", marker))
+                },
+            };
+
+            if !found[libi].evidence.contains(&evidence) {
+                found[libi].evidence.push(evidence);
+            }
+        }
+
+        let identified = LIBRARIES.iter()
+            .zip(has_marker.iter())
+            .zip(found.into_iter())
+            .filter(|&((lib, &marker), ref ident)| {
+                !ident.evidence.is_empty() && (marker || !lib.version_needs_marker)
+            })
+            .map(|((lib, _), ident)| (lib, ident))
+            .collect::<Vec<_>>();
+
+        let names = identified.iter()
+            .map(|&(lib, _)| lib.name)
+            .collect::<Vec<_>>();
+
+        identified.into_iter()
+            .filter(|&(lib, _)| !lib.superseded_by.iter().any(|s| names.contains(s)))
+            .map(|(lib, mut ident)| {
+                let algorithms = lib.algorithms.iter()
+                    .filter(|&a| tables.iter().any(|t| t.pattern.algorithm == *a))
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                if !algorithms.is_empty() {
+                    ident.evidence.push(format!("tables for {}", algorithms.join(", ")));
+                }
+                for layout in lib.layouts.iter().filter(|l| has_layout(tables, l)) {
+                    ident.evidence.push(format!("table layout {}", layout.join(", ")));
+                }
+
+                ident
+            })
+            .collect()
+    }
+}
+
+
+/// Returns whether the given tables were all found, each one starting right
+/// after the one before it.
+fn has_layout(tables: &[Match], layout: &[&str]) -> bool {
+    tables.iter()
+        .filter(|t| t.pattern.varname == layout[0])
+        .any(|first| {
+            let mut end = first.offset + first.len;
+            for name in &layout[1..] {
+                let next = tables.iter().find(|t| {
+                    t.pattern.varname == *name && t.offset >= end && t.offset - end <= LAYOUT_SLACK
+                });
+                match next {
+                    Some(t) => end = t.offset + t.len,
+                    None    => return false,
+                }
+            }
+            true
+        })
+}
+
+
+/// Parses a version number from the start of the given data.  A version number
+/// must start with a digit and contain at least one '.'.
+fn parse_version(data: &[u8]) -> Option<String> {
+    let len = data.iter()
+        .take(32)
+        .take_while(|&&b| match b {
+            b'0'...b'9' | b'a'...b'z' | b'A'...b'Z' | b'.' | b'-' => true,
+            _                                                    => false,
+        })
+        .count();
+    let version = &data[..len];
+
+    if version.is_empty() || !(version[0] as char).is_digit(10) || !version.contains(&b'.') {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(version).into_owned())
+}
+
+// ----------------------------------------------------------------------
+
+/// Returns matches for the given built-in tables, found at the given offsets.
+#[cfg(test)]
+fn found<'a>(patterns: &'a [::patterns::Pattern], tables: &[(&str, usize)]) -> Vec<Match<'a>> {
+    use endian::Endianness;
+
+    tables.iter().map(|&(varname, offset)| {
+        let pattern = patterns.iter().find(|p| p.varname == varname).unwrap();
+        Match {
+            pattern:    pattern,
+            endianness: Endianness::LittleEndian,
+            offset:     offset,
+            len:        pattern.bytes.as_byte_vec(Endianness::LittleEndian).len(),
+        }
+    }).collect()
+}
+
+#[test]
+fn test_identify_version() {
+    let patterns = ::patterns::get_patterns();
+    let tables = found(&patterns, &[("crypto::RIJNDAEL_TE0", 0x100), ("crc::CRC32", 0x600)]);
+
+    let data = b"\x00\x00OpenSSL 1.0.2k  26 Jan 2017\x00crypto/evp/evp_enc.c\x00";
+    let idents = Fingerprinter::new().identify(data, &tables);
+
+    assert_eq!(idents.len(), 1);
+    assert_eq!(idents[0].library, "OpenSSL");
+    assert_eq!(idents[0].version_range(), "1.0.2k");
+    assert_eq!(idents[0].evidence, vec![
+        "version string \"OpenSSL 1.0.2k\"".to_string(),
+        "string \"crypto/evp/\"".to_string(),
+        "tables for Rijndael".to_string(),
+    ]);
+}
+
+#[test]
+fn test_identify_superseded() {
+    let data = b"OpenSSL 1.1.1 (compatible; BoringSSL)\x00";
+    let idents = Fingerprinter::new().identify(data, &[]);
+
+    assert_eq!(idents.len(), 1);
+    assert_eq!(idents[0].library, "BoringSSL");
+}
+
+#[test]
+fn test_version_order() {
+    let data = b"OpenSSL 3.0.9\x00OpenSSL 3.0.10\x00OpenSSL 3.0.2\x00";
+    let idents = Fingerprinter::new().identify(data, &[]);
+    assert_eq!(idents[0].version_range(), "3.0.2 - 3.0.10");
+
+    let mut versions = vec!["1.0.2k", "1.0.10", "1.0.2", "1.1.0-pre1", "0.9.8zh"].into_iter()
+        .map(|v| Version(v.to_string()))
+        .collect::<Vec<_>>();
+    versions.sort();
+    assert_eq!(versions.iter().map(|v| &v.0[..]).collect::<Vec<_>>(),
+               vec!["0.9.8zh", "1.0.2", "1.0.2k", "1.0.10", "1.1.0-pre1"]);
+}
+
+#[test]
+fn test_identify_layout() {
+    let patterns = ::patterns::get_patterns();
+    let names = ["crypto::RIJNDAEL_TE0", "crypto::RIJNDAEL_TE1", "crypto::RIJNDAEL_TE2",
+                 "crypto::RIJNDAEL_TE3", "crypto::RIJNDAEL_TD0"];
+    let data = b"OpenSSL 1.0.2k  26 Jan 2017\x00";
+
+    let tables = found(&patterns, &names.iter().enumerate().map(|(i, &n)| (n, 0x1000 + i * 0x400)).collect::<Vec<_>>());
+    let idents = Fingerprinter::new().identify(data, &tables);
+    assert_eq!(idents[0].evidence.last().unwrap(), &format!("table layout {}", names.join(", ")));
+
+    // Td0 is too far away from Te3 for them to have been stored together.
+    let tables = found(&patterns, &names.iter().enumerate().map(|(i, &n)| (n, 0x1000 + i * 0x500)).collect::<Vec<_>>());
+    let idents = Fingerprinter::new().identify(data, &tables);
+    assert_eq!(idents[0].evidence.last().unwrap(), "tables for Rijndael");
+}
+
+#[test]
+fn test_identify_go() {
+    let idents = Fingerprinter::new().identify(b"go1.21.3\x00runtime.goexit\x00", &[]);
+    assert_eq!(idents.len(), 1);
+    assert_eq!(idents[0].library, "Go crypto");
+    assert_eq!(idents[0].version_range(), "1.21.3");
+    assert_eq!(idents[0].evidence[0], "version string \"go1.21.3\"");
+
+    // OpenSSL's source paths look like Go's package names.
+    let data = b"OpenSSL 3.0.2\x00crypto/aes/aes_core.c\x00crypto/sha256\x00go1.2.3\x00";
+    let idents = Fingerprinter::new().identify(data, &[]);
+    assert_eq!(idents.iter().map(|i| i.library).collect::<Vec<_>>(), vec!["OpenSSL"]);
+}
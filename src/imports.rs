@@ -0,0 +1,259 @@
+use binfmt::{self, Binary};
+
+
+/// Maps imported symbols starting with `prefix` to an algorithm.  `library` is
+/// the library that provides the symbol: prefixes like "AES_" or "SSL_" are
+/// only meaningful for symbols from that library, so other imports are never
+/// matched.  ELF doesn't record which library a symbol comes from, so the
+/// binary has to depend on it instead.
+struct Mapping {
+    prefix: &'static str,
+    library: &'static str,
+    algorithm: &'static str,
+}
+
+
+macro_rules! mapping {
+    ($prefix:expr, $lib:expr, $alg:expr) => {
+        Mapping { prefix: $prefix, library: $lib, algorithm: $alg }
+    };
+}
+
+
+// When more than one prefix matches a symbol, the longest one wins.
+static MAPPINGS: &'static [Mapping] = &[
+    // OpenSSL / LibreSSL / BoringSSL
+    mapping!("EVP_aes_",                    "libcrypto", "Rijndael"),
+    mapping!("AES_",                        "libcrypto", "Rijndael"),
+    mapping!("EVP_des_",                    "libcrypto", "DES"),
+    mapping!("DES_",                        "libcrypto", "DES"),
+    mapping!("EVP_bf_",                     "libcrypto", "Blowfish"),
+    mapping!("BF_",                         "libcrypto", "Blowfish"),
+    mapping!("EVP_rc4",                     "libcrypto", "RC4"),
+    mapping!("RC4",                         "libcrypto", "RC4"),
+    mapping!("EVP_chacha20",                "libcrypto", "ChaCha20"),
+    mapping!("EVP_md5",                     "libcrypto", "MD5"),
+    mapping!("MD5",                         "libcrypto", "MD5"),
+    mapping!("EVP_sha1",                    "libcrypto", "SHA-1"),
+    mapping!("SHA1",                        "libcrypto", "SHA-1"),
+    mapping!("EVP_sha224",                  "libcrypto", "SHA-256"),
+    mapping!("EVP_sha256",                  "libcrypto", "SHA-256"),
+    mapping!("SHA224",                      "libcrypto", "SHA-256"),
+    mapping!("SHA256",                      "libcrypto", "SHA-256"),
+    mapping!("EVP_sha384",                  "libcrypto", "SHA-512"),
+    mapping!("EVP_sha512",                  "libcrypto", "SHA-512"),
+    mapping!("SHA384",                      "libcrypto", "SHA-512"),
+    mapping!("SHA512",                      "libcrypto", "SHA-512"),
+    mapping!("EVP_sha3_",                   "libcrypto", "Keccak"),
+    mapping!("EVP_shake",                   "libcrypto", "Keccak"),
+    mapping!("RSA_",                        "libcrypto", "RSA"),
+    mapping!("DSA_",                        "libcrypto", "DSA"),
+    mapping!("DH_",                         "libcrypto", "DH"),
+    mapping!("ECDSA_",                      "libcrypto", "ECDSA"),
+    mapping!("ECDH_",                       "libcrypto", "ECDH"),
+    mapping!("EC_KEY_",                     "libcrypto", "ECC"),
+    mapping!("HMAC",                        "libcrypto", "HMAC"),
+    mapping!("EVP_Encrypt",                 "libcrypto", "Cipher (generic)"),
+    mapping!("EVP_Decrypt",                 "libcrypto", "Cipher (generic)"),
+    mapping!("EVP_Cipher",                  "libcrypto", "Cipher (generic)"),
+    mapping!("EVP_Digest",                  "libcrypto", "Hash (generic)"),
+    mapping!("SSL_",                        "libssl",    "TLS"),
+    mapping!("TLS_",                        "libssl",    "TLS"),
+
+    // Windows CryptoAPI, CNG and DPAPI
+    mapping!("CryptAcquireContext",         "advapi32.dll", "CryptoAPI"),
+    mapping!("CryptEncrypt",                "advapi32.dll", "CryptoAPI (cipher)"),
+    mapping!("CryptDecrypt",                "advapi32.dll", "CryptoAPI (cipher)"),
+    mapping!("CryptDeriveKey",              "advapi32.dll", "CryptoAPI (cipher)"),
+    mapping!("CryptGenKey",                 "advapi32.dll", "CryptoAPI (cipher)"),
+    mapping!("CryptImportKey",              "advapi32.dll", "CryptoAPI (cipher)"),
+    mapping!("CryptCreateHash",             "advapi32.dll", "CryptoAPI (hash)"),
+    mapping!("CryptHashData",               "advapi32.dll", "CryptoAPI (hash)"),
+    mapping!("CryptSignHash",               "advapi32.dll", "CryptoAPI (signature)"),
+    mapping!("CryptVerifySignature",        "advapi32.dll", "CryptoAPI (signature)"),
+    mapping!("CryptGenRandom",              "advapi32.dll", "CryptoAPI (RNG)"),
+    mapping!("CryptProtectData",            "crypt32.dll",  "DPAPI"),
+    mapping!("CryptUnprotectData",          "crypt32.dll",  "DPAPI"),
+    mapping!("BCryptOpenAlgorithmProvider", "bcrypt.dll",   "CNG"),
+    mapping!("BCryptEncrypt",               "bcrypt.dll",   "CNG (cipher)"),
+    mapping!("BCryptDecrypt",               "bcrypt.dll",   "CNG (cipher)"),
+    mapping!("BCryptCreateHash",            "bcrypt.dll",   "CNG (hash)"),
+    mapping!("BCryptHashData",              "bcrypt.dll",   "CNG (hash)"),
+    mapping!("BCryptSignHash",              "bcrypt.dll",   "CNG (signature)"),
+    mapping!("BCryptVerifySignature",       "bcrypt.dll",   "CNG (signature)"),
+    mapping!("BCryptGenRandom",             "bcrypt.dll",   "CNG (RNG)"),
+    mapping!("NCrypt",                      "ncrypt.dll",   "CNG (key storage)"),
+
+    // mbedTLS
+    mapping!("mbedtls_aes_",                "libmbedcrypto", "Rijndael"),
+    mapping!("mbedtls_des",                 "libmbedcrypto", "DES"),
+    mapping!("mbedtls_blowfish_",           "libmbedcrypto", "Blowfish"),
+    mapping!("mbedtls_arc4_",               "libmbedcrypto", "RC4"),
+    mapping!("mbedtls_chacha20_",           "libmbedcrypto", "ChaCha20"),
+    mapping!("mbedtls_gcm_",                "libmbedcrypto", "GCM"),
+    mapping!("mbedtls_md5",                 "libmbedcrypto", "MD5"),
+    mapping!("mbedtls_sha1",                "libmbedcrypto", "SHA-1"),
+    mapping!("mbedtls_sha256",              "libmbedcrypto", "SHA-256"),
+    mapping!("mbedtls_sha512",              "libmbedcrypto", "SHA-512"),
+    mapping!("mbedtls_rsa_",                "libmbedcrypto", "RSA"),
+    mapping!("mbedtls_ecdsa_",              "libmbedcrypto", "ECDSA"),
+    mapping!("mbedtls_ecdh_",               "libmbedcrypto", "ECDH"),
+    mapping!("mbedtls_dhm_",                "libmbedcrypto", "DH"),
+    mapping!("mbedtls_ssl_",                "libmbedtls",    "TLS"),
+
+    // libsodium / NaCl
+    mapping!("crypto_box_",                 "libsodium", "X25519-XSalsa20-Poly1305"),
+    mapping!("crypto_secretbox_",           "libsodium", "XSalsa20-Poly1305"),
+    mapping!("crypto_sign_",                "libsodium", "Ed25519"),
+    mapping!("crypto_scalarmult",           "libsodium", "X25519"),
+    mapping!("crypto_kx_",                  "libsodium", "X25519"),
+    mapping!("crypto_aead_aes256gcm_",      "libsodium", "AES-GCM"),
+    mapping!("crypto_aead_chacha20poly1305_", "libsodium", "ChaCha20-Poly1305"),
+    mapping!("crypto_aead_xchacha20poly1305_", "libsodium", "XChaCha20-Poly1305"),
+    mapping!("crypto_generichash",          "libsodium", "BLAKE2b"),
+    mapping!("crypto_hash_sha256",          "libsodium", "SHA-256"),
+    mapping!("crypto_hash_sha512",          "libsodium", "SHA-512"),
+    mapping!("crypto_pwhash",               "libsodium", "Argon2"),
+    mapping!("crypto_stream_",              "libsodium", "Salsa20/ChaCha20"),
+
+    // libgcrypt
+    mapping!("gcry_cipher_",                "libgcrypt", "Cipher (generic)"),
+    mapping!("gcry_md_",                    "libgcrypt", "Hash (generic)"),
+    mapping!("gcry_pk_",                    "libgcrypt", "Public key (generic)"),
+
+    // wolfSSL
+    mapping!("wc_Aes",                      "libwolfssl", "Rijndael"),
+    mapping!("wc_Des",                      "libwolfssl", "DES"),
+    mapping!("wc_Sha256",                   "libwolfssl", "SHA-256"),
+    mapping!("wc_Sha512",                   "libwolfssl", "SHA-512"),
+    mapping!("wc_Md5",                      "libwolfssl", "MD5"),
+    mapping!("wc_RsaPublicEncrypt",         "libwolfssl", "RSA"),
+    mapping!("wolfSSL_",                    "libwolfssl", "TLS"),
+];
+
+
+/// Other names that libraries are shipped under, mostly on Windows.
+static ALIASES: &'static [(&'static str, &'static str)] = &[
+    ("libcrypto",     "libeay32"),
+    ("libssl",        "ssleay32"),
+    ("libmbedcrypto", "mbedcrypto"),
+    ("libmbedtls",    "mbedtls"),
+    ("libwolfssl",    "wolfssl"),
+];
+
+
+/// Returns whether `library` is the library that a mapping wants, possibly
+/// with a version or extension - e.g. "libcrypto.so.1.1" or
+/// "libcrypto-3-x64.dll" for "libcrypto".  Case is ignored, as it is on
+/// Windows.
+fn library_matches(wanted: &str, library: &str) -> bool {
+    let library = library.to_lowercase();
+    let aliases = ALIASES.iter().filter(|a| a.0 == wanted).map(|a| a.1);
+
+    Some(wanted).into_iter().chain(aliases).any(|name| {
+        library == name || (library.starts_with(name) && match library.as_bytes()[name.len()] {
+            b'.' | b'-' | b'_' => true,
+            _                  => false,
+        })
+    })
+}
+
+
+/// An imported symbol that maps to a cryptographic algorithm.
+#[derive(Debug)]
+pub struct CryptoImport {
+    pub library: String,
+    pub symbol: String,
+    pub algorithm: &'static str,
+}
+
+
+/// Finds imported symbols that correspond to cryptographic APIs in the given
+/// data, if it's an ELF or PE file.
+pub fn find_imports(data: &[u8]) -> Vec<CryptoImport> {
+    match binfmt::parse(data) {
+        Some(bin) => crypto_imports(&bin),
+        None      => vec![],
+    }
+}
+
+
+pub fn crypto_imports(bin: &Binary) -> Vec<CryptoImport> {
+    let mut ret = vec![];
+
+    for import in bin.imports.iter() {
+        // For ELF files, any library that's needed could provide the symbol.
+        let library = |m: &Mapping| match import.library {
+            Some(ref l) => if library_matches(m.library, l) { Some(l) } else { None },
+            None        => bin.needed.iter().find(|n| library_matches(m.library, n)),
+        };
+
+        let found = MAPPINGS.iter()
+            .filter(|m| import.name.starts_with(m.prefix))
+            .filter_map(|m| library(m).map(|l| (m, l)))
+            .max_by_key(|&(m, _)| m.prefix.len());
+
+        let (mapping, library) = match found {
+            Some(f) => f,
+            None    => continue,
+        };
+
+        ret.push(CryptoImport {
+            library:   library.clone(),
+            symbol:    import.name.clone(),
+            algorithm: mapping.algorithm,
+        });
+    }
+
+    ret
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_crypto_imports() {
+    use binfmt::{Format, Import, Machine};
+
+    let binary = |needed: &[&str], imports: &[(Option<&str>, &str)]| Binary {
+        format:   Format::Elf,
+        machine:  Machine::X86_64,
+        sections: vec![],
+        segments: vec![],
+        needed:   needed.iter().map(|n| n.to_string()).collect(),
+        imports:  imports.iter().map(|&(l, n)| Import {
+            library: l.map(|l| l.to_string()),
+            name:    n.to_string(),
+        }).collect(),
+        core:         false,
+        mapped_files: vec![],
+    };
+    let found = |bin: &Binary| {
+        crypto_imports(bin).into_iter()
+            .map(|i| format!("{}!{} ({})", i.library, i.symbol, i.algorithm))
+            .collect::<Vec<_>>()
+    };
+
+    // ELF imports are attributed to a library that the binary needs.
+    let elf = binary(&["libc.so.6", "libcrypto.so.3"],
+                     &[(None, "EVP_aes_256_gcm"), (None, "DH_check"), (None, "SSL_read"), (None, "memcpy")]);
+    assert_eq!(found(&elf), vec![
+        "libcrypto.so.3!EVP_aes_256_gcm (Rijndael)",
+        "libcrypto.so.3!DH_check (DH)",
+    ]);
+
+    // Lookalike symbols from other libraries aren't crypto.
+    let pe = binary(&["game.dll", "LIBCRYPTO-3-X64.DLL", "ncrypt.dll"], &[
+        (Some("game.dll"),            "AES_Brightness"),
+        (Some("game.dll"),            "NCryptoLevel"),
+        (Some("LIBCRYPTO-3-X64.DLL"), "MD5_Init"),
+        (Some("ncrypt.dll"),          "NCryptOpenKey"),
+    ]);
+    assert_eq!(found(&pe), vec![
+        "LIBCRYPTO-3-X64.DLL!MD5_Init (MD5)",
+        "ncrypt.dll!NCryptOpenKey (CNG (key storage))",
+    ]);
+
+    assert!(library_matches("libcrypto", "libeay32.dll"));
+    assert!(!library_matches("libcrypto", "libcryptopp.so"));
+    assert!(!library_matches("libssl", "libssl3.so"));
+}
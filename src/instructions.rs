@@ -0,0 +1,503 @@
+use binfmt::{Binary, Machine};
+
+
+/// The instruction sets that we know how to find crypto instructions in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Isa {
+    X86,
+    Arm,
+    Thumb,
+    Arm64,
+    RiscV,
+}
+
+
+impl Isa {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Isa::X86   => "x86",
+            Isa::Arm   => "arm",
+            Isa::Thumb => "thumb",
+            Isa::Arm64 => "arm64",
+            Isa::RiscV => "riscv",
+        }
+    }
+}
+
+
+/// A region of code that contains instructions for a particular algorithm -
+/// usually a single function.
+#[derive(Debug)]
+pub struct Region {
+    /// The offsets of the first and last instructions in the data.
+    pub start: usize,
+    pub end: usize,
+
+    /// The virtual address of the first instruction, if known.
+    pub address: Option<u64>,
+
+    pub isa: Isa,
+    pub algorithm: &'static str,
+    pub count: usize,
+
+    /// The distinct mnemonics of the instructions that were found.
+    pub mnemonics: Vec<&'static str>,
+}
+
+
+struct Hit {
+    offset: usize,
+    isa: Isa,
+    mnemonic: &'static str,
+    algorithm: &'static str,
+}
+
+
+// Instructions that are further apart than this are considered to belong to
+// different regions.
+const MAX_GAP: usize = 512;
+
+// A handful of matching instructions are just as likely to be random data, so
+// we only report regions with at least this many.  Real implementations use
+// far more - e.g. an unrolled AES block function has 10+ rounds.
+const MIN_COUNT: usize = 4;
+
+
+/// Searches the given data for cryptographic instructions, and returns the
+/// regions of code that contain them.  If the data is an ELF or PE file (given
+/// as `binary`), only executable sections are searched, and only for the
+/// file's instruction set.
+pub fn find_regions(data: &[u8], binary: Option<&Binary>) -> Vec<Region> {
+    let all_isas = [Isa::X86, Isa::Arm, Isa::Thumb, Isa::Arm64, Isa::RiscV];
+
+    // Ranges of the data to search, as (offset, size, address).
+    let mut ranges = vec![];
+    let isas = match binary {
+        Some(bin) => {
+            // Prefer sections, since they're more precise, but fall back to
+            // segments for binaries without section headers.
+            if bin.sections.iter().any(|s| s.executable) {
+                for s in bin.sections.iter().filter(|s| s.executable) {
+                    ranges.push((s.offset, s.size, Some(s.address)));
+                }
+            } else {
+                for s in bin.segments.iter().filter(|s| s.executable) {
+                    ranges.push((s.offset, s.size, Some(s.address)));
+                }
+            }
+
+            match bin.machine {
+                Machine::X86 | Machine::X86_64       => vec![Isa::X86],
+                Machine::Arm                         => vec![Isa::Arm, Isa::Thumb],
+                Machine::Aarch64                     => vec![Isa::Arm64],
+                Machine::RiscV32 | Machine::RiscV64  => vec![Isa::RiscV],
+                Machine::Unknown                     => all_isas.to_vec(),
+            }
+        },
+        None => {
+            ranges.push((0, data.len() as u64, None));
+            all_isas.to_vec()
+        },
+    };
+
+    let mut regions = vec![];
+
+    for &(offset, size, address) in ranges.iter() {
+        let end = match offset.checked_add(size) {
+            Some(e) if e <= data.len() as u64 => e as usize,
+            _                                 => continue,
+        };
+        let start = offset as usize;
+        if start >= end {
+            continue;
+        }
+
+        for &isa in isas.iter() {
+            let mut hits = vec![];
+            match isa {
+                Isa::X86   => scan_x86(&data[start..end], start, &mut hits),
+                Isa::Arm   => scan_fixed(&data[start..end], start, 4, ARM_INSNS, Isa::Arm, &mut hits),
+                Isa::Thumb => scan_fixed(&data[start..end], start, 2, THUMB_INSNS, Isa::Thumb, &mut hits),
+                Isa::Arm64 => scan_fixed(&data[start..end], start, 4, ARM64_INSNS, Isa::Arm64, &mut hits),
+                Isa::RiscV => scan_fixed(&data[start..end], start, 2, RISCV_INSNS, Isa::RiscV, &mut hits),
+            }
+
+            let base_address = address.map(|a| a.wrapping_sub(offset));
+            cluster(hits, base_address, &mut regions);
+        }
+    }
+
+    regions.sort_by(|a, b| a.start.cmp(&b.start));
+    regions
+}
+
+
+/// Groups hits for the same algorithm that are close to each other into
+/// regions.
+fn cluster(mut hits: Vec<Hit>, base_address: Option<u64>, out: &mut Vec<Region>) {
+    hits.sort_by(|a, b| (a.algorithm, a.offset).cmp(&(b.algorithm, b.offset)));
+
+    let mut current: Option<Region> = None;
+
+    for hit in hits {
+        let extends = match current {
+            Some(ref r) => r.algorithm == hit.algorithm && hit.offset - r.end <= MAX_GAP,
+            None        => false,
+        };
+
+        if extends {
+            let r = current.as_mut().unwrap();
+            r.end = hit.offset;
+            r.count += 1;
+            if !r.mnemonics.contains(&hit.mnemonic) {
+                r.mnemonics.push(hit.mnemonic);
+            }
+            continue;
+        }
+
+        if let Some(r) = current.take() {
+            if r.count >= MIN_COUNT {
+                out.push(r);
+            }
+        }
+
+        current = Some(Region {
+            start:     hit.offset,
+            end:       hit.offset,
+            address:   base_address.map(|b| b.wrapping_add(hit.offset as u64)),
+            isa:       hit.isa,
+            algorithm: hit.algorithm,
+            count:     1,
+            mnemonics: vec![hit.mnemonic],
+        });
+    }
+
+    if let Some(r) = current {
+        if r.count >= MIN_COUNT {
+            out.push(r);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------
+
+/// An x86 instruction that we search for - the opcode map (0x38 or 0x3A, i.e.
+/// 0F 38 or 0F 3A), the opcode, and the mandatory prefix (if any).
+struct X86Insn {
+    map: u8,
+    opcode: u8,
+    prefix: Option<u8>,
+    mnemonic: &'static str,
+    algorithm: &'static str,
+}
+
+
+macro_rules! x86 {
+    ($map:expr, $op:expr, $prefix:expr, $mn:expr, $alg:expr) => {
+        X86Insn { map: $map, opcode: $op, prefix: $prefix, mnemonic: $mn, algorithm: $alg }
+    };
+}
+
+
+static X86_INSNS: &'static [X86Insn] = &[
+    // AES-NI
+    x86!(0x38, 0xDB, Some(0x66), "aesimc",          "Rijndael"),
+    x86!(0x38, 0xDC, Some(0x66), "aesenc",          "Rijndael"),
+    x86!(0x38, 0xDD, Some(0x66), "aesenclast",      "Rijndael"),
+    x86!(0x38, 0xDE, Some(0x66), "aesdec",          "Rijndael"),
+    x86!(0x38, 0xDF, Some(0x66), "aesdeclast",      "Rijndael"),
+    x86!(0x3A, 0xDF, Some(0x66), "aeskeygenassist", "Rijndael"),
+
+    // SHA extensions
+    x86!(0x38, 0xC8, None,       "sha1nexte",       "SHA-1"),
+    x86!(0x38, 0xC9, None,       "sha1msg1",        "SHA-1"),
+    x86!(0x38, 0xCA, None,       "sha1msg2",        "SHA-1"),
+    x86!(0x3A, 0xCC, None,       "sha1rnds4",       "SHA-1"),
+    x86!(0x38, 0xCB, None,       "sha256rnds2",     "SHA-256"),
+    x86!(0x38, 0xCC, None,       "sha256msg1",      "SHA-256"),
+    x86!(0x38, 0xCD, None,       "sha256msg2",      "SHA-256"),
+
+    // Carry-less multiplication (GCM, CRC folding)
+    x86!(0x3A, 0x44, Some(0x66), "pclmulqdq",       "CLMUL"),
+
+    // SSE4.2 CRC32 - which uses the CRC-32C polynomial
+    x86!(0x38, 0xF0, Some(0xF2), "crc32",           "CRC-32C"),
+    x86!(0x38, 0xF1, Some(0xF2), "crc32",           "CRC-32C"),
+];
+
+
+fn find_x86(map: u8, opcode: u8, prefix: Option<u8>) -> Option<&'static X86Insn> {
+    X86_INSNS.iter().find(|i| i.map == map && i.opcode == opcode && i.prefix == prefix)
+}
+
+
+fn scan_x86(data: &[u8], base: usize, hits: &mut Vec<Hit>) {
+    if data.len() < 4 {
+        return;
+    }
+
+    for i in 0..data.len() - 3 {
+        // Legacy encoding: [prefix] [REX] 0F <map> <opcode>
+        if data[i] == 0x0F && (data[i + 1] == 0x38 || data[i + 1] == 0x3A) {
+            let mut start = i;
+            if start > 0 && data[start - 1] & 0xF0 == 0x40 {
+                start -= 1;
+            }
+
+            let prefix = if start > 0 {
+                match data[start - 1] {
+                    p @ 0x66 | p @ 0xF2 | p @ 0xF3 => Some(p),
+                    _                              => None,
+                }
+            } else {
+                None
+            };
+            if prefix.is_some() {
+                start -= 1;
+            }
+
+            if let Some(insn) = find_x86(data[i + 1], data[i + 2], prefix) {
+                hits.push(Hit {
+                    offset:    base + start,
+                    isa:       Isa::X86,
+                    mnemonic:  insn.mnemonic,
+                    algorithm: insn.algorithm,
+                });
+            }
+        }
+
+        // Three-byte VEX encoding: C4 <RXB.mmmmm> <W.vvvv.L.pp> <opcode>
+        if data[i] == 0xC4 {
+            let map = match data[i + 1] & 0x1F {
+                2 => 0x38,
+                3 => 0x3A,
+                _ => continue,
+            };
+            let prefix = match data[i + 2] & 0x03 {
+                1 => Some(0x66),
+                2 => Some(0xF3),
+                3 => Some(0xF2),
+                _ => None,
+            };
+
+            if let Some(insn) = find_x86(map, data[i + 3], prefix) {
+                hits.push(Hit {
+                    offset:    base + i,
+                    isa:       Isa::X86,
+                    mnemonic:  insn.mnemonic,
+                    algorithm: insn.algorithm,
+                });
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------
+
+/// An instruction from a fixed-width, little-endian instruction set, which
+/// matches if `word & mask == value`.
+struct FixedInsn {
+    mask: u32,
+    value: u32,
+    mnemonic: &'static str,
+    algorithm: &'static str,
+}
+
+
+macro_rules! fixed {
+    ($mask:expr, $value:expr, $mn:expr, $alg:expr) => {
+        FixedInsn { mask: $mask, value: $value, mnemonic: $mn, algorithm: $alg }
+    };
+}
+
+
+// The AArch32 forms of the ARMv8 Cryptographic Extension, plus CRC32, in the
+// A32 (ARM) instruction set.  CRC32 is only matched when it's unconditional.
+static ARM_INSNS: &'static [FixedInsn] = &[
+    fixed!(0xFFBF0FD0, 0xF3B00300, "aese",      "Rijndael"),
+    fixed!(0xFFBF0FD0, 0xF3B00340, "aesd",      "Rijndael"),
+    fixed!(0xFFBF0FD0, 0xF3B00380, "aesmc",     "Rijndael"),
+    fixed!(0xFFBF0FD0, 0xF3B003C0, "aesimc",    "Rijndael"),
+    fixed!(0xFFB00F50, 0xF2000C40, "sha1c",     "SHA-1"),
+    fixed!(0xFFB00F50, 0xF2100C40, "sha1p",     "SHA-1"),
+    fixed!(0xFFB00F50, 0xF2200C40, "sha1m",     "SHA-1"),
+    fixed!(0xFFB00F50, 0xF2300C40, "sha1su0",   "SHA-1"),
+    fixed!(0xFFBF0FD0, 0xF3B902C0, "sha1h",     "SHA-1"),
+    fixed!(0xFFBF0FD0, 0xF3BA0380, "sha1su1",   "SHA-1"),
+    fixed!(0xFFB00F50, 0xF3000C40, "sha256h",   "SHA-256"),
+    fixed!(0xFFB00F50, 0xF3100C40, "sha256h2",  "SHA-256"),
+    fixed!(0xFFB00F50, 0xF3200C40, "sha256su1", "SHA-256"),
+    fixed!(0xFFBF0FD0, 0xF3BA03C0, "sha256su0", "SHA-256"),
+    fixed!(0xFFB00F50, 0xF2A00E00, "vmull.p64", "CLMUL"),
+    fixed!(0xFF900FF0, 0xE1000040, "crc32",     "CRC32"),
+    fixed!(0xFF900FF0, 0xE1000240, "crc32c",    "CRC-32C"),
+];
+
+
+// The same instructions in the T32 (Thumb-2) instruction set.  The vector
+// instructions are the A32 ones with a different first byte.
+static THUMB_INSNS: &'static [FixedInsn] = &[
+    fixed!(0xFFBF0FD0, 0xFFB00300, "aese",      "Rijndael"),
+    fixed!(0xFFBF0FD0, 0xFFB00340, "aesd",      "Rijndael"),
+    fixed!(0xFFBF0FD0, 0xFFB00380, "aesmc",     "Rijndael"),
+    fixed!(0xFFBF0FD0, 0xFFB003C0, "aesimc",    "Rijndael"),
+    fixed!(0xFFB00F50, 0xEF000C40, "sha1c",     "SHA-1"),
+    fixed!(0xFFB00F50, 0xEF100C40, "sha1p",     "SHA-1"),
+    fixed!(0xFFB00F50, 0xEF200C40, "sha1m",     "SHA-1"),
+    fixed!(0xFFB00F50, 0xEF300C40, "sha1su0",   "SHA-1"),
+    fixed!(0xFFBF0FD0, 0xFFB902C0, "sha1h",     "SHA-1"),
+    fixed!(0xFFBF0FD0, 0xFFBA0380, "sha1su1",   "SHA-1"),
+    fixed!(0xFFB00F50, 0xFF000C40, "sha256h",   "SHA-256"),
+    fixed!(0xFFB00F50, 0xFF100C40, "sha256h2",  "SHA-256"),
+    fixed!(0xFFB00F50, 0xFF200C40, "sha256su1", "SHA-256"),
+    fixed!(0xFFBF0FD0, 0xFFBA03C0, "sha256su0", "SHA-256"),
+    fixed!(0xFFB00F50, 0xEFA00E00, "vmull.p64", "CLMUL"),
+    fixed!(0xFFF0F0C0, 0xFAC0F080, "crc32",     "CRC32"),
+    fixed!(0xFFF0F0C0, 0xFAD0F080, "crc32c",    "CRC-32C"),
+];
+
+
+// ARMv8 Cryptographic Extension, plus CRC32.
+static ARM64_INSNS: &'static [FixedInsn] = &[
+    fixed!(0xFFFFFC00, 0x4E284800, "aese",      "Rijndael"),
+    fixed!(0xFFFFFC00, 0x4E285800, "aesd",      "Rijndael"),
+    fixed!(0xFFFFFC00, 0x4E286800, "aesmc",     "Rijndael"),
+    fixed!(0xFFFFFC00, 0x4E287800, "aesimc",    "Rijndael"),
+    fixed!(0xFFE0FC00, 0x5E000000, "sha1c",     "SHA-1"),
+    fixed!(0xFFE0FC00, 0x5E001000, "sha1p",     "SHA-1"),
+    fixed!(0xFFE0FC00, 0x5E002000, "sha1m",     "SHA-1"),
+    fixed!(0xFFE0FC00, 0x5E003000, "sha1su0",   "SHA-1"),
+    fixed!(0xFFFFFC00, 0x5E280800, "sha1h",     "SHA-1"),
+    fixed!(0xFFFFFC00, 0x5E281800, "sha1su1",   "SHA-1"),
+    fixed!(0xFFE0FC00, 0x5E004000, "sha256h",   "SHA-256"),
+    fixed!(0xFFE0FC00, 0x5E005000, "sha256h2",  "SHA-256"),
+    fixed!(0xFFE0FC00, 0x5E006000, "sha256su1", "SHA-256"),
+    fixed!(0xFFFFFC00, 0x5E282800, "sha256su0", "SHA-256"),
+    fixed!(0xFFE0FC00, 0xCE608000, "sha512h",   "SHA-512"),
+    fixed!(0xFFE0FC00, 0xCE608400, "sha512h2",  "SHA-512"),
+    fixed!(0xFFE0FC00, 0xCE608800, "sha512su1", "SHA-512"),
+    fixed!(0xFFFFFC00, 0xCEC08000, "sha512su0", "SHA-512"),
+    fixed!(0xBFE0FC00, 0x0EE0E000, "pmull",     "CLMUL"),
+    fixed!(0x7FE0F000, 0x1AC04000, "crc32",     "CRC32"),
+    fixed!(0x7FE0F000, 0x1AC05000, "crc32c",    "CRC-32C"),
+];
+
+
+// RISC-V scalar cryptography extensions (Zkne, Zknd, Zknh, Zbkc).
+static RISCV_INSNS: &'static [FixedInsn] = &[
+    fixed!(0x3E00707F, 0x22000033, "aes32esi",   "Rijndael"),
+    fixed!(0x3E00707F, 0x26000033, "aes32esmi",  "Rijndael"),
+    fixed!(0x3E00707F, 0x2A000033, "aes32dsi",   "Rijndael"),
+    fixed!(0x3E00707F, 0x2E000033, "aes32dsmi",  "Rijndael"),
+    fixed!(0xFE00707F, 0x32000033, "aes64es",    "Rijndael"),
+    fixed!(0xFE00707F, 0x36000033, "aes64esm",   "Rijndael"),
+    fixed!(0xFE00707F, 0x3A000033, "aes64ds",    "Rijndael"),
+    fixed!(0xFE00707F, 0x3E000033, "aes64dsm",   "Rijndael"),
+    fixed!(0xFFF0707F, 0x30001013, "aes64im",    "Rijndael"),
+    fixed!(0xFF00707F, 0x31001013, "aes64ks1i",  "Rijndael"),
+    fixed!(0xFE00707F, 0x7E000033, "aes64ks2",   "Rijndael"),
+    fixed!(0xFFF0707F, 0x10001013, "sha256sum0", "SHA-256"),
+    fixed!(0xFFF0707F, 0x10101013, "sha256sum1", "SHA-256"),
+    fixed!(0xFFF0707F, 0x10201013, "sha256sig0", "SHA-256"),
+    fixed!(0xFFF0707F, 0x10301013, "sha256sig1", "SHA-256"),
+    fixed!(0xFFF0707F, 0x10401013, "sha512sum0", "SHA-512"),
+    fixed!(0xFFF0707F, 0x10501013, "sha512sum1", "SHA-512"),
+    fixed!(0xFFF0707F, 0x10601013, "sha512sig0", "SHA-512"),
+    fixed!(0xFFF0707F, 0x10701013, "sha512sig1", "SHA-512"),
+    fixed!(0xFE00707F, 0x0A001033, "clmul",      "CLMUL"),
+    fixed!(0xFE00707F, 0x0A003033, "clmulh",     "CLMUL"),
+];
+
+
+fn scan_fixed(data: &[u8], base: usize, align: usize, insns: &'static [FixedInsn], isa: Isa, hits: &mut Vec<Hit>) {
+    let mut i = 0;
+    while i + 4 <= data.len() {
+        let word = (data[i] as u32)
+            | (data[i + 1] as u32) << 8
+            | (data[i + 2] as u32) << 16
+            | (data[i + 3] as u32) << 24;
+
+        // 32-bit Thumb instructions are two halfwords, the first of which has
+        // the opcode.
+        let word = if isa == Isa::Thumb { word.rotate_left(16) } else { word };
+
+        if let Some(insn) = insns.iter().find(|insn| word & insn.mask == insn.value) {
+            hits.push(Hit {
+                offset:    base + i,
+                isa:       isa,
+                mnemonic:  insn.mnemonic,
+                algorithm: insn.algorithm,
+            });
+        }
+
+        i += align;
+    }
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_find_x86_regions() {
+    let data = [
+        0x90, 0x90,
+        0x66, 0x0F, 0x38, 0xDC, 0xC1,           // aesenc xmm0, xmm1
+        0x66, 0x41, 0x0F, 0x38, 0xDC, 0xC1,     // aesenc xmm0, xmm9
+        0x66, 0x0F, 0x38, 0xDC, 0xC2,           // aesenc xmm0, xmm2
+        0x66, 0x0F, 0x38, 0xDD, 0xC1,           // aesenclast xmm0, xmm1
+        0xC3,
+    ];
+
+    let regions = find_regions(&data, None);
+
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].algorithm, "Rijndael");
+    assert_eq!(regions[0].start, 2);
+    assert_eq!(regions[0].end, 18);
+    assert_eq!(regions[0].count, 4);
+    assert_eq!(regions[0].mnemonics, vec!["aesenc", "aesenclast"]);
+}
+
+#[test]
+fn test_find_arm64_regions() {
+    let data = [
+        0x20, 0x48, 0x28, 0x4E,     // aese v0.16b, v1.16b
+        0x00, 0x68, 0x28, 0x4E,     // aesmc v0.16b, v0.16b
+        0x40, 0x48, 0x28, 0x4E,     // aese v0.16b, v2.16b
+        0x00, 0x68, 0x28, 0x4E,     // aesmc v0.16b, v0.16b
+    ];
+
+    let regions = find_regions(&data, None);
+
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].isa, Isa::Arm64);
+    assert_eq!(regions[0].mnemonics, vec!["aese", "aesmc"]);
+}
+
+#[test]
+fn test_find_arm32_regions() {
+    let arm = [
+        0x02, 0x03, 0xB0, 0xF3,     // aese.8 q0, q1
+        0x80, 0x03, 0xB0, 0xF3,     // aesmc.8 q0, q0
+        0x44, 0x03, 0xB0, 0xF3,     // aesd.8 q0, q2
+        0xC0, 0x03, 0xB0, 0xF3,     // aesimc.8 q0, q0
+    ];
+    let thumb = [
+        0xB0, 0xFF, 0x02, 0x03,     // aese.8 q0, q1
+        0xB0, 0xFF, 0x80, 0x03,     // aesmc.8 q0, q0
+        0x00, 0xBF,                 // nop
+        0x02, 0xFF, 0x44, 0x0C,     // sha256h.32 q0, q1, q2
+        0xA1, 0xEF, 0x02, 0x0E,     // vmull.p64 q0, d1, d2
+        0xB0, 0xFF, 0x44, 0x03,     // aesd.8 q0, q2
+        0xB0, 0xFF, 0xC0, 0x03,     // aesimc.8 q0, q0
+    ];
+
+    let regions = find_regions(&arm, None);
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].isa, Isa::Arm);
+    assert_eq!(regions[0].mnemonics, vec!["aese", "aesmc", "aesd", "aesimc"]);
+
+    let regions = find_regions(&thumb, None);
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].isa, Isa::Thumb);
+    assert_eq!((regions[0].start, regions[0].end), (0, 22));
+}
@@ -0,0 +1,56 @@
+//! Searches data for patterns that indicate cryptographic algorithms - e.g.
+//! the constants used by SHA-256, or AES/Rijndael's lookup tables - in both
+//! byte orders.
+//!
+//! A `Scanner` is built from a set of patterns, usually the built-in ones from
+//! `patterns::get_patterns()`:
+//!
+//! ```no_run
+//! use findcrypt::patterns;
+//! use findcrypt::scanner::Scanner;
+//!
+//! let scanner = Scanner::new(patterns::get_patterns());
+//! for m in scanner.scan_path("/usr/lib/libcrypto.so").unwrap() {
+//!     println!("0x{:08x}: {} ({})", m.offset, m.pattern.algorithm, m.endianness.name());
+//! }
+//! ```
+
+extern crate aho_corasick;
+extern crate byteorder;
+extern crate bzip2;
+extern crate flate2;
+extern crate libc;
+#[macro_use] extern crate log;
+extern crate mmap;
+extern crate rustc_serialize;
+extern crate toml;
+extern crate xz2;
+
+/// Like `try!`, but for `Option`.
+macro_rules! try_opt {
+    ($e:expr) => {
+        match $e {
+            Some(v) => v,
+            None    => return None,
+        }
+    };
+}
+
+pub mod algorithms;
+pub mod alphabet;
+pub mod baseline;
+pub mod binfmt;
+pub mod container;
+pub mod detector;
+pub mod diff;
+pub mod endian;
+pub mod fingerprint;
+pub mod imports;
+pub mod instructions;
+pub mod output;
+pub mod patterns;
+pub mod policy;
+pub mod process;
+pub mod scanner;
+pub mod signatures;
+pub mod yara;
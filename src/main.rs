@@ -1,29 +1,35 @@
-extern crate aho_corasick;
 extern crate ansi_term;
-extern crate byteorder;
 extern crate clap;
 extern crate fern;
+extern crate findcrypt;
 extern crate handlebars;
 #[macro_use] extern crate itertools;
-extern crate libc;
 #[macro_use] extern crate log;
-extern crate mmap;
+extern crate regex;
 extern crate rustc_serialize;
 extern crate time;
 
+use std::cell::RefCell;
+use std::cmp;
 use std::collections::BTreeMap;
 use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
 
-use aho_corasick::{Automaton, AcAutomaton};
-use clap::{Arg, App, SubCommand};
+use clap::{Arg, App, ArgMatches, SubCommand};
+use findcrypt::{algorithms, baseline, binfmt, container, diff, fingerprint, output, patterns, policy, process,
+                signatures, yara};
+use findcrypt::detector::{self, Detector, Finding};
+use findcrypt::endian::Endianness;
+use findcrypt::scanner::{self, Match, Scanner};
 use handlebars::Handlebars;
 use itertools::Itertools;
-use mmap::{MemoryMap, MapOption};
+use regex::Regex;
+use rustc_serialize::base64::{self, ToBase64};
+use rustc_serialize::hex::ToHex;
 use rustc_serialize::json::{Json, ToJson};
 
-mod endian;
 mod logger;
-mod patterns;
 
 
 fn main() {
@@ -38,146 +44,1282 @@ fn main() {
         .subcommand_required(true)
         .subcommand(SubCommand::with_name("scan")
                     .about("Search the given input file(s)")
+                    .arg(Arg::with_name("signatures")
+                         .long("signatures")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Also load signatures from this file of YARA rules or findcrypt2-style C arrays"))
                     .arg(Arg::with_name("template")
                          .long("template")
                          .takes_value(true)
-                         .help("Handlebars template specifying the output format for found items."))
+                         .help("Handlebars template specifying the output format for found items.  Context hints are prefixed with \"[context] \"."))
+                    .arg(Arg::with_name("non-crypto")
+                         .long("non-crypto")
+                         .help("Also search for non-cryptographic hashes and PRNGs, whose 32-bit constants are only reported in pairs"))
+                    .arg(Arg::with_name("context-hints")
+                         .long("context-hints")
+                         .help("Also search for compression and encoding tables, which are reported separately"))
+                    .arg(Arg::with_name("fingerprint")
+                         .long("fingerprint")
+                         .help("Attempt to identify the cryptographic libraries used"))
+                    .arg(Arg::with_name("imports")
+                         .long("imports")
+                         .help("Also report imported cryptographic APIs in ELF and PE files"))
+                    .arg(Arg::with_name("instructions")
+                         .long("instructions")
+                         .help("Also search for hardware cryptographic instructions (AES-NI, SHA-NI, ARMv8 Crypto, ...)"))
+                    .arg(Arg::with_name("detectors")
+                         .long("detectors")
+                         .takes_value(true)
+                         .help("Comma-separated list of detectors to use, instead of the ones enabled by the flags above"))
+                    .arg(Arg::with_name("disable-detectors")
+                         .long("disable-detectors")
+                         .takes_value(true)
+                         .help("Comma-separated list of detectors not to use"))
+                    .arg(Arg::with_name("only")
+                         .long("only")
+                         .takes_value(true)
+                         .help("Comma-separated list of the signatures to search for, in any category: algorithms, families, variable names, categories or globs like 'SHA*'"))
+                    .arg(Arg::with_name("exclude")
+                         .long("exclude")
+                         .takes_value(true)
+                         .help("Comma-separated list of signatures not to search for, in the same form as --only"))
+                    .arg(Arg::with_name("endian")
+                         .long("endian")
+                         .takes_value(true)
+                         .help("Byte order to search for tables in: le, be or both (default)"))
+                    .arg(Arg::with_name("max-depth")
+                         .long("max-depth")
+                         .takes_value(true)
+                         .help("Maximum depth of nested archives to unpack (default: 8)"))
+                    .arg(Arg::with_name("max-size")
+                         .long("max-size")
+                         .takes_value(true)
+                         .help("Maximum size of a decompressed file, in MiB (default: 256)"))
+                    .arg(Arg::with_name("max-total")
+                         .long("max-total")
+                         .takes_value(true)
+                         .help("Maximum total size of decompressed data, including that of enclosing archives, in MiB (default: 1024)"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .help("Output format: text (default), json, sarif, or a script for idapython, ghidra or r2"))
+                    .arg(Arg::with_name("severity")
+                         .long("severity")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Sets the SARIF level (error, warning, note or none) for an algorithm, as ALGORITHM=LEVEL"))
+                    .arg(Arg::with_name("policy")
+                         .long("policy")
+                         .takes_value(true)
+                         .help("TOML file saying which algorithms to allow, warn about or deny; exits non-zero if any are denied"))
+                    .arg(Arg::with_name("baseline")
+                         .long("baseline")
+                         .takes_value(true)
+                         .help("JSON file of known findings, which aren't reported"))
+                    .arg(Arg::with_name("write-baseline")
+                         .long("write-baseline")
+                         .requires("baseline")
+                         .help("Add any new findings to the baseline file, creating it if needed"))
+                    .arg(Arg::with_name("context")
+                         .long("context")
+                         .takes_value(true)
+                         .help("Include this many bytes before and after each match in the output"))
+                    .arg(Arg::with_name("extract")
+                         .long("extract")
+                         .takes_value(true)
+                         .help("Write each match to its own file in this directory"))
+                    .arg(Arg::with_name("extract-padding")
+                         .long("extract-padding")
+                         .takes_value(true)
+                         .help("Include this many bytes before and after each extracted match"))
+                    .arg(Arg::with_name("pid")
+                         .long("pid")
+                         .takes_value(true)
+                         .help("Search the memory of the running process with this PID (Linux only)"))
                     .arg(Arg::with_name("input")
-                         .help("Sets the input file(s) to search")
-                         .required(true)
+                         .help("Sets the input file(s) to search, or - for standard input")
                          .multiple(true)
                          ))
         .subcommand(SubCommand::with_name("list")
-                    .about("Lists available signatures"))
+                    .about("Lists available signatures")
+                    .arg(Arg::with_name("signatures")
+                         .long("signatures")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Also load signatures from this file of YARA rules or findcrypt2-style C arrays"))
+                    .arg(Arg::with_name("non-crypto")
+                         .long("non-crypto")
+                         .help("Also list non-cryptographic hashes and PRNGs"))
+                    .arg(Arg::with_name("context-hints")
+                         .long("context-hints")
+                         .help("Also list compression and encoding tables"))
+                    .arg(Arg::with_name("detectors")
+                         .long("detectors")
+                         .help("List the available detectors instead"))
+                    .arg(Arg::with_name("verbose")
+                         .short("v")
+                         .long("verbose")
+                         .help("Show each signature's variable name, size and description"))
+                    .arg(Arg::with_name("algorithm")
+                         .long("algorithm")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Only list signatures for this algorithm"))
+                    .arg(Arg::with_name("category")
+                         .long("category")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Only list signatures in this category: crypto, non-crypto or context"))
+                    .arg(Arg::with_name("match")
+                         .long("match")
+                         .takes_value(true)
+                         .help("Only list signatures whose algorithm, variable name or description matches this regular expression"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .help("Output format: text (default) or json")))
+        .subcommand(SubCommand::with_name("show")
+                    .about("Prints a signature's bytes, in both byte orders")
+                    .arg(Arg::with_name("signatures")
+                         .long("signatures")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Also load signatures from this file of YARA rules or findcrypt2-style C arrays"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .help("Output format: text (default) or json"))
+                    .arg(Arg::with_name("varname")
+                         .help("The signature's variable name, as shown by list --verbose")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("export-yara")
+                    .about("Prints YARA rules for the available signatures")
+                    .arg(Arg::with_name("signatures")
+                         .long("signatures")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Also load signatures from this file of YARA rules or findcrypt2-style C arrays"))
+                    .arg(Arg::with_name("non-crypto")
+                         .long("non-crypto")
+                         .help("Also export non-cryptographic hashes and PRNGs"))
+                    .arg(Arg::with_name("context-hints")
+                         .long("context-hints")
+                         .help("Also export compression and encoding tables"))
+                    .arg(Arg::with_name("group")
+                         .long("group")
+                         .help("Also add a rule for each algorithm that matches any of its signatures")))
+        .subcommand(SubCommand::with_name("diff")
+                    .about("Compares the algorithms and tables found in two files or directories")
+                    .arg(Arg::with_name("signatures")
+                         .long("signatures")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Also load signatures from this file of YARA rules or findcrypt2-style C arrays"))
+                    .arg(Arg::with_name("non-crypto")
+                         .long("non-crypto")
+                         .help("Also compare non-cryptographic hashes and PRNGs"))
+                    .arg(Arg::with_name("context-hints")
+                         .long("context-hints")
+                         .help("Also compare compression and encoding tables"))
+                    .arg(Arg::with_name("max-depth")
+                         .long("max-depth")
+                         .takes_value(true)
+                         .help("Maximum depth of nested archives to unpack (default: 8)"))
+                    .arg(Arg::with_name("max-size")
+                         .long("max-size")
+                         .takes_value(true)
+                         .help("Maximum size of a decompressed file, in MiB (default: 256)"))
+                    .arg(Arg::with_name("max-total")
+                         .long("max-total")
+                         .takes_value(true)
+                         .help("Maximum total size of decompressed data, including that of enclosing archives, in MiB (default: 1024)"))
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .help("Output format: text (default) or json"))
+                    .arg(Arg::with_name("old")
+                         .help("The old file or directory")
+                         .required(true))
+                    .arg(Arg::with_name("new")
+                         .help("The new file or directory")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("selftest")
+                    .about("Checks the built-in tables against the definitions they're generated from"))
         .get_matches();
     logger::init_logger_config(&matches);
 
-    let patterns = patterns::get_patterns();
+    if let Some(submatches) = matches.subcommand_matches("list") {
+        if submatches.is_present("detectors") {
+            let patterns = match select_patterns(submatches) {
+                Some(p) => p,
+                None    => return,
+            };
+            let scanner = Scanner::new(patterns);
 
-    if let Some(_) = matches.subcommand_matches("list") {
-        println!("Supported signatures:\n--------------------------------------------------");
-        for pat in patterns.into_iter().unique_by(|p| p.algorithm) {
-            println!(" - {}", pat.algorithm);
+            println!("Available detectors:\n--------------------------------------------------");
+            for det in detector::builtin(&scanner) {
+                println!(" - {:<14}{}", det.name(), det.description());
+            }
+            return;
+        }
+
+        run_list(submatches);
+    } else if let Some(submatches) = matches.subcommand_matches("show") {
+        run_show(submatches);
+    } else if let Some(submatches) = matches.subcommand_matches("export-yara") {
+        let patterns = match select_patterns(submatches) {
+            Some(p) => p,
+            None    => return,
+        };
+
+        print!("{}", yara::rules(&patterns, submatches.is_present("group")));
+    } else if let Some(submatches) = matches.subcommand_matches("diff") {
+        run_diff(submatches);
+    } else if matches.subcommand_matches("selftest").is_some() {
+        let checks = patterns::selftest::run();
+        for check in &checks {
+            match check.error {
+                Some(ref e) => println!("FAIL {}: {}", check.name, e),
+                None        => println!("ok   {}", check.name),
+            }
+        }
+
+        let failed = checks.iter().filter(|c| c.error.is_some()).count();
+        if failed > 0 {
+            error!("{} of {} table(s) don't match their definitions", failed, checks.len());
+            std::process::exit(1);
         }
     } else if let Some(submatches) = matches.subcommand_matches("scan") {
+        let patterns = match select_patterns(submatches) {
+            Some(p) => p,
+            None    => return,
+        };
+
         debug!("Compiling Handlebars template");
         let mut hbs = Handlebars::new();
         let template = submatches.value_of("template")
             .unwrap_or("{{path}}:{{address}}:{{algorithm}} ({{endian}}) - {{desc}}");
 
+        // Context hints are tagged, so they can't be confused with actual
+        // findings.  The tag goes in front of the user's own template.
+        let context_template = match submatches.value_of("template") {
+            Some(t) => format!("[context] {}", t),
+            None    => "{{path}}:{{address}}:[context] {{algorithm}} ({{endian}}) - {{desc}}".to_string(),
+        };
+
         if let Err(e) = hbs.register_template_string("crypt", template.to_string()) {
             error!("Could not compile template string: {}", e);
             return;
         }
+        if let Err(e) = hbs.register_template_string("context", context_template) {
+            error!("Could not compile template string: {}", e);
+            return;
+        }
+        hbs.register_template_string("library",
+            "{{path}}:library:{{library}} {{version}} - {{evidence}}".to_string()).unwrap();
+        hbs.register_template_string("import",
+            "{{path}}:import:{{library}}!{{symbol}} - {{algorithm}}".to_string()).unwrap();
+        hbs.register_template_string("instructions",
+            "{{path}}:{{address}}:{{algorithm}} (instructions, {{isa}}) - {{count}} instructions: {{mnemonics}}".to_string()).unwrap();
+        hbs.register_template_string("violation",
+            "{{action}}: {{path}}:{{address}}:{{algorithm}} ({{reason}}) - {{count}} match(es)".to_string()).unwrap();
+
+        let limits = match select_limits(submatches) {
+            Some(l) => l,
+            None    => return,
+        };
+
+        let format = match submatches.value_of("format").map(output::Format::from_name) {
+            None          => output::Format::Text,
+            Some(Some(f)) => f,
+            Some(None)    => {
+                error!("Invalid output format: {}", submatches.value_of("format").unwrap());
+                return;
+            },
+        };
+
+        let mut severities = BTreeMap::new();
+        if let Some(values) = submatches.values_of("severity") {
+            for value in values {
+                let mut parts = value.rsplitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(level), Some(algorithm)) if output::SARIF_LEVELS.contains(&level) => {
+                        severities.insert(algorithm.to_lowercase(), level.to_string());
+                    },
+                    _ => {
+                        error!("Invalid severity: {} (expected ALGORITHM=LEVEL)", value);
+                        return;
+                    },
+                }
+            }
+        }
+
+        let policy = match submatches.value_of("policy").map(policy::load) {
+            None         => None,
+            Some(Ok(p))  => Some(p),
+            Some(Err(e)) => {
+                error!("Could not load policy from {}: {}", submatches.value_of("policy").unwrap(), e);
+                return;
+            },
+        };
+
+        // Unless they've been given explicitly, SARIF levels follow the policy.
+        if let Some(ref policy) = policy {
+            for pat in &patterns {
+                let level = match policy.action(pat.algorithm).0 {
+                    policy::Action::Deny  => "error",
+                    policy::Action::Warn  => "warning",
+                    policy::Action::Allow => continue,
+                };
+                severities.entry(pat.algorithm.to_lowercase()).or_insert(level.to_string());
+            }
+        }
+
+        let write_baseline = submatches.is_present("write-baseline");
+        let baseline = match submatches.value_of("baseline").map(|p| baseline::load(p, write_baseline)) {
+            None         => None,
+            Some(Ok(b))  => Some(RefCell::new(b)),
+            Some(Err(e)) => {
+                error!("Could not load baseline from {}: {}", submatches.value_of("baseline").unwrap(), e);
+                return;
+            },
+        };
+
+        let mut context = 0;
+        if let Some(value) = submatches.value_of("context") {
+            match value.parse() {
+                Ok(n)  => context = n,
+                Err(_) => {
+                    error!("Invalid context size: {}", value);
+                    return;
+                },
+            }
+        }
+        let mut extract_padding = 0;
+        if let Some(value) = submatches.value_of("extract-padding") {
+            match value.parse() {
+                Ok(n)  => extract_padding = n,
+                Err(_) => {
+                    error!("Invalid extraction padding: {}", value);
+                    return;
+                },
+            }
+        }
+
+        let extract = submatches.value_of("extract").map(|d| d.to_string());
+        if let Some(ref dir) = extract {
+            if let Err(e) = fs::create_dir_all(dir) {
+                error!("Could not create extraction directory {}: {}", dir, e);
+                return;
+            }
+        }
+
+        let endians = match submatches.value_of("endian").unwrap_or("both") {
+            "le"   => vec![Endianness::LittleEndian],
+            "be"   => vec![Endianness::BigEndian],
+            "both" => vec![Endianness::LittleEndian, Endianness::BigEndian],
+            e      => {
+                error!("Invalid byte order: {} (expected le, be or both)", e);
+                return;
+            },
+        };
 
-        // Build Aho-Corasick automaton.
         debug!("Creating Aho-Corasick automaton");
-        let at = build_automaton(&patterns);
+        let scanner = Scanner::with_endianness(patterns, &endians);
+
+        let detectors = match select_detectors(submatches, &scanner) {
+            Some(d) => d,
+            None    => return,
+        };
+
+        let fingerprinter = if submatches.is_present("fingerprint") {
+            Some(fingerprint::Fingerprinter::new())
+        } else {
+            None
+        };
+
+        let options = ScanOptions {
+            detectors:       detectors,
+            fingerprinter:   fingerprinter,
+            limits:          limits,
+            format:          format,
+            context:         context,
+            extract:         extract,
+            extract_padding: extract_padding,
+            severities:      severities,
+            policy:          policy,
+            baseline:        baseline,
+            write_baseline:  write_baseline,
+            results:         RefCell::new(vec![]),
+            violations:      RefCell::new(vec![]),
+        };
 
         debug!("Starting search");
+        if let Some(pid) = submatches.value_of("pid") {
+            match pid.parse() {
+                Ok(pid) => {
+                    info!("Searching process: {}", pid);
+                    search_process(pid, &hbs, &options);
+                },
+                Err(_) => error!("Invalid PID: {}", pid),
+            }
+        }
+
         if let Some(ref input_paths) = submatches.values_of("input") {
             for input_path in input_paths {
                 info!("Searching file: {}", input_path);
-                search_file(&patterns, &at, input_path, &hbs);
+                search_file(&scanner, input_path, &hbs, &options);
             }
-        } else {
+        } else if !submatches.is_present("pid") {
             warn!("No input file(s) given");
         }
+
+        match options.format {
+            output::Format::Sarif => {
+                println!("{}", output::sarif(scanner.patterns(), &options.results.borrow(), &options.severities).pretty());
+            },
+            f if f.collects() => print!("{}", output::script(f, &options.results.borrow())),
+            _                 => {},
+        }
+
+        if let Some(ref baseline) = options.baseline {
+            let baseline = baseline.borrow();
+            if baseline.suppressed > 0 {
+                info!("{} finding(s) suppressed by the baseline", baseline.suppressed);
+            }
+
+            if options.write_baseline {
+                let path = submatches.value_of("baseline").unwrap();
+                match baseline::save(path, &baseline) {
+                    Ok(()) => info!("Added {} finding(s) to {}", baseline.added, path),
+                    Err(e) => error!("Could not write baseline to {}: {}", path, e),
+                }
+            }
+        }
+
+        if report_violations(&hbs, &options) {
+            io::stdout().flush().unwrap();
+            std::process::exit(1);
+        }
     }
 }
 
-fn build_automaton(patterns: &Vec<patterns::Pattern>) -> AcAutomaton<Vec<u8>> {
-    let mut patterns_vec = vec![];
+/// Options that control what `search_file` looks for, other than the patterns
+/// themselves.
+struct ScanOptions<'a> {
+    /// The detectors to run on each file.
+    detectors: Vec<&'a Detector>,
+
+    /// Used to identify the libraries that the found tables come from, if
+    /// asked to.
+    fingerprinter: Option<fingerprint::Fingerprinter>,
+
+    /// Limits on unpacking compressed files and archives.
+    limits: container::Limits,
+
+    format: output::Format,
+
+    /// The number of bytes around each match to include in the output.
+    context: usize,
+
+    /// The directory to write matches to, if any, and the number of bytes
+    /// around each match to include.
+    extract: Option<String>,
+    extract_padding: usize,
+
+    /// SARIF levels for algorithms, keyed by lowercased algorithm name.
+    severities: BTreeMap<String, String>,
+
+    /// Which algorithms are allowed, if there's a policy.
+    policy: Option<policy::Policy>,
+
+    /// Known findings that aren't reported, and whether new findings should
+    /// be added to them.
+    baseline: Option<RefCell<baseline::Baseline>>,
+    write_baseline: bool,
+
+    /// Results collected for formats that are written once the scan is done.
+    results: RefCell<Vec<BTreeMap<String, Json>>>,
 
-    for pat in patterns {
-        // NOTE: Order matters here! See comment in `search_file`.
-        patterns_vec.push(pat.bytes.as_byte_vec(endian::Endianness::LittleEndian));
-        patterns_vec.push(pat.bytes.as_byte_vec(endian::Endianness::BigEndian));
+    /// Algorithms found that the policy doesn't allow, one per path and
+    /// algorithm.
+    violations: RefCell<Vec<BTreeMap<String, Json>>>,
+}
+
+/// Returns the patterns that should be used, given the command-line options,
+/// or `None` if a signature file couldn't be loaded or nothing is left to
+/// search for.  Non-cryptographic patterns and context hints are excluded
+/// unless asked for, or picked out with `--only`.
+fn select_patterns(matches: &ArgMatches) -> Option<Vec<patterns::Pattern>> {
+    let non_crypto = matches.is_present("non-crypto");
+    let context_hints = matches.is_present("context-hints");
+
+    let all = match load_patterns(matches) {
+        Some(p) => p,
+        None    => return None,
+    };
+
+    let selectors = |name| {
+        matches.value_of(name)
+            .map_or(vec![], |s: &str| s.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect::<Vec<_>>())
+    };
+    let (only, exclude) = (selectors("only"), selectors("exclude"));
+
+    // A selector that matches nothing is probably a typo.
+    for selector in only.iter().chain(exclude.iter()) {
+        if !all.iter().any(|p| p.is_selected_by(selector)) {
+            warn!("No signatures match {}", selector);
+        }
     }
 
-    AcAutomaton::new(patterns_vec)
+    let selected = all
+        .into_iter()
+        .filter(|p| if only.is_empty() {
+            match p.category {
+                patterns::Category::Crypto    => true,
+                patterns::Category::NonCrypto => non_crypto,
+                patterns::Category::Context   => context_hints,
+            }
+        } else {
+            only.iter().any(|s| p.is_selected_by(s))
+        })
+        .filter(|p| !exclude.iter().any(|s| p.is_selected_by(s)))
+        .collect::<Vec<_>>();
+
+    if selected.is_empty() && !(only.is_empty() && exclude.is_empty()) {
+        error!("No signatures left to search for");
+        return None;
+    }
+    Some(selected)
 }
 
+/// Returns the built-in patterns in every category, along with any loaded
+/// with `--signatures`, or `None` if a file couldn't be loaded.
+fn load_patterns(matches: &ArgMatches) -> Option<Vec<patterns::Pattern>> {
+    let mut all = patterns::get_patterns();
+    if let Some(paths) = matches.values_of("signatures") {
+        for path in paths {
+            match signatures::load(path) {
+                Ok(p)  => {
+                    info!("Loaded {} signature(s) from {}", p.len(), path);
+                    all.extend(p);
+                },
+                Err(e) => {
+                    error!("Could not load signatures from {}: {}", path, e);
+                    return None;
+                },
+            }
+        }
+    }
 
-fn search_file<P>(patterns: &Vec<patterns::Pattern>, at: &AcAutomaton<Vec<u8>>, input_path: P, hbs: &Handlebars)
+    Some(all)
+}
+
+/// Returns the detectors to use, given the command-line options, or `None` if
+/// an unknown one was named.  Unless they're listed explicitly, tables are
+/// always searched for and the other detectors have their own flags.
+fn select_detectors<'a>(matches: &ArgMatches, scanner: &'a Scanner) -> Option<Vec<&'a Detector>> {
+    let all = detector::builtin(scanner);
+
+    let mut enabled = match matches.value_of("detectors") {
+        Some(names) => names.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()).collect(),
+        None        => {
+            let mut names = vec!["tables"];
+            if matches.is_present("context-hints") {
+                names.push("alphabets");
+            }
+            if matches.is_present("imports") {
+                names.push("imports");
+            }
+            if matches.is_present("instructions") {
+                names.push("instructions");
+            }
+            names
+        },
+    };
+    let disabled = matches.value_of("disable-detectors")
+        .map_or(vec![], |names| names.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()).collect());
+
+    for name in enabled.iter().chain(disabled.iter()) {
+        if !all.iter().any(|d| d.name() == *name) {
+            error!("Unknown detector: {} (available: {})", name, all.iter().map(|d| d.name()).join(", "));
+            return None;
+        }
+    }
+    enabled.retain(|n| !disabled.contains(n));
+
+    Some(all.into_iter().filter(|d| enabled.contains(&d.name())).collect())
+}
+
+/// Returns the limits on unpacking archives given on the command line, or
+/// `None` if they're invalid.
+fn select_limits(matches: &ArgMatches) -> Option<container::Limits> {
+    let mut limits = container::Limits::default();
+    if let Some(depth) = matches.value_of("max-depth") {
+        match depth.parse() {
+            Ok(d)  => limits.max_depth = d,
+            Err(_) => {
+                error!("Invalid maximum depth: {}", depth);
+                return None;
+            },
+        }
+    }
+    if let Some(size) = matches.value_of("max-size") {
+        match size.parse::<u64>().ok().and_then(|s| s.checked_mul(1024 * 1024)) {
+            Some(s) => limits.max_size = s,
+            None    => {
+                error!("Invalid maximum size: {}", size);
+                return None;
+            },
+        }
+    }
+    if let Some(size) = matches.value_of("max-total") {
+        match size.parse::<u64>().ok().and_then(|s| s.checked_mul(1024 * 1024)) {
+            Some(s) => limits.max_total = s,
+            None    => {
+                error!("Invalid maximum total size: {}", size);
+                return None;
+            },
+        }
+    }
+    Some(limits)
+}
+
+
+fn search_file<P>(scanner: &Scanner, input_path: P, hbs: &Handlebars, options: &ScanOptions)
 where P: std::convert::AsRef<std::path::Path>
 {
     let path = input_path.as_ref();
+    let name = format!("{}", path.display());
 
-    with_file_mmap(path, |map| {
-        // Run the automaton on the file!
-        for mm in at.stream_find(map) {
-            // Reading should never fail, since we're using a mmap'd buffer.
-            let mtch = mm.unwrap();
-
-            // When building, we added two patterns to the automaton - little
-            // endian and big-endian, in that order.  Reverse this back to the
-            // corresponding `Pattern`.
-            let pati = mtch.pati / 2;
-            let endian = if mtch.pati % 2 == 0 {
-                "LE"
-            } else {
-                "BE"
-            };
-            let pattern = &patterns[pati];
-
-            // Insert information into a map that we use for rendering.
-            let mut info = BTreeMap::<String, Json>::new();
+    if name == "-" {
+        let stdin = io::stdin();
+        search_stream(scanner, "-", stdin.lock(), hbs, options);
+        return;
+    }
 
-            info.insert("path".to_string(),      format!("{}", path.display()).to_json());
-            info.insert("address".to_string(),   format!("0x{:08x}", mtch.start).to_json());
-            info.insert("algorithm".to_string(), pattern.algorithm.to_json());
-            info.insert("endian".to_string(),    endian.to_json());
-            info.insert("desc".to_string(),      pattern.desc.to_json());
+    let res = scanner::with_file_mmap(path, |map| {
+        // Compressed files and archives are unpacked, and each file inside is
+        // searched separately.
+        container::walk(&name, map, &options.limits, &mut |member, data, base| {
+            search_data(member, data, base, &BTreeMap::new(), hbs, options);
+        });
+    });
 
-            // Run the template.
-            let res = match hbs.render("crypt", &info) {
-                Ok(r)  => r,
-                Err(_) => "error rendering template".to_string(),
-            };
+    // Pipes, sockets and many procfs/sysfs files can't be mapped, but we can
+    // still read them.
+    if let Err(e) = res {
+        debug!("Could not map {}, falling back to streaming: {}", name, e);
 
-            println!("{}", res);
+        match fs::File::open(path) {
+            Ok(file) => search_stream(scanner, &name, file, hbs, options),
+            Err(e)   => error!("Could not open {}: {}", name, e),
         }
+    }
+}
+
+
+/// Searches data that we can't map into memory, by streaming it through the
+/// automaton.  Only the patterns are searched for, since everything else needs
+/// all of the data at once.
+fn search_stream<R: Read>(scanner: &Scanner, path: &str, reader: R, hbs: &Handlebars, options: &ScanOptions) {
+    if options.fingerprinter.is_some() || options.detectors.iter().any(|d| d.name() != scanner.name()) {
+        warn!("{}: only signatures can be searched for in streamed input", path);
+    }
+    if options.context > 0 || options.extract.is_some() {
+        warn!("{}: matches can't be shown or extracted from streamed input", path);
+    }
+    if options.baseline.is_some() {
+        warn!("{}: baseline hashes of streamed input only cover the matched bytes", path);
+    }
+    if !options.detectors.iter().any(|d| d.name() == scanner.name()) {
+        return;
+    }
+
+    let extra = BTreeMap::new();
+
+    let res = scanner.scan_reader_with(reader, |mtch| {
+        report_match(&mtch, path, None, 0, None, &extra, hbs, options);
     });
+    if let Err(e) = res {
+        error!("Error reading {}: {}", path, e);
+    }
 }
 
 
-// --------------------------------------------------
+/// Searches the memory of a running process, one mapping at a time.
+fn search_process(pid: u32, hbs: &Handlebars, options: &ScanOptions) {
+    let mappings = match process::mappings(pid) {
+        Ok(m)  => m,
+        Err(e) => {
+            error!("Could not read memory maps of process {}: {}", pid, e);
+            return;
+        },
+    };
+    let mut mem = match process::open_memory(pid) {
+        Ok(f)  => f,
+        Err(e) => {
+            error!("Could not open memory of process {}: {}", pid, e);
+            return;
+        },
+    };
+
+    for mapping in mappings.iter().filter(|m| m.readable()) {
+        let size = mapping.end - mapping.start;
+        if size > options.limits.max_size {
+            warn!("Skipping mapping at 0x{:x} ({}): {} bytes is larger than the limit", mapping.start, mapping.name(), size);
+            continue;
+        }
+        // The kernel's vsyscall page and vvar data can't be read through
+        // /proc/<pid>/mem.
+        if mapping.path == "[vvar]" || mapping.path == "[vsyscall]" {
+            continue;
+        }
+
+        debug!("Searching mapping at 0x{:x} ({} {})", mapping.start, mapping.perms, mapping.name());
+        let (data, unreadable) = process::read_mapping(&mut mem, mapping);
+        if unreadable > 0 {
+            warn!("{} page(s) of the mapping at 0x{:x} ({}) could not be read", unreadable, mapping.start, mapping.name());
+        }
+
+        let mut extra = BTreeMap::<String, Json>::new();
+        extra.insert("pid".to_string(),     pid.to_json());
+        extra.insert("mapping".to_string(), mapping.name().to_json());
+        extra.insert("perms".to_string(),   mapping.perms.to_json());
 
-#[cfg(unix)]
-fn get_fd(file: &fs::File) -> libc::c_int {
-    use std::os::unix::io::AsRawFd;
-    file.as_raw_fd()
+        let path = format!("pid {}:{} ({})", pid, mapping.name(), mapping.perms);
+        search_data(&path, &data, mapping.start, &extra, hbs, options);
+    }
 }
 
-#[cfg(windows)]
-fn get_fd(file: &fs::File) -> libc::HANDLE {
-    use std::os::windows::io::AsRawHandle;
-    file.as_raw_handle() as libc::HANDLE
+
+/// Renders a match.  `data` is the data that was searched, if we have all of
+/// it, and `binary` is that data parsed as an executable, if it is one.
+fn report_match(mtch: &Match, path: &str, data: Option<&[u8]>, base: u64, binary: Option<&binfmt::Binary>,
+                extra: &BTreeMap<String, Json>, hbs: &Handlebars, options: &ScanOptions) {
+    let pattern = mtch.pattern;
+    let (start, end) = (mtch.offset, mtch.offset + mtch.len);
+
+    // Streamed input only gives us the match itself, so its hash is the same
+    // wherever the table is found.
+    let hash = match data {
+        Some(data) => baseline::match_hash(data, start, end),
+        None       => baseline::content_hash(&pattern.bytes.as_byte_vec(mtch.endianness)),
+    };
+    let finding = baseline::Finding {
+        path:      path.to_string(),
+        algorithm: pattern.algorithm.to_string(),
+        varname:   pattern.varname.to_string(),
+        hash:      hash,
+    };
+    if let Some(ref baseline) = options.baseline {
+        let mut baseline = baseline.borrow_mut();
+        if baseline.suppress(&finding) {
+            return;
+        }
+
+        // New findings are still reported when they're added.
+        if options.write_baseline {
+            baseline.add(&finding);
+        }
+    }
+
+    // Insert information into a map that we use for rendering.
+    let mut info = new_info(path, extra);
+    info.insert("address".to_string(),   format!("0x{:08x}", base + start as u64).to_json());
+    info.insert("algorithm".to_string(), pattern.algorithm.to_json());
+    info.insert("endian".to_string(),    mtch.endianness.name().to_json());
+    info.insert("desc".to_string(),      pattern.desc.to_json());
+    info.insert("category".to_string(),  pattern.category.name().to_json());
+    info.insert("varname".to_string(),   pattern.varname.to_json());
+    info.insert("hash".to_string(),      finding.hash.to_json());
+    info.insert("offset".to_string(),    start.to_json());
+    info.insert("size".to_string(),      mtch.len.to_json());
+    info.insert("element_width".to_string(), pattern.bytes.element_width().to_json());
+
+    if let Some(alg) = algorithms::lookup(pattern.algorithm) {
+        info.insert("family".to_string(), alg.family.to_json());
+        info.insert("status".to_string(), alg.status.name().to_json());
+    }
+
+    if let Some(address) = binary.and_then(|b| b.address_of(start as u64)) {
+        info.insert("virtual_address".to_string(), format!("0x{:08x}", address).to_json());
+    }
+
+    if let Some(data) = data {
+        if options.context > 0 {
+            let from = start.saturating_sub(options.context);
+            let to = cmp::min(end + options.context, data.len());
+            let context = &data[from..to];
+
+            match options.format {
+                output::Format::Text => {
+                    info.insert("hexdump".to_string(), output::hexdump(context, base + from as u64).to_json());
+                },
+                _ => {
+                    info.insert("context_address".to_string(), format!("0x{:08x}", base + from as u64).to_json());
+                    info.insert("context_hex".to_string(),     context.to_hex().to_json());
+                    info.insert("context_base64".to_string(),  context.to_base64(base64::STANDARD).to_json());
+                },
+            }
+        }
+
+        if let Some(ref dir) = options.extract {
+            let from = start.saturating_sub(options.extract_padding);
+            let to = cmp::min(end + options.extract_padding, data.len());
+            let name = output::extract_name(path, base + from as u64, pattern.algorithm);
+
+            let res = fs::File::create(Path::new(dir).join(&name))
+                .and_then(|mut f| f.write_all(&data[from..to]));
+            if let Err(e) = res {
+                error!("Could not write {}: {}", name, e);
+            }
+        }
+    }
+
+    let template = match pattern.category {
+        patterns::Category::Context => "context",
+        _                           => "crypt",
+    };
+    render(hbs, options, template, &info);
 }
 
-fn with_file_mmap<P, F, T>(path: P, f: F) -> T
-where P: std::convert::AsRef<std::path::Path>,
-      F: Fn(&[u8]) -> T
-{
-    let file = fs::OpenOptions::new()
-        .read(true)
-        .open(path)
-        .unwrap();
 
-    // Get the size of the file.
-    let len = file.metadata().unwrap().len() as usize;
+/// Returns a new map of information for rendering a result, with the path and
+/// any extra information already filled in.
+fn new_info(path: &str, extra: &BTreeMap<String, Json>) -> BTreeMap<String, Json> {
+    let mut info = extra.clone();
+    info.insert("path".to_string(), path.to_json());
+    info
+}
+
+
+/// Searches a single file (or member of an archive).  `base` is the address
+/// that the data is loaded at, which is added to reported addresses, and
+/// `extra` is any additional information to include with each result.
+fn search_data(path: &str, map: &[u8], base: u64,
+               extra: &BTreeMap<String, Json>, hbs: &Handlebars, options: &ScanOptions) {
+    let mut tables = vec![];
+    let binary = binfmt::parse(map);
+    let source = detector::Source {
+        path:   path,
+        base:   base,
+        binary: binary.as_ref(),
+    };
+
+    for det in &options.detectors {
+        for finding in det.detect(map, &source) {
+            match finding {
+                Finding::Table(mtch) => {
+                    report_match(&mtch, path, Some(map), base, binary.as_ref(), extra, hbs, options);
+                    tables.push(mtch);
+                },
+                Finding::Alphabet(mtch) => {
+                    let chars = String::from_utf8_lossy(&map[mtch.offset..mtch.offset + mtch.len]);
 
-    let fd = get_fd(&file);
+                    let mut info = new_info(path, extra);
+                    info.insert("address".to_string(),   format!("0x{:08x}", base + mtch.offset as u64).to_json());
+                    info.insert("algorithm".to_string(), mtch.name.to_json());
+                    info.insert("endian".to_string(),    "-".to_json());
+                    info.insert("desc".to_string(),      format!("custom alphabet: {}", chars).to_json());
+                    info.insert("category".to_string(),  patterns::Category::Context.name().to_json());
 
-    let chunk = MemoryMap::new(len, &[
-                               MapOption::MapReadable,
-                               MapOption::MapFd(fd),
-    ]).unwrap();
+                    render(hbs, options, "context", &info);
+                },
+                Finding::Import(import) => {
+                    let mut info = new_info(path, extra);
+                    info.insert("library".to_string(),   import.library.to_json());
+                    info.insert("symbol".to_string(),    import.symbol.to_json());
+                    info.insert("algorithm".to_string(), import.algorithm.to_json());
 
-    let file_data: &[u8] = unsafe {
-        std::slice::from_raw_parts(chunk.data() as *const _, chunk.len())
+                    render(hbs, options, "import", &info);
+                },
+                Finding::Instructions(region) => {
+                    let address = match region.address {
+                        Some(a) => format!("0x{:08x}", a),
+                        None    => format!("0x{:08x}", base + region.start as u64),
+                    };
+
+                    let mut info = new_info(path, extra);
+                    info.insert("address".to_string(),   address.to_json());
+                    info.insert("offset".to_string(),    format!("0x{:08x}", region.start).to_json());
+                    info.insert("size".to_string(),      (region.end - region.start).to_json());
+                    info.insert("algorithm".to_string(), region.algorithm.to_json());
+                    info.insert("isa".to_string(),       region.isa.name().to_json());
+                    info.insert("count".to_string(),     region.count.to_json());
+                    info.insert("mnemonics".to_string(), region.mnemonics.join(", ").to_json());
+
+                    render(hbs, options, "instructions", &info);
+                },
+                Finding::Other { offset, len, algorithm, desc } => {
+                    let mut info = new_info(path, extra);
+                    info.insert("address".to_string(),   format!("0x{:08x}", base + offset as u64).to_json());
+                    info.insert("offset".to_string(),    offset.to_json());
+                    info.insert("size".to_string(),      len.to_json());
+                    info.insert("algorithm".to_string(), algorithm.to_json());
+                    info.insert("endian".to_string(),    "-".to_json());
+                    info.insert("desc".to_string(),      desc.to_json());
+                    info.insert("detector".to_string(),  det.name().to_json());
+
+                    render(hbs, options, "crypt", &info);
+                },
+            }
+        }
+    }
+
+    if let Some(ref fingerprinter) = options.fingerprinter {
+        for ident in fingerprinter.identify(map, &tables) {
+            let mut info = new_info(path, extra);
+            info.insert("library".to_string(),  ident.library.to_json());
+            info.insert("version".to_string(),  ident.version_range().to_json());
+            info.insert("evidence".to_string(), ident.evidence.join("; ").to_json());
+
+            render(hbs, options, "library", &info);
+        }
+    }
+}
+
+
+/// Prints a result, either by rendering the given template or as JSON, or
+/// collects it to be written later.
+fn render(hbs: &Handlebars, options: &ScanOptions, template: &str, info: &BTreeMap<String, Json>) {
+    match template {
+        "crypt" | "import" | "instructions" => check_policy(options, info),
+        _                                   => {},
+    }
+
+    if options.format != output::Format::Text {
+        let mut obj = info.clone();
+        obj.insert("kind".to_string(), template.to_json());
+
+        if options.format.collects() {
+            options.results.borrow_mut().push(obj);
+        } else {
+            println!("{}", Json::Object(obj));
+        }
+        return;
+    }
+
+    let res = match hbs.render(template, info) {
+        Ok(r)  => r,
+        Err(_) => "error rendering template".to_string(),
+    };
+
+    println!("{}", res);
+    if let Some(&Json::String(ref dump)) = info.get("hexdump") {
+        println!("{}", dump);
+    }
+}
+
+
+/// Lists the signatures that match the filters given on the command line.
+fn run_list(matches: &ArgMatches) {
+    let json = match matches.value_of("format").unwrap_or("text") {
+        "text" => false,
+        "json" => true,
+        f      => {
+            error!("Invalid output format: {}", f);
+            return;
+        },
     };
 
-    f(file_data)
+    // Naming a category lists it even if it wouldn't be searched by default.
+    let categories = match matches.values_of("category") {
+        Some(names) => {
+            let mut ret = vec![];
+            for name in names {
+                match patterns::Category::from_name(name) {
+                    Some(c) => ret.push(c),
+                    None    => {
+                        error!("Unknown category: {} (available: crypto, non-crypto, context)", name);
+                        return;
+                    },
+                }
+            }
+            Some(ret)
+        },
+        None => None,
+    };
+    let all = match if categories.is_some() { load_patterns(matches) } else { select_patterns(matches) } {
+        Some(p) => p,
+        None    => return,
+    };
+
+    let re = match matches.value_of("match").map(Regex::new) {
+        Some(Ok(re)) => Some(re),
+        Some(Err(e)) => {
+            error!("Invalid regular expression: {}", e);
+            return;
+        },
+        None         => None,
+    };
+    let algorithms = matches.values_of("algorithm")
+        .map_or(vec![], |names| names.iter().map(|n| n.to_lowercase()).collect());
+
+    let selected = all.iter()
+        .filter(|p| categories.as_ref().map_or(true, |c| c.contains(&p.category)))
+        .filter(|p| algorithms.is_empty() || algorithms.contains(&p.algorithm.to_lowercase()))
+        .filter(|p| re.as_ref().map_or(true, |re| re.is_match(p.algorithm) || re.is_match(p.varname) || re.is_match(p.desc)))
+        .collect::<Vec<_>>();
+
+    if json {
+        for pat in &selected {
+            println!("{}", Json::Object(output::pattern_info(pat)));
+        }
+        return;
+    }
+
+    println!("Supported signatures:\n--------------------------------------------------");
+    for algorithm in selected.iter().map(|p| p.algorithm).unique_by(|a| *a) {
+        println!(" - {}", algorithm);
+        if !matches.is_present("verbose") {
+            continue;
+        }
+
+        for pat in selected.iter().filter(|p| p.algorithm == algorithm) {
+            let width = pat.bytes.element_width();
+            let size = pat.bytes.as_byte_vec(Endianness::LittleEndian).len();
+
+            // Built-in signatures are described by their variable name, so
+            // there's no need to show it twice.
+            let line = format!("     {:<36} {} x {:<4} {:>5} bytes", pat.varname, width, size / width, size);
+            if pat.desc != pat.varname {
+                println!("{}  {}", line, pat.desc);
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+
+/// Prints a signature's bytes in both byte orders.
+fn run_show(matches: &ArgMatches) {
+    let json = match matches.value_of("format").unwrap_or("text") {
+        "text" => false,
+        "json" => true,
+        f      => {
+            error!("Invalid output format: {}", f);
+            return;
+        },
+    };
+    let all = match load_patterns(matches) {
+        Some(p) => p,
+        None    => return,
+    };
+
+    let varname = matches.value_of("varname").unwrap();
+    let pat = match all.iter().find(|p| p.varname == varname) {
+        Some(p) => p,
+        None    => {
+            error!("Unknown signature: {} (see list --verbose)", varname);
+            return;
+        },
+    };
+
+    let le = pat.bytes.as_byte_vec(Endianness::LittleEndian);
+    let be = pat.bytes.as_byte_vec(Endianness::BigEndian);
+    if json {
+        let mut info = output::pattern_info(pat);
+        info.insert("le".to_string(), le.to_hex().to_json());
+        info.insert("be".to_string(), be.to_hex().to_json());
+        println!("{}", Json::Object(info).pretty());
+        return;
+    }
+
+    let width = pat.bytes.element_width();
+    println!("{} ({}, {})", pat.varname, pat.algorithm, pat.category.name());
+    if pat.desc != pat.varname {
+        println!("{}", pat.desc);
+    }
+    println!("{} element(s) of {} byte(s), {} bytes in total", le.len() / width, width, le.len());
+
+    // Byte order makes no difference to most tables of single bytes, but big
+    // numbers are reversed as a whole.
+    if le == be {
+        println!("\nBytes:\n{}", output::hexdump(&le, 0));
+    } else {
+        println!("\nLittle endian:\n{}", output::hexdump(&le, 0));
+        println!("\nBig endian:\n{}", output::hexdump(&be, 0));
+    }
+}
+
+
+/// Compares what's found in two files or directories.
+fn run_diff(matches: &ArgMatches) {
+    let patterns = match select_patterns(matches) {
+        Some(p) => p,
+        None    => return,
+    };
+    let limits = match select_limits(matches) {
+        Some(l) => l,
+        None    => return,
+    };
+    let json = match matches.value_of("format").unwrap_or("text") {
+        "text" => false,
+        "json" => true,
+        f      => {
+            error!("Invalid output format: {}", f);
+            return;
+        },
+    };
+
+    // Both sides are searched with the same automaton, so that they're
+    // compared like for like.
+    let scanner = Scanner::new(patterns);
+
+    let (old_path, new_path) = (Path::new(matches.value_of("old").unwrap()), Path::new(matches.value_of("new").unwrap()));
+    let name = new_path.file_name().map_or("-".to_string(), |n| n.to_string_lossy().into_owned());
+    let old = match inventory(&scanner, old_path, &name, &limits) {
+        Some(i) => i,
+        None    => return,
+    };
+    let new = match inventory(&scanner, new_path, &name, &limits) {
+        Some(i) => i,
+        None    => return,
+    };
+
+    let changes = diff::compare(&old, &new);
+    if json {
+        for change in &changes {
+            println!("{}", change.to_json());
+        }
+        return;
+    }
+
+    if changes.is_empty() {
+        println!("No differences found");
+    }
+    let mut last = None;
+    for change in &changes {
+        if last != Some(&change.path) {
+            println!("{}:", change.path);
+            last = Some(&change.path);
+        }
+        println!("  {}", diff::describe(change));
+    }
+}
+
+
+/// Returns what's found in each file under the given path, or `None` if it
+/// can't be read.
+fn inventory(scanner: &Scanner, root: &Path, name: &str, limits: &container::Limits) -> Option<diff::Inventory> {
+    let files = match diff::files(root, name) {
+        Ok(f)  => f,
+        Err(e) => {
+            error!("Could not read {}: {}", root.display(), e);
+            return None;
+        },
+    };
+
+    let inventory = RefCell::new(diff::Inventory::new());
+    for (key, path) in files {
+        info!("Searching file: {}", path.display());
+        inventory.borrow_mut().insert(key.clone(), BTreeMap::new());
+
+        let res = scanner::with_file_mmap(&path, |map| {
+            container::walk(&key, map, limits, &mut |member, data, _| {
+                let mut inventory = inventory.borrow_mut();
+                let tables = inventory.entry(member.to_string()).or_insert(BTreeMap::new());
+
+                for mtch in scanner.scan_bytes(data) {
+                    let key = (mtch.pattern.algorithm.to_string(), mtch.pattern.varname.to_string());
+                    *tables.entry(key).or_insert(0) += 1;
+                }
+            });
+        });
+        if let Err(e) = res {
+            warn!("Could not read {}: {}", path.display(), e);
+        }
+    }
+
+    Some(inventory.into_inner())
+}
+
+
+/// Records a result if the policy doesn't allow its algorithm.  Violations are
+/// counted per path and algorithm, keeping the first address found.
+fn check_policy(options: &ScanOptions, info: &BTreeMap<String, Json>) {
+    let policy = match options.policy {
+        Some(ref p) => p,
+        None        => return,
+    };
+    let path = info.get("path").and_then(|p| p.as_string()).unwrap_or("-");
+    let algorithm = match info.get("algorithm").and_then(|a| a.as_string()) {
+        Some(a) => a,
+        None    => return,
+    };
+
+    let (action, reason) = policy.action(algorithm);
+    if action == policy::Action::Allow {
+        return;
+    }
+
+    let mut violations = options.violations.borrow_mut();
+    let existing = violations.iter_mut().find(|v| {
+        v.get("path").and_then(|p| p.as_string()) == Some(path) &&
+        v.get("algorithm").and_then(|a| a.as_string()) == Some(algorithm)
+    });
+    if let Some(v) = existing {
+        let count = v.get("count").and_then(|c| c.as_u64()).unwrap_or(0);
+        v.insert("count".to_string(), (count + 1).to_json());
+        return;
+    }
+
+    let mut v = BTreeMap::new();
+    v.insert("path".to_string(),      path.to_json());
+    v.insert("address".to_string(),   info.get("address").cloned().unwrap_or("-".to_json()));
+    v.insert("algorithm".to_string(), algorithm.to_json());
+    v.insert("action".to_string(),    action.name().to_json());
+    v.insert("reason".to_string(),    reason.to_json());
+    v.insert("count".to_string(),     1u64.to_json());
+    if let Some(alg) = algorithms::lookup(algorithm) {
+        v.insert("status".to_string(),     alg.status.name().to_json());
+        v.insert("references".to_string(), Json::Array(alg.references.iter().map(|r| r.to_json()).collect()));
+    }
+    violations.push(v);
+}
+
+
+/// Prints the policy violations found, separately from the other results, and
+/// returns whether any algorithms were denied.
+fn report_violations(hbs: &Handlebars, options: &ScanOptions) -> bool {
+    let violations = options.violations.borrow();
+    if violations.is_empty() {
+        return false;
+    }
+
+    if options.format == output::Format::Text {
+        println!("\nPolicy violations:\n--------------------------------------------------");
+    }
+
+    let mut denied = false;
+    for v in violations.iter() {
+        let deny = v.get("action").and_then(|a| a.as_string()) == Some("deny");
+        denied = denied || deny;
+
+        // Formats that are written once the scan is done need standard output
+        // to themselves.
+        if options.format.collects() {
+            let msg = hbs.render("violation", v).unwrap_or("error rendering template".to_string());
+            if deny {
+                error!("policy violation: {}", msg);
+            } else {
+                warn!("policy violation: {}", msg);
+            }
+        } else {
+            render(hbs, options, "violation", v);
+        }
+    }
+
+    denied
 }
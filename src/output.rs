@@ -0,0 +1,577 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use rustc_serialize::json::{Json, ToJson};
+
+use endian::Endianness;
+use patterns::{Category, Pattern};
+
+
+/// How results are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One line per result, using the Handlebars templates.
+    Text,
+
+    /// One JSON object per line.
+    Json,
+
+    /// Scripts that label each match in a disassembler.
+    IdaPython,
+    Ghidra,
+    Radare2,
+
+    /// A SARIF 2.1.0 log, for code scanning tools.
+    Sarif,
+}
+
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text),
+            "json"      => Some(Format::Json),
+            "idapython" => Some(Format::IdaPython),
+            "ghidra"    => Some(Format::Ghidra),
+            "r2"        => Some(Format::Radare2),
+            "sarif"     => Some(Format::Sarif),
+            _           => None,
+        }
+    }
+
+    /// Whether results are collected and written all at once when the scan is
+    /// finished, rather than printed as they're found.
+    pub fn collects(&self) -> bool {
+        match *self {
+            Format::Text | Format::Json => false,
+            _                           => true,
+        }
+    }
+}
+
+
+/// Formats the given data as a hex and ASCII dump, with each line prefixed by
+/// the address of its first byte.
+pub fn hexdump(data: &[u8], address: u64) -> String {
+    let mut ret = String::new();
+
+    for (i, line) in data.chunks(16).enumerate() {
+        write!(ret, "  {:08x}  ", address + (i * 16) as u64).unwrap();
+
+        for j in 0..16 {
+            match line.get(j) {
+                Some(b) => write!(ret, "{:02x} ", b).unwrap(),
+                None    => ret.push_str("   "),
+            }
+            if j == 7 {
+                ret.push(' ');
+            }
+        }
+
+        ret.push_str(" |");
+        for &b in line {
+            ret.push(if b >= 0x20 && b < 0x7f { b as char } else { '.' });
+        }
+        ret.push_str("|\n");
+    }
+
+    // No trailing newline, since this is printed with `println!`.
+    ret.pop();
+    ret
+}
+
+
+/// Returns what's known about a pattern, for listing it: its names, category
+/// and the size of its table.
+pub fn pattern_info(pattern: &Pattern) -> BTreeMap<String, Json> {
+    let width = pattern.bytes.element_width();
+    let size = pattern.bytes.as_byte_vec(Endianness::LittleEndian).len();
+
+    let mut info = BTreeMap::new();
+    info.insert("algorithm".to_string(),     pattern.algorithm.to_json());
+    info.insert("varname".to_string(),       pattern.varname.to_json());
+    info.insert("desc".to_string(),          pattern.desc.to_json());
+    info.insert("category".to_string(),      pattern.category.name().to_json());
+    info.insert("element_width".to_string(), width.to_json());
+    info.insert("elements".to_string(),      (size / width).to_json());
+    info.insert("size".to_string(),          size.to_json());
+    info
+}
+
+
+/// Returns the file name to use when extracting a match, made from the path
+/// it was found in, its address and the algorithm.
+pub fn extract_name(path: &str, address: u64, algorithm: &str) -> String {
+    let sanitize = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .collect()
+    };
+
+    format!("{}_0x{:08x}_{}.bin", sanitize(path.trim_left_matches('/')), address, sanitize(algorithm))
+}
+
+/// A match to label in a disassembler.
+struct Label {
+    address: String,
+    name: String,
+    width: u64,
+    count: u64,
+    comment: String,
+}
+
+
+/// Returns the string value of a field in a result, or an empty string.
+fn field<'a>(result: &'a BTreeMap<String, Json>, key: &str) -> &'a str {
+    result.get(key).and_then(|v| v.as_string()).unwrap_or("")
+}
+
+
+/// Turns collected results into labels.  Only signature matches are labelled,
+/// since they're the only results that we know the layout of.  Names come from
+/// the pattern's variable name (e.g. `crypto::RIJNDAEL_TE0` becomes
+/// `RIJNDAEL_TE0`), with a numeric suffix if the same table is found more than
+/// once.
+fn labels(results: &[BTreeMap<String, Json>]) -> Vec<Label> {
+    let mut ret = vec![];
+    let mut seen = BTreeMap::new();
+
+    for result in results {
+        let varname = field(result, "varname");
+        let width = result.get("element_width").and_then(|v| v.as_u64()).unwrap_or(0);
+        let size = result.get("size").and_then(|v| v.as_u64()).unwrap_or(0);
+        if varname.is_empty() || width == 0 {
+            continue;
+        }
+
+        let address = match field(result, "virtual_address") {
+            ""   => field(result, "address"),
+            addr => addr,
+        };
+
+        let base = varname.rsplit("::").next().unwrap_or(varname)
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .flat_map(|c| c.to_uppercase())
+            .collect::<String>();
+        let n = seen.entry(base.clone()).or_insert(0);
+        *n += 1;
+        let name = if *n == 1 { base } else { format!("{}_{}", base, n) };
+
+        ret.push(Label {
+            address: address.to_string(),
+            name:    name,
+            width:   width,
+            count:   size / width,
+            comment: format!("{} ({}) - {}", field(result, "algorithm"), field(result, "endian"), field(result, "desc")),
+        });
+    }
+
+    ret
+}
+
+
+/// Returns a script for the given format that labels each of the collected
+/// results.
+pub fn script(format: Format, results: &[BTreeMap<String, Json>]) -> String {
+    let mut ret = String::new();
+    let labels = labels(results);
+
+    match format {
+        Format::IdaPython => {
+            ret.push_str(IDAPYTHON_HEADER);
+            for l in labels {
+                writeln!(ret, "label({}, {}, {}, {}, {})",
+                         l.address, l.name.to_json(), l.width, l.count, l.comment.to_json()).unwrap();
+            }
+        },
+        Format::Ghidra => {
+            ret.push_str(GHIDRA_HEADER);
+            for l in labels {
+                writeln!(ret, "label({}, {}, {}, {}, {})",
+                         l.address, l.name.to_json(), l.width, l.count, l.comment.to_json()).unwrap();
+            }
+        },
+        Format::Radare2 => {
+            ret.push_str("fs crypto\n");
+            for l in labels {
+                // Quoting the command stops r2 from interpreting characters
+                // like ';' and '|' in the comment.
+                writeln!(ret, "f crypto.{} {} @ {}", l.name, l.width * l.count, l.address).unwrap();
+                writeln!(ret, "Cd {} {} @ {}", l.width, l.count, l.address).unwrap();
+                writeln!(ret, "\"CC {}\" @ {}", l.comment.replace('"', "'"), l.address).unwrap();
+            }
+        },
+        Format::Text | Format::Json | Format::Sarif => {},
+    }
+
+    ret
+}
+
+
+const IDAPYTHON_HEADER: &'static str = r#"# Generated by findcrypt - run with File > Script file...
+import idc
+import ida_bytes
+import ida_name
+
+CREATE = {1: idc.create_byte, 2: idc.create_word, 4: idc.create_dword, 8: idc.create_qword}
+
+def label(ea, name, width, count, comment):
+    ida_bytes.del_items(ea, ida_bytes.DELIT_SIMPLE, width * count)
+    CREATE[width](ea)
+    if count > 1:
+        idc.make_array(ea, count)
+    idc.set_name(ea, name, ida_name.SN_NOWARN | ida_name.SN_FORCE)
+    idc.set_cmt(ea, comment, 0)
+
+"#;
+
+
+const GHIDRA_HEADER: &'static str = r#"# Generated by findcrypt - run from the Script Manager.
+#@category Analysis
+from ghidra.program.model.data import ArrayDataType, ByteDataType, WordDataType, DWordDataType, QWordDataType
+from ghidra.program.model.symbol import SourceType
+
+TYPES = {1: ByteDataType.dataType, 2: WordDataType.dataType, 4: DWordDataType.dataType, 8: QWordDataType.dataType}
+
+def label(address, name, width, count, comment):
+    addr = toAddr(address)
+    data_type = TYPES[width]
+    if count > 1:
+        data_type = ArrayDataType(data_type, count, width)
+    clearListing(addr, addr.add(width * count - 1))
+    createData(addr, data_type)
+    createLabel(addr, name, True, SourceType.USER_DEFINED)
+    setEOLComment(addr, comment)
+
+"#;
+
+/// The SARIF levels that a result can have.
+pub const SARIF_LEVELS: &'static [&'static str] = &["error", "warning", "note", "none"];
+
+
+/// Returns the SARIF level for results for the given pattern.  `severities`
+/// maps lowercased algorithm names to levels; otherwise cryptographic
+/// algorithms are warnings and everything else is a note.
+fn sarif_level(pattern: &Pattern, severities: &BTreeMap<String, String>) -> String {
+    match severities.get(&pattern.algorithm.to_lowercase()) {
+        Some(level) => level.clone(),
+        None        => match pattern.category {
+            Category::Crypto => "warning".to_string(),
+            _                => "note".to_string(),
+        },
+    }
+}
+
+
+/// Percent-encodes a path for use in a URI, leaving only unreserved
+/// characters and slashes as they are.
+fn percent_encode(path: &str) -> String {
+    let mut ret = String::new();
+    for &b in path.as_bytes() {
+        match b {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' |
+            b'-' | b'.' | b'_' | b'~' | b'/' => ret.push(b as char),
+            _                                => ret.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    ret
+}
+
+
+/// Returns the URI for a path given on the command line: a `file` URI if it's
+/// absolute, or a relative reference otherwise.
+fn path_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let bytes = path.as_bytes();
+
+    if path.starts_with("//") {
+        // A UNC path, whose server becomes the URI's host.
+        format!("file:{}", percent_encode(&path))
+    } else if path.starts_with('/') {
+        format!("file://{}", percent_encode(&path))
+    } else if bytes.len() >= 3 && (bytes[0] as char).is_alphabetic() && &bytes[1..3] == b":/" {
+        format!("file:///{}{}", &path[..2], percent_encode(&path[2..]))
+    } else {
+        percent_encode(&path)
+    }
+}
+
+
+/// Returns the index in `artifacts` of the file with the given path, adding
+/// it (and the files that it came from) if necessary.  Members of containers
+/// (e.g. "fw.zip!/lib/libcrypto.so") are nested in the artifact for their
+/// container, and their URIs are relative to it.
+fn artifact_index(path: &str, artifacts: &mut Vec<Json>, indexes: &mut BTreeMap<String, usize>) -> usize {
+    let mut parent: Option<usize> = None;
+    let mut key = String::new();
+
+    for (i, part) in path.split("!/").enumerate() {
+        if i > 0 {
+            key.push_str("!/");
+        }
+        key.push_str(part);
+
+        let index = match indexes.get(&key) {
+            Some(&index) => index,
+            None         => {
+                let uri = if i == 0 { path_uri(part) } else { percent_encode(part) };
+
+                let mut location = BTreeMap::new();
+                location.insert("uri".to_string(), uri.to_json());
+
+                let mut artifact = BTreeMap::new();
+                artifact.insert("location".to_string(), Json::Object(location));
+                if let Some(p) = parent {
+                    artifact.insert("parentIndex".to_string(), p.to_json());
+                }
+
+                artifacts.push(Json::Object(artifact));
+                indexes.insert(key.clone(), artifacts.len() - 1);
+                artifacts.len() - 1
+            },
+        };
+        parent = Some(index);
+    }
+
+    // `split` always returns at least one part.
+    parent.unwrap()
+}
+
+
+/// Returns a SARIF log with a rule for each pattern and a result for each
+/// collected signature match.
+pub fn sarif(patterns: &[Pattern], results: &[BTreeMap<String, Json>], severities: &BTreeMap<String, String>) -> Json {
+    let text = |s: &str| {
+        let mut obj = BTreeMap::new();
+        obj.insert("text".to_string(), s.to_json());
+        Json::Object(obj)
+    };
+
+    // Rules are identified by the pattern's variable name, which is also how
+    // results refer to them.
+    let mut rules = vec![];
+    let mut indexes = BTreeMap::new();
+    for pat in patterns {
+        if indexes.contains_key(pat.varname) {
+            continue;
+        }
+        indexes.insert(pat.varname, (rules.len(), sarif_level(pat, severities)));
+
+        let mut config = BTreeMap::new();
+        config.insert("level".to_string(), sarif_level(pat, severities).to_json());
+
+        let mut props = BTreeMap::new();
+        props.insert("category".to_string(), pat.category.name().to_json());
+
+        let mut rule = BTreeMap::new();
+        rule.insert("id".to_string(),                   pat.varname.to_json());
+        rule.insert("name".to_string(),                 pat.algorithm.to_json());
+        rule.insert("shortDescription".to_string(),     text(pat.algorithm));
+        rule.insert("fullDescription".to_string(),      text(pat.desc));
+        rule.insert("help".to_string(),                 text(pat.desc));
+        rule.insert("defaultConfiguration".to_string(), Json::Object(config));
+        rule.insert("properties".to_string(),           Json::Object(props));
+        rules.push(Json::Object(rule));
+    }
+
+    let mut artifacts = vec![];
+    let mut artifact_indexes = BTreeMap::new();
+    let mut sarif_results = vec![];
+    for result in results {
+        let (index, level) = match indexes.get(field(result, "varname")) {
+            Some(&(i, ref l)) => (i, l.clone()),
+            None              => continue,
+        };
+
+        let file = artifact_index(field(result, "path"), &mut artifacts, &mut artifact_indexes);
+
+        let mut artifact = BTreeMap::new();
+        artifact.insert("uri".to_string(),   artifacts[file]["location"]["uri"].clone());
+        artifact.insert("index".to_string(), file.to_json());
+
+        let mut region = BTreeMap::new();
+        region.insert("byteOffset".to_string(), result.get("offset").cloned().unwrap_or(Json::Null));
+        region.insert("byteLength".to_string(), result.get("size").cloned().unwrap_or(Json::Null));
+
+        let mut physical = BTreeMap::new();
+        physical.insert("artifactLocation".to_string(), Json::Object(artifact));
+        physical.insert("region".to_string(),           Json::Object(region));
+
+        let mut location = BTreeMap::new();
+        location.insert("physicalLocation".to_string(), Json::Object(physical));
+
+        let mut props = BTreeMap::new();
+        for key in ["address", "virtual_address", "endian"].iter() {
+            if let Some(value) = result.get(*key) {
+                props.insert(key.to_string(), value.clone());
+            }
+        }
+
+        let message = format!("{} ({}) - {} at {}", field(result, "algorithm"), field(result, "endian"),
+                              field(result, "desc"), field(result, "address"));
+
+        let mut obj = BTreeMap::new();
+        obj.insert("ruleId".to_string(),     field(result, "varname").to_json());
+        obj.insert("ruleIndex".to_string(),  index.to_json());
+        obj.insert("level".to_string(),      level.to_json());
+        obj.insert("message".to_string(),    text(&message));
+        obj.insert("locations".to_string(),  Json::Array(vec![Json::Object(location)]));
+        obj.insert("properties".to_string(), Json::Object(props));
+        sarif_results.push(Json::Object(obj));
+    }
+
+    let mut driver = BTreeMap::new();
+    driver.insert("name".to_string(),    "findcrypt".to_json());
+    driver.insert("version".to_string(), env!("CARGO_PKG_VERSION").to_json());
+    driver.insert("rules".to_string(),   Json::Array(rules));
+
+    let mut tool = BTreeMap::new();
+    tool.insert("driver".to_string(), Json::Object(driver));
+
+    let mut run = BTreeMap::new();
+    run.insert("tool".to_string(),      Json::Object(tool));
+    run.insert("artifacts".to_string(), Json::Array(artifacts));
+    run.insert("results".to_string(),   Json::Array(sarif_results));
+
+    let mut log = BTreeMap::new();
+    log.insert("$schema".to_string(), "https://json.schemastore.org/sarif-2.1.0.json".to_json());
+    log.insert("version".to_string(), "2.1.0".to_json());
+    log.insert("runs".to_string(),    Json::Array(vec![Json::Object(run)]));
+    Json::Object(log)
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_hexdump() {
+    let dump = hexdump(b"0123456789abcdef\x00\x01AB", 0x1000);
+
+    assert_eq!(dump,
+               "  00001000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|\n\
+                \x20 00001010  00 01 41 42                                       |..AB|");
+}
+
+#[test]
+fn test_extract_name() {
+    assert_eq!(extract_name("/tmp/fw.zip!/lib/libcrypto.so", 0x1234, "Rijndael"),
+               "tmp_fw.zip__lib_libcrypto.so_0x00001234_Rijndael.bin");
+}
+
+#[test]
+fn test_script_labels() {
+    let mut result = BTreeMap::new();
+    result.insert("address".to_string(),       "0x00000040".to_json());
+    result.insert("algorithm".to_string(),     "Rijndael".to_json());
+    result.insert("endian".to_string(),        "LE".to_json());
+    result.insert("desc".to_string(),          "crypto::RIJNDAEL_TE0".to_json());
+    result.insert("varname".to_string(),       "crypto::RIJNDAEL_TE0".to_json());
+    result.insert("element_width".to_string(), 4.to_json());
+    result.insert("size".to_string(),          1024.to_json());
+
+    let mut mapped = result.clone();
+    mapped.insert("virtual_address".to_string(), "0x00401040".to_json());
+
+    let script = script(Format::Radare2, &[result, mapped]);
+    let lines = script.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines[1], "f crypto.RIJNDAEL_TE0 1024 @ 0x00000040");
+    assert_eq!(lines[2], "Cd 4 256 @ 0x00000040");
+    assert_eq!(lines[3], "\"CC Rijndael (LE) - crypto::RIJNDAEL_TE0\" @ 0x00000040");
+    assert_eq!(lines[4], "f crypto.RIJNDAEL_TE0_2 1024 @ 0x00401040");
+}
+
+#[cfg(test)]
+static TEST_TABLE: ::endian::EndianWrapper<'static, u32> = ::endian::EndianWrapper(&[0x01020304]);
+
+#[test]
+fn test_sarif() {
+    let patterns = vec![
+        Pattern {
+            algorithm: "MD5",
+            desc:      "hash::MD5_CONSTS",
+            bytes:     &TEST_TABLE,
+            varname:   "hash::MD5_CONSTS",
+            category:  Category::Crypto,
+        },
+    ];
+
+    let mut result = BTreeMap::new();
+    result.insert("path".to_string(),    "fw.bin".to_json());
+    result.insert("varname".to_string(), "hash::MD5_CONSTS".to_json());
+    result.insert("offset".to_string(),  16usize.to_json());
+    result.insert("size".to_string(),    4usize.to_json());
+
+    let mut severities = BTreeMap::new();
+    severities.insert("md5".to_string(), "error".to_string());
+
+    let log = sarif(&patterns, &[result], &severities);
+    let run = &log["runs"][0];
+
+    assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "hash::MD5_CONSTS".to_json());
+    assert_eq!(run["tool"]["driver"]["rules"][0]["defaultConfiguration"]["level"], "error".to_json());
+    assert_eq!(run["results"][0]["ruleIndex"], 0usize.to_json());
+    assert_eq!(run["results"][0]["level"], "error".to_json());
+    assert_eq!(run["results"][0]["locations"][0]["physicalLocation"]["region"]["byteOffset"], 16usize.to_json());
+    assert_eq!(run["results"][0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "fw.bin".to_json());
+}
+
+#[test]
+fn test_sarif_artifacts() {
+    let patterns = vec![
+        Pattern {
+            algorithm: "MD5",
+            desc:      "hash::MD5_CONSTS",
+            bytes:     &TEST_TABLE,
+            varname:   "hash::MD5_CONSTS",
+            category:  Category::Crypto,
+        },
+    ];
+    let result = |path: &str| {
+        let mut result = BTreeMap::new();
+        result.insert("path".to_string(),    path.to_json());
+        result.insert("varname".to_string(), "hash::MD5_CONSTS".to_json());
+        result
+    };
+
+    let results = vec![result("my fw/fw.zip!/lib/libcrypto.so"), result("my fw/fw.zip!/lib/libssl.so"),
+                       result("my fw/fw.zip")];
+    let log = sarif(&patterns, &results, &BTreeMap::new());
+    let run = &log["runs"][0];
+
+    let artifacts = run["artifacts"].as_array().unwrap();
+    assert_eq!(artifacts.len(), 3);
+    assert_eq!(artifacts[0]["location"]["uri"], "my%20fw/fw.zip".to_json());
+    assert_eq!(artifacts[1]["location"]["uri"], "lib/libcrypto.so".to_json());
+    assert_eq!(artifacts[1]["parentIndex"], 0usize.to_json());
+    assert_eq!(artifacts[2]["parentIndex"], 0usize.to_json());
+
+    let index = |i: usize| run["results"][i]["locations"][0]["physicalLocation"]["artifactLocation"]["index"].clone();
+    assert_eq!((index(0), index(1), index(2)), (1usize.to_json(), 2usize.to_json(), 0usize.to_json()));
+}
+
+#[test]
+fn test_path_uri() {
+    assert_eq!(path_uri("fw/100% done.bin"), "fw/100%25%20done.bin");
+    assert_eq!(path_uri("pid 1:/lib/libc.so"), "pid%201%3A/lib/libc.so");
+    assert_eq!(path_uri("/tmp/a b.bin"), "file:///tmp/a%20b.bin");
+    assert_eq!(path_uri("C:\\fw\\a.bin"), "file:///C:/fw/a.bin");
+    assert_eq!(path_uri("\\\\server\\share\\a.bin"), "file://server/share/a.bin");
+}
+
+#[test]
+fn test_pattern_info() {
+    let pattern = Pattern {
+        algorithm: "Test",
+        desc:      "A test table",
+        bytes:     &TEST_TABLE,
+        varname:   "test::TABLE",
+        category:  Category::NonCrypto,
+    };
+
+    let info = pattern_info(&pattern);
+    assert_eq!(info["varname"], "test::TABLE".to_json());
+    assert_eq!(info["category"], "non-crypto".to_json());
+    assert_eq!((&info["element_width"], &info["elements"], &info["size"]),
+               (&4usize.to_json(), &1usize.to_json(), &4usize.to_json()));
+}
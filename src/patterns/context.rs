@@ -0,0 +1,159 @@
+use byteorder::ByteOrder;
+
+use super::super::endian::{AsBytes, EndianWrapper};
+
+// These aren't cryptographic, but knowing that a crypto table sits next to
+// (de)compression or encoding code helps to make sense of it.
+
+
+/// An entry in one of zlib's decoding tables - the `code` struct from
+/// inftrees.h, whose fields are `op`, `bits` and `val`.
+#[derive(Debug)]
+pub struct ZlibCode(pub u8, pub u8, pub u16);
+
+impl AsBytes for ZlibCode {
+    fn as_bytes<O: ByteOrder>(&self, buf: &mut [u8]) -> usize {
+        buf[0] = self.0;
+        buf[1] = self.1;
+        O::write_u16(&mut buf[2..4], self.2);
+        4
+    }
+}
+
+
+// DEFLATE - base values for length codes 257..285.  zlib and puff store these
+// as 16-bit values, miniz and others as 32-bit values.
+pub static DEFLATE_LENGTH_BASE: EndianWrapper<'static, u16> = EndianWrapper(&[
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+]);
+
+pub static DEFLATE_LENGTH_BASE_32: EndianWrapper<'static, u32> = EndianWrapper(&[
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+]);
+
+// DEFLATE - extra bits for length codes.  zlib adds 16 to each of these; the
+// entries after the ones below differ between zlib versions.
+pub static DEFLATE_LENGTH_EXTRA: EndianWrapper<'static, u8> = EndianWrapper(&[
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+]);
+
+pub static ZLIB_LENGTH_EXTRA: EndianWrapper<'static, u16> = EndianWrapper(&[
+    16, 16, 16, 16, 16, 16, 16, 16, 17, 17, 17, 17, 18, 18, 18, 18,
+    19, 19, 19, 19, 20, 20, 20, 20, 21, 21, 21, 21, 16,
+]);
+
+// DEFLATE - base values for distance codes 0..29.
+pub static DEFLATE_DIST_BASE: EndianWrapper<'static, u16> = EndianWrapper(&[
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+]);
+
+pub static DEFLATE_DIST_BASE_32: EndianWrapper<'static, u32> = EndianWrapper(&[
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+]);
+
+// DEFLATE - extra bits for distance codes, again with zlib's offset of 16.
+pub static ZLIB_DIST_EXTRA: EndianWrapper<'static, u16> = EndianWrapper(&[
+    16, 16, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22,
+    23, 23, 24, 24, 25, 25, 26, 26, 27, 27, 28, 28, 29, 29,
+]);
+
+// DEFLATE - the order in which code length code lengths are sent.
+pub static DEFLATE_CODELEN_ORDER: EndianWrapper<'static, u8> = EndianWrapper(&[
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+]);
+
+pub static DEFLATE_CODELEN_ORDER_16: EndianWrapper<'static, u16> = EndianWrapper(&[
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+]);
+
+// zlib - the fixed Huffman decoding tables from inffixed.h.  Only the start of
+// `lenfix` is included, since the entries for the invalid length codes 286 and
+// 287 take their `op` from the end of `lext`, which changes with each version.
+pub static ZLIB_LENFIX: EndianWrapper<'static, ZlibCode> = EndianWrapper(&[
+    ZlibCode(96,7,0), ZlibCode(0,8,80), ZlibCode(0,8,16), ZlibCode(20,8,115), ZlibCode(18,7,31),
+    ZlibCode(0,8,112), ZlibCode(0,8,48), ZlibCode(0,9,192), ZlibCode(16,7,10), ZlibCode(0,8,96),
+    ZlibCode(0,8,32), ZlibCode(0,9,160), ZlibCode(0,8,0), ZlibCode(0,8,128), ZlibCode(0,8,64),
+    ZlibCode(0,9,224), ZlibCode(16,7,6), ZlibCode(0,8,88), ZlibCode(0,8,24), ZlibCode(0,9,144),
+    ZlibCode(19,7,59), ZlibCode(0,8,120), ZlibCode(0,8,56), ZlibCode(0,9,208), ZlibCode(17,7,17),
+    ZlibCode(0,8,104), ZlibCode(0,8,40), ZlibCode(0,9,176), ZlibCode(0,8,8), ZlibCode(0,8,136),
+    ZlibCode(0,8,72), ZlibCode(0,9,240), ZlibCode(16,7,4), ZlibCode(0,8,84), ZlibCode(0,8,20),
+    ZlibCode(21,8,227), ZlibCode(19,7,43), ZlibCode(0,8,116), ZlibCode(0,8,52), ZlibCode(0,9,200),
+    ZlibCode(17,7,13), ZlibCode(0,8,100), ZlibCode(0,8,36), ZlibCode(0,9,168), ZlibCode(0,8,4),
+    ZlibCode(0,8,132), ZlibCode(0,8,68), ZlibCode(0,9,232), ZlibCode(16,7,8), ZlibCode(0,8,92),
+    ZlibCode(0,8,28), ZlibCode(0,9,152), ZlibCode(20,7,83), ZlibCode(0,8,124), ZlibCode(0,8,60),
+    ZlibCode(0,9,216), ZlibCode(18,7,23), ZlibCode(0,8,108), ZlibCode(0,8,44), ZlibCode(0,9,184),
+    ZlibCode(0,8,12), ZlibCode(0,8,140), ZlibCode(0,8,76), ZlibCode(0,9,248), ZlibCode(16,7,3),
+    ZlibCode(0,8,82), ZlibCode(0,8,18), ZlibCode(21,8,163), ZlibCode(19,7,35), ZlibCode(0,8,114),
+    ZlibCode(0,8,50), ZlibCode(0,9,196), ZlibCode(17,7,11), ZlibCode(0,8,98), ZlibCode(0,8,34),
+    ZlibCode(0,9,164), ZlibCode(0,8,2), ZlibCode(0,8,130), ZlibCode(0,8,66), ZlibCode(0,9,228),
+    ZlibCode(16,7,7), ZlibCode(0,8,90), ZlibCode(0,8,26), ZlibCode(0,9,148), ZlibCode(20,7,67),
+    ZlibCode(0,8,122), ZlibCode(0,8,58), ZlibCode(0,9,212), ZlibCode(18,7,19), ZlibCode(0,8,106),
+    ZlibCode(0,8,42), ZlibCode(0,9,180), ZlibCode(0,8,10), ZlibCode(0,8,138), ZlibCode(0,8,74),
+    ZlibCode(0,9,244), ZlibCode(16,7,5), ZlibCode(0,8,86), ZlibCode(0,8,22),
+]);
+
+pub static ZLIB_DISTFIX: EndianWrapper<'static, ZlibCode> = EndianWrapper(&[
+    ZlibCode(16,5,1), ZlibCode(23,5,257), ZlibCode(19,5,17), ZlibCode(27,5,4097), ZlibCode(17,5,5),
+    ZlibCode(25,5,1025), ZlibCode(21,5,65), ZlibCode(29,5,16385), ZlibCode(16,5,3), ZlibCode(24,5,513),
+    ZlibCode(20,5,33), ZlibCode(28,5,8193), ZlibCode(18,5,9), ZlibCode(26,5,2049), ZlibCode(22,5,129),
+    ZlibCode(64,5,0), ZlibCode(16,5,2), ZlibCode(23,5,385), ZlibCode(19,5,25), ZlibCode(27,5,6145),
+    ZlibCode(17,5,7), ZlibCode(25,5,1537), ZlibCode(21,5,97), ZlibCode(29,5,24577), ZlibCode(16,5,4),
+    ZlibCode(24,5,769), ZlibCode(20,5,49), ZlibCode(28,5,12289), ZlibCode(18,5,13), ZlibCode(26,5,3073),
+    ZlibCode(22,5,193), ZlibCode(64,5,0),
+]);
+
+
+// LZMA - the state transition table for literals, from the LZMA SDK.
+pub static LZMA_LITERAL_NEXT_STATES: EndianWrapper<'static, u8> = EndianWrapper(&[
+    0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 4, 5,
+]);
+
+// The .xz stream header magic.
+pub static XZ_MAGIC: EndianWrapper<'static, u8> = EndianWrapper(&[
+    0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00,
+]);
+
+
+// bzip2 - the start of the block randomisation table (`BZ2_rNums`).
+pub static BZIP2_RNUMS: EndianWrapper<'static, u32> = EndianWrapper(&[
+    619, 720, 127, 481, 931, 816, 813, 233,
+    566, 247, 985, 724, 205, 454, 863, 491,
+]);
+
+
+// Encoding alphabets.  Custom (shuffled) alphabets are detected separately,
+// in `alphabet.rs`.
+pub static BASE64_ALPHABET: EndianWrapper<'static, u8> = EndianWrapper(
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/");
+
+pub static BASE64URL_ALPHABET: EndianWrapper<'static, u8> = EndianWrapper(
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_");
+
+pub static BASE32_ALPHABET: EndianWrapper<'static, u8> = EndianWrapper(
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567");
+
+pub static BASE32HEX_ALPHABET: EndianWrapper<'static, u8> = EndianWrapper(
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUV");
+
+pub static BASE58_ALPHABET: EndianWrapper<'static, u8> = EndianWrapper(
+    b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz");
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_zlib_code_layout() {
+    use endian::{AsByteVec, Endianness};
+
+    // Only `val` depends on the byte order.
+    let code = EndianWrapper(&[ZlibCode(29, 5, 0x4001)]);
+    assert_eq!(code.as_byte_vec(Endianness::LittleEndian), vec![29, 5, 0x01, 0x40]);
+    assert_eq!(code.as_byte_vec(Endianness::BigEndian), vec![29, 5, 0x40, 0x01]);
+    assert_eq!(code.element_width(), 4);
+}
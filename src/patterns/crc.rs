@@ -0,0 +1,295 @@
+use super::super::endian::{AsByteVec, Endianness, EndianWrapper};
+
+
+/// A CRC model, described using the usual Rocksoft parameters.  Rather than
+/// storing the lookup table for each model, we generate it from the
+/// polynomial, width and reflection parameters when building the automaton.
+///
+/// Note that the table only depends on `width`, `poly` and `reflect` - the
+/// `init`, `xorout` and `check` values are only used to verify the model (see
+/// `checksum`).  Models that share a table (e.g. CRC-16/MODBUS and
+/// CRC-16/ARC) are indistinguishable by their tables alone.
+///
+/// The table is indexed by a whole byte, so `width` must be at least 8.
+#[derive(Debug)]
+pub struct CrcModel {
+    pub name: &'static str,
+    pub width: u32,
+    pub poly: u64,
+    pub init: u64,
+    pub reflect: bool,
+    pub xorout: u64,
+
+    /// The checksum of the ASCII string "123456789".
+    pub check: u64,
+}
+
+
+impl CrcModel {
+    fn mask(&self) -> u64 {
+        if self.width >= 64 {
+            !0
+        } else {
+            (1 << self.width) - 1
+        }
+    }
+
+    /// Generates the 256-entry lookup table for this model, as used by the
+    /// common byte-at-a-time implementations.
+    pub fn table(&self) -> Vec<u64> {
+        assert!(self.width >= 8, "{}: CRCs narrower than 8 bits aren't supported", self.name);
+
+        let mask = self.mask();
+        let mut table = Vec::with_capacity(256);
+
+        if self.reflect {
+            let poly = reflect(self.poly, self.width);
+
+            for i in 0..256u64 {
+                let mut crc = i;
+                for _ in 0..8 {
+                    crc = if crc & 1 == 1 {
+                        (crc >> 1) ^ poly
+                    } else {
+                        crc >> 1
+                    };
+                }
+                table.push(crc);
+            }
+        } else {
+            let top = 1 << (self.width - 1);
+
+            for i in 0..256u64 {
+                let mut crc = i << (self.width - 8);
+                for _ in 0..8 {
+                    crc = if crc & top != 0 {
+                        (crc << 1) ^ self.poly
+                    } else {
+                        crc << 1
+                    };
+                }
+                table.push(crc & mask);
+            }
+        }
+
+        table
+    }
+
+    /// Calculates the checksum of the given data using this model.
+    pub fn checksum(&self, data: &[u8]) -> u64 {
+        let mask = self.mask();
+        let table = self.table();
+
+        let crc = if self.reflect {
+            let mut crc = reflect(self.init, self.width);
+            for b in data {
+                crc = table[((crc ^ *b as u64) & 0xFF) as usize] ^ (crc >> 8);
+            }
+            crc
+        } else {
+            let mut crc = self.init;
+            for b in data {
+                let idx = ((crc >> (self.width - 8)) ^ *b as u64) & 0xFF;
+                crc = (table[idx as usize] ^ (crc << 8)) & mask;
+            }
+            crc
+        };
+
+        (crc ^ self.xorout) & mask
+    }
+}
+
+
+impl AsByteVec for CrcModel {
+    fn as_byte_vec(&self, e: Endianness) -> Vec<u8> {
+        let table = self.table();
+
+        // Implementations store the table using the smallest integer type that
+        // holds the CRC, so do the same here.
+        match self.width {
+            0...8 => table.iter().map(|&x| x as u8).collect(),
+            9...16 => {
+                let v = table.iter().map(|&x| x as u16).collect::<Vec<_>>();
+                EndianWrapper(&v).as_byte_vec(e)
+            },
+            17...32 => {
+                let v = table.iter().map(|&x| x as u32).collect::<Vec<_>>();
+                EndianWrapper(&v).as_byte_vec(e)
+            },
+            _ => EndianWrapper(&table).as_byte_vec(e),
+        }
+    }
+
+    fn element_width(&self) -> usize {
+        match self.width {
+            0...8   => 1,
+            9...16  => 2,
+            17...32 => 4,
+            _       => 8,
+        }
+    }
+}
+
+
+/// Reverses the lowest `width` bits of `val`.
+fn reflect(val: u64, width: u32) -> u64 {
+    let mut ret = 0;
+    for i in 0..width {
+        if val & (1 << i) != 0 {
+            ret |= 1 << (width - 1 - i);
+        }
+    }
+    ret
+}
+
+
+// Parameters are from the "Catalogue of parametrised CRC algorithms":
+//   http://reveng.sourceforge.net/crc-catalogue/
+
+pub static CRC16_CCITT: CrcModel = CrcModel {
+    name:    "CRC-16/CCITT-FALSE",
+    width:   16,
+    poly:    0x1021,
+    init:    0xFFFF,
+    reflect: false,
+    xorout:  0x0000,
+    check:   0x29B1,
+};
+
+pub static CRC16_KERMIT: CrcModel = CrcModel {
+    name:    "CRC-16/KERMIT",
+    width:   16,
+    poly:    0x1021,
+    init:    0x0000,
+    reflect: true,
+    xorout:  0x0000,
+    check:   0x2189,
+};
+
+pub static CRC16_MODBUS: CrcModel = CrcModel {
+    name:    "CRC-16/MODBUS",
+    width:   16,
+    poly:    0x8005,
+    init:    0xFFFF,
+    reflect: true,
+    xorout:  0x0000,
+    check:   0x4B37,
+};
+
+// The catalogue calls this CRC-32, but it has always been reported as CRC32.
+pub static CRC32: CrcModel = CrcModel {
+    name:    "CRC32",
+    width:   32,
+    poly:    0x04C11DB7,
+    init:    0xFFFFFFFF,
+    reflect: true,
+    xorout:  0xFFFFFFFF,
+    check:   0xCBF43926,
+};
+
+pub static CRC32_BZIP2: CrcModel = CrcModel {
+    name:    "CRC-32/BZIP2",
+    width:   32,
+    poly:    0x04C11DB7,
+    init:    0xFFFFFFFF,
+    reflect: false,
+    xorout:  0xFFFFFFFF,
+    check:   0xFC891918,
+};
+
+pub static CRC32C: CrcModel = CrcModel {
+    name:    "CRC-32C",
+    width:   32,
+    poly:    0x1EDC6F41,
+    init:    0xFFFFFFFF,
+    reflect: true,
+    xorout:  0xFFFFFFFF,
+    check:   0xE3069283,
+};
+
+pub static CRC64_ECMA: CrcModel = CrcModel {
+    name:    "CRC-64/ECMA-182",
+    width:   64,
+    poly:    0x42F0E1EBA9EA3693,
+    init:    0x0000000000000000,
+    reflect: false,
+    xorout:  0x0000000000000000,
+    check:   0x6C40DF5F0B497347,
+};
+
+pub static CRC64_XZ: CrcModel = CrcModel {
+    name:    "CRC-64/XZ",
+    width:   64,
+    poly:    0x42F0E1EBA9EA3693,
+    init:    0xFFFFFFFFFFFFFFFF,
+    reflect: true,
+    xorout:  0xFFFFFFFFFFFFFFFF,
+    check:   0x995DC9BBDF1939FA,
+};
+
+
+// Adler-32 has no lookup table, so all we can look for is its modulus.  That's
+// too short to search for on its own, but compilers turn the reduction into a
+// multiplication by its reciprocal (i.e. `(x * 0x80078071) >> 47`), and the
+// two are only reported when they're found together (see
+// `scanner::PAIR_DISTANCE`).
+pub static ADLER32_BASE: EndianWrapper<'static, u32> = EndianWrapper(&[0x0000fff1]);
+pub static ADLER32_RECIPROCAL: EndianWrapper<'static, u32> = EndianWrapper(&[0x80078071]);
+
+
+/// All known CRC models.
+pub static MODELS: &'static [&'static CrcModel] = &[
+    &CRC16_CCITT,
+    &CRC16_KERMIT,
+    &CRC16_MODBUS,
+    &CRC32,
+    &CRC32_BZIP2,
+    &CRC32C,
+    &CRC64_ECMA,
+    &CRC64_XZ,
+];
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_crc_check_values() {
+    for model in MODELS {
+        assert_eq!(model.checksum(b"123456789"), model.check, "{}", model.name);
+    }
+}
+
+#[test]
+fn test_crc32_table() {
+    let table = CRC32.table();
+
+    assert_eq!(table.len(), 256);
+    assert_eq!(&table[0..4], &[0x00000000, 0x77073096, 0xee0e612c, 0x990951ba]);
+    assert_eq!(table[255], 0x2d02ef8d);
+}
+
+#[test]
+fn test_crc8() {
+    let smbus = CrcModel {
+        name:    "CRC-8/SMBUS",
+        width:   8,
+        poly:    0x07,
+        init:    0x00,
+        reflect: false,
+        xorout:  0x00,
+        check:   0xF4,
+    };
+
+    assert_eq!(smbus.checksum(b"123456789"), smbus.check);
+    assert_eq!(smbus.element_width(), 1);
+    assert_eq!(&smbus.as_byte_vec(Endianness::LittleEndian)[..4], &[0x00, 0x07, 0x0E, 0x09]);
+}
+
+#[test]
+fn test_adler32_reciprocal() {
+    let base = ADLER32_BASE.0[0] as u64;
+    let reciprocal = ADLER32_RECIPROCAL.0[0] as u64;
+
+    for &x in &[0u64, 65520, 65521, 0x12345678, 0xFFFFFFFF] {
+        assert_eq!((x * reciprocal) >> 47, x / base);
+    }
+}
@@ -1,13 +1,54 @@
 use super::endian::AsByteVec;
 
+use std::ascii::AsciiExt;
 use std::clone::Clone;
 use std::cmp::{PartialEq, Eq};
 use std::hash::{Hash, Hasher};
 use std::fmt;
 
+mod context;
+pub mod crc;
 mod crypto;
 mod hash;
+mod noncrypto;
 mod other;
+mod pqc;
+pub mod selftest;
+
+
+/// The broad category that a pattern belongs to.  Only `Crypto` patterns are
+/// searched for by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Cryptographic algorithms, hashes, checksums and related constants.
+    Crypto,
+
+    /// Non-cryptographic hash functions and PRNGs.
+    NonCrypto,
+
+    /// Compression and encoding tables, which give context to other findings.
+    Context,
+}
+
+
+impl Category {
+    pub fn from_name(name: &str) -> Option<Category> {
+        match name {
+            "crypto"     => Some(Category::Crypto),
+            "non-crypto" => Some(Category::NonCrypto),
+            "context"    => Some(Category::Context),
+            _            => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Category::Crypto    => "crypto",
+            Category::NonCrypto => "non-crypto",
+            Category::Context   => "context",
+        }
+    }
+}
 
 
 pub struct Pattern {
@@ -15,6 +56,36 @@ pub struct Pattern {
     pub desc: &'static str,
     pub bytes: &'static AsByteVec,
     pub varname: &'static str,
+    pub category: Category,
+}
+
+
+impl Pattern {
+    /// Returns whether the given selector picks out this pattern.  Selectors
+    /// are algorithm names, algorithm families (e.g. "AES" or "SHA-2"),
+    /// variable names or categories, and can use `*` and `?` as wildcards.
+    /// Case is ignored.
+    pub fn is_selected_by(&self, selector: &str) -> bool {
+        let family = ::algorithms::lookup(self.algorithm).map(|a| a.family);
+
+        [Some(self.algorithm), family, Some(self.varname), Some(self.category.name())]
+            .iter()
+            .filter_map(|n| *n)
+            .any(|n| glob_match(selector.as_bytes(), n.as_bytes()))
+    }
+}
+
+
+/// Matches a glob, where `*` matches any number of characters and `?` matches
+/// one, without regard to case.
+fn glob_match(glob: &[u8], text: &[u8]) -> bool {
+    match (glob.first(), text.first()) {
+        (None, None)           => true,
+        (Some(&b'*'), _)       => glob_match(&glob[1..], text) || (!text.is_empty() && glob_match(glob, &text[1..])),
+        (Some(&b'?'), Some(_)) => glob_match(&glob[1..], &text[1..]),
+        (Some(g), Some(t))     => g.eq_ignore_ascii_case(t) && glob_match(&glob[1..], &text[1..]),
+        _                      => false,
+    }
 }
 
 
@@ -35,6 +106,7 @@ impl Clone for Pattern {
             desc:      self.desc,
             bytes:     self.bytes,
             varname:   self.varname,
+            category:  self.category,
         }
     }
 }
@@ -55,6 +127,7 @@ impl fmt::Debug for Pattern {
             .field("algorithm", &self.algorithm)
             .field("desc",      &self.desc)
             .field("varname",   &self.varname)
+            .field("category",  &self.category)
             .finish()
     }
 }
@@ -62,18 +135,27 @@ impl fmt::Debug for Pattern {
 
 pub fn get_patterns() -> Vec<Pattern> {
     macro_rules! mkpat {
-        ($name:expr, $var:expr) => {
-            mkpat!($name, $var, stringify!($var))
+        ($cat:ident; $name:expr, $var:expr) => {
+            mkpat!($cat; $name, $var, stringify!($var))
         };
 
-        ($name:expr, $var:expr, $desc:expr) => {
+        ($cat:ident; $name:expr, $var:expr, $desc:expr) => {
             Pattern {
                 algorithm:  $name,
                 desc:       $desc,
                 bytes:      & $var as &AsByteVec,
                 varname:    stringify!($var),
+                category:   Category::$cat,
             }
         };
+
+        ($name:expr, $var:expr) => {
+            mkpat!(Crypto; $name, $var)
+        };
+
+        ($name:expr, $var:expr, $desc:expr) => {
+            mkpat!(Crypto; $name, $var, $desc)
+        };
     };
 
     vec![
@@ -81,9 +163,18 @@ pub fn get_patterns() -> Vec<Pattern> {
         mkpat!("SHA-256",           hash::SHA256_CONSTS),
         mkpat!("SHA-512",           hash::SHA512_CONSTS),
         mkpat!("MD5",               hash::MD5_CONSTS),
-        mkpat!("CRC32",             hash::CRC32_CONSTS),
         mkpat!("Keccak",            hash::KECCAK_CONSTS),
 
+        // Checksums
+        mkpat!(crc::CRC16_CCITT.name,  crc::CRC16_CCITT),
+        mkpat!(crc::CRC16_KERMIT.name, crc::CRC16_KERMIT),
+        mkpat!(crc::CRC16_MODBUS.name, crc::CRC16_MODBUS),
+        mkpat!(crc::CRC32.name,        crc::CRC32),
+        mkpat!(crc::CRC32_BZIP2.name,  crc::CRC32_BZIP2),
+        mkpat!(crc::CRC32C.name,       crc::CRC32C),
+        mkpat!(crc::CRC64_ECMA.name,   crc::CRC64_ECMA),
+        mkpat!(crc::CRC64_XZ.name,     crc::CRC64_XZ),
+
         // Crypto algorithms
         mkpat!("Blowfish",          crypto::BLOWFISH_P_PERM),
         mkpat!("Blowfish",          crypto::BLOWFISH_S_PERM_0),
@@ -114,6 +205,33 @@ pub fn get_patterns() -> Vec<Pattern> {
         mkpat!("Rijndael",          crypto::RIJNDAEL_TD2),
         mkpat!("Rijndael",          crypto::RIJNDAEL_TD3),
         mkpat!("Rijndael",          crypto::RIJNDAEL_TD4),
+        mkpat!("Rijndael",          crypto::RIJNDAEL_SBOX),
+        mkpat!("Rijndael",          crypto::RIJNDAEL_INV_SBOX),
+
+        // Post-quantum algorithms
+        mkpat!("ML-KEM/Kyber",      pqc::KYBER_ZETAS),
+        mkpat!("ML-KEM/Kyber",      pqc::KYBER_ZETAS_UNSIGNED),
+        mkpat!("ML-KEM/Kyber",      pqc::MLKEM_ZETAS),
+        mkpat!("ML-KEM/Kyber",      pqc::MLKEM_512_OID),
+        mkpat!("ML-KEM/Kyber",      pqc::MLKEM_768_OID),
+        mkpat!("ML-KEM/Kyber",      pqc::MLKEM_1024_OID),
+        mkpat!("ML-DSA/Dilithium",  pqc::DILITHIUM_ZETAS),
+        mkpat!("ML-DSA/Dilithium",  pqc::DILITHIUM_ZETAS_UNSIGNED),
+        mkpat!("ML-DSA/Dilithium",  pqc::MLDSA_ZETAS),
+        mkpat!("ML-DSA/Dilithium",  pqc::MLDSA_44_OID),
+        mkpat!("ML-DSA/Dilithium",  pqc::MLDSA_65_OID),
+        mkpat!("ML-DSA/Dilithium",  pqc::MLDSA_87_OID),
+        mkpat!("Falcon",            pqc::FALCON_GMB),
+        mkpat!("Falcon",            pqc::FALCON_IGMB),
+        mkpat!("Falcon",            pqc::FALCON_FPR_GM_TAB),
+        mkpat!("SLH-DSA/SPHINCS+",  pqc::SLHDSA_SHAKE_128S_OID),
+        mkpat!("SLH-DSA/SPHINCS+",  pqc::SLHDSA_SHAKE_128F_OID),
+        mkpat!("SLH-DSA/SPHINCS+",  pqc::SLHDSA_SHAKE_192S_OID),
+        mkpat!("SLH-DSA/SPHINCS+",  pqc::SLHDSA_SHAKE_192F_OID),
+        mkpat!("SLH-DSA/SPHINCS+",  pqc::SLHDSA_SHAKE_256S_OID),
+        mkpat!("SLH-DSA/SPHINCS+",  pqc::SLHDSA_SHAKE_256F_OID),
+        mkpat!("SLH-DSA/SPHINCS+",  pqc::SPHINCS_SHAKE_NAME),
+        mkpat!("SLH-DSA/SPHINCS+",  pqc::SLHDSA_SHAKE_NAME),
 
         // Other things
         mkpat!("PKCS MD2",          other::PKCS_DIGEST_MD2),
@@ -123,5 +241,94 @@ pub fn get_patterns() -> Vec<Pattern> {
         mkpat!("PKCS SHA256",       other::PKCS_DIGEST_SHA256),
         mkpat!("PKCS SHA384",       other::PKCS_DIGEST_SHA384),
         mkpat!("PKCS SHA512",       other::PKCS_DIGEST_SHA512),
+        mkpat!("DH-1024",           other::DH_MODP_1024),
+        mkpat!("DH-1536",           other::DH_MODP_1536),
+        mkpat!("DH-2048",           other::DH_MODP_2048),
+
+        // Non-cryptographic hashes and PRNGs
+        mkpat!(NonCrypto; "MurmurHash3",       noncrypto::MURMUR3_32_C1),
+        mkpat!(NonCrypto; "MurmurHash3",       noncrypto::MURMUR3_32_C2),
+        mkpat!(NonCrypto; "MurmurHash3",       noncrypto::MURMUR3_FMIX32_1),
+        mkpat!(NonCrypto; "MurmurHash3",       noncrypto::MURMUR3_FMIX32_2),
+        mkpat!(NonCrypto; "MurmurHash3",       noncrypto::MURMUR3_128_C1),
+        mkpat!(NonCrypto; "MurmurHash3",       noncrypto::MURMUR3_128_C2),
+        mkpat!(NonCrypto; "MurmurHash3",       noncrypto::MURMUR3_FMIX64_1),
+        mkpat!(NonCrypto; "MurmurHash3",       noncrypto::MURMUR3_FMIX64_2),
+        mkpat!(NonCrypto; "MurmurHash2",       noncrypto::MURMUR2_M),
+        mkpat!(NonCrypto; "MurmurHash2",       noncrypto::MURMUR64A_M),
+        mkpat!(NonCrypto; "FNV-1/FNV-1a",      noncrypto::FNV32_OFFSET),
+        mkpat!(NonCrypto; "FNV-1/FNV-1a",      noncrypto::FNV32_PRIME),
+        mkpat!(NonCrypto; "FNV-1/FNV-1a",      noncrypto::FNV64_OFFSET),
+        mkpat!(NonCrypto; "FNV-1/FNV-1a",      noncrypto::FNV64_PRIME),
+        mkpat!(NonCrypto; "xxHash32",          noncrypto::XXH32_PRIMES),
+        mkpat!(NonCrypto; "xxHash64",          noncrypto::XXH64_PRIME_1),
+        mkpat!(NonCrypto; "xxHash64",          noncrypto::XXH64_PRIME_2),
+        mkpat!(NonCrypto; "xxHash64",          noncrypto::XXH64_PRIME_3),
+        mkpat!(NonCrypto; "xxHash64",          noncrypto::XXH64_PRIME_4),
+        mkpat!(NonCrypto; "xxHash64",          noncrypto::XXH64_PRIME_5),
+        mkpat!(NonCrypto; "CityHash",          noncrypto::CITYHASH_K0),
+        mkpat!(NonCrypto; "CityHash",          noncrypto::CITYHASH_K1),
+        mkpat!(NonCrypto; "CityHash",          noncrypto::CITYHASH_K2),
+        mkpat!(NonCrypto; "CityHash",          noncrypto::CITYHASH_KMUL),
+        mkpat!(NonCrypto; "SipHash",           noncrypto::SIPHASH_IV_0),
+        mkpat!(NonCrypto; "SipHash",           noncrypto::SIPHASH_IV_1),
+        mkpat!(NonCrypto; "SipHash",           noncrypto::SIPHASH_IV_2),
+        mkpat!(NonCrypto; "SipHash",           noncrypto::SIPHASH_IV_3),
+        mkpat!(NonCrypto; "Jenkins lookup3",   noncrypto::LOOKUP3_INIT),
+        mkpat!(NonCrypto; "MT19937",           noncrypto::MT19937_MAG01),
+        mkpat!(NonCrypto; "MT19937",           noncrypto::MT19937_TEMPER_B),
+        mkpat!(NonCrypto; "MT19937",           noncrypto::MT19937_TEMPER_C),
+        mkpat!(NonCrypto; "MT19937",           noncrypto::MT19937_INIT_MULT),
+        mkpat!(NonCrypto; "MT19937-64",        noncrypto::MT19937_64_MAG01),
+        mkpat!(NonCrypto; "MT19937-64",        noncrypto::MT19937_64_TEMPER_B),
+        mkpat!(NonCrypto; "MT19937-64",        noncrypto::MT19937_64_TEMPER_C),
+        mkpat!(NonCrypto; "PCG",               noncrypto::PCG_MULT),
+        mkpat!(NonCrypto; "PCG",               noncrypto::PCG_INCREMENT),
+        mkpat!(NonCrypto; "xorshift",          noncrypto::XORSHIFT64_STAR_MULT),
+        mkpat!(NonCrypto; "xorshift",          noncrypto::XORSHIFT1024_STAR_MULT),
+        mkpat!(NonCrypto; "SplitMix64",        noncrypto::SPLITMIX64_GAMMA),
+        mkpat!(NonCrypto; "SplitMix64",        noncrypto::SPLITMIX64_MIX_1),
+        mkpat!(NonCrypto; "SplitMix64",        noncrypto::SPLITMIX64_MIX_2),
+        mkpat!(NonCrypto; "Adler-32",          crc::ADLER32_BASE),
+        mkpat!(NonCrypto; "Adler-32",          crc::ADLER32_RECIPROCAL),
+
+        // Compression and encoding tables
+        mkpat!(Context; "DEFLATE",             context::DEFLATE_LENGTH_BASE),
+        mkpat!(Context; "DEFLATE",             context::DEFLATE_LENGTH_BASE_32),
+        mkpat!(Context; "DEFLATE",             context::DEFLATE_LENGTH_EXTRA),
+        mkpat!(Context; "DEFLATE",             context::ZLIB_LENGTH_EXTRA),
+        mkpat!(Context; "DEFLATE",             context::DEFLATE_DIST_BASE),
+        mkpat!(Context; "DEFLATE",             context::DEFLATE_DIST_BASE_32),
+        mkpat!(Context; "DEFLATE",             context::ZLIB_DIST_EXTRA),
+        mkpat!(Context; "DEFLATE",             context::DEFLATE_CODELEN_ORDER),
+        mkpat!(Context; "DEFLATE",             context::DEFLATE_CODELEN_ORDER_16),
+        mkpat!(Context; "DEFLATE",             context::ZLIB_LENFIX),
+        mkpat!(Context; "DEFLATE",             context::ZLIB_DISTFIX),
+        mkpat!(Context; "LZMA",                context::LZMA_LITERAL_NEXT_STATES),
+        mkpat!(Context; "LZMA",                context::XZ_MAGIC),
+        mkpat!(Context; "bzip2",               context::BZIP2_RNUMS),
+        mkpat!(Context; "Base64",              context::BASE64_ALPHABET),
+        mkpat!(Context; "Base64",              context::BASE64URL_ALPHABET),
+        mkpat!(Context; "Base32",              context::BASE32_ALPHABET),
+        mkpat!(Context; "Base32",              context::BASE32HEX_ALPHABET),
+        mkpat!(Context; "Base58",              context::BASE58_ALPHABET),
     ]
 }
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_is_selected_by() {
+    let patterns = get_patterns();
+    let select = |selector: &str| {
+        patterns.iter().filter(|p| p.is_selected_by(selector)).map(|p| p.varname).collect::<Vec<_>>()
+    };
+
+    assert_eq!(select("sha-256"), vec!["hash::SHA256_CONSTS"]);
+    assert_eq!(select("AES").len(), 12);
+    assert_eq!(select("crypto::DES_SBOX_?").len(), 8);
+    assert_eq!(select("crypto::DES_*").len(), 14);
+    assert_eq!(select("SHA-2").len(), 5);
+    assert_eq!(select("context").len(), patterns.iter().filter(|p| p.category == Category::Context).count());
+    assert!(select("AES*256").is_empty());
+}
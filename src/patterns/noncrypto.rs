@@ -0,0 +1,91 @@
+use super::super::endian::EndianWrapper;
+
+// Most of these are single constants that implementations use as immediates
+// (or as separate `static const` values), so they can't be grouped into
+// tables the way the cryptographic patterns are.  The 32-bit ones are only
+// reported alongside another constant from the same algorithm (see
+// `scanner::PAIR_DISTANCE`), apart from Jenkins' lookup3, which has nothing to
+// pair with.
+
+
+// MurmurHash3 - x86_32 block constants, followed by the fmix32 multipliers.
+pub static MURMUR3_32_C1: EndianWrapper<'static, u32> = EndianWrapper(&[0xcc9e2d51]);
+pub static MURMUR3_32_C2: EndianWrapper<'static, u32> = EndianWrapper(&[0x1b873593]);
+pub static MURMUR3_FMIX32_1: EndianWrapper<'static, u32> = EndianWrapper(&[0x85ebca6b]);
+pub static MURMUR3_FMIX32_2: EndianWrapper<'static, u32> = EndianWrapper(&[0xc2b2ae35]);
+
+// MurmurHash3 - x64_128 block constants, followed by the fmix64 multipliers.
+pub static MURMUR3_128_C1: EndianWrapper<'static, u64> = EndianWrapper(&[0x87c37b91114253d5]);
+pub static MURMUR3_128_C2: EndianWrapper<'static, u64> = EndianWrapper(&[0x4cf5ad432745937f]);
+pub static MURMUR3_FMIX64_1: EndianWrapper<'static, u64> = EndianWrapper(&[0xff51afd7ed558ccd]);
+pub static MURMUR3_FMIX64_2: EndianWrapper<'static, u64> = EndianWrapper(&[0xc4ceb9fe1a85ec53]);
+
+// MurmurHash2 / MurmurHash64A mixing constants.
+pub static MURMUR2_M: EndianWrapper<'static, u32> = EndianWrapper(&[0x5bd1e995]);
+pub static MURMUR64A_M: EndianWrapper<'static, u64> = EndianWrapper(&[0xc6a4a7935bd1e995]);
+
+
+// FNV-1 and FNV-1a use the same offset basis and prime.
+pub static FNV32_OFFSET: EndianWrapper<'static, u32> = EndianWrapper(&[0x811c9dc5]);
+pub static FNV32_PRIME: EndianWrapper<'static, u32> = EndianWrapper(&[0x01000193]);
+pub static FNV64_OFFSET: EndianWrapper<'static, u64> = EndianWrapper(&[0xcbf29ce484222325]);
+pub static FNV64_PRIME: EndianWrapper<'static, u64> = EndianWrapper(&[0x00000100000001b3]);
+
+
+// xxHash primes.  These are commonly laid out next to each other, so we search
+// for the entire set.
+pub static XXH32_PRIMES: EndianWrapper<'static, u32> = EndianWrapper(&[
+    0x9e3779b1, 0x85ebca77, 0xc2b2ae3d, 0x27d4eb2f, 0x165667b1,
+]);
+
+pub static XXH64_PRIME_1: EndianWrapper<'static, u64> = EndianWrapper(&[0x9e3779b185ebca87]);
+pub static XXH64_PRIME_2: EndianWrapper<'static, u64> = EndianWrapper(&[0xc2b2ae3d27d4eb4f]);
+pub static XXH64_PRIME_3: EndianWrapper<'static, u64> = EndianWrapper(&[0x165667b19e3779f9]);
+pub static XXH64_PRIME_4: EndianWrapper<'static, u64> = EndianWrapper(&[0x85ebca77c2b2ae63]);
+pub static XXH64_PRIME_5: EndianWrapper<'static, u64> = EndianWrapper(&[0x27d4eb2f165667c5]);
+
+
+// CityHash / FarmHash constants.
+pub static CITYHASH_K0: EndianWrapper<'static, u64> = EndianWrapper(&[0xc3a5c85c97cb3127]);
+pub static CITYHASH_K1: EndianWrapper<'static, u64> = EndianWrapper(&[0xb492b66fbe98f273]);
+pub static CITYHASH_K2: EndianWrapper<'static, u64> = EndianWrapper(&[0x9ae16a3b2f90404f]);
+pub static CITYHASH_KMUL: EndianWrapper<'static, u64> = EndianWrapper(&[0x9ddfea08eb382d69]);
+
+
+// SipHash initialization vector - "somepseudorandomlygeneratedbytes".
+pub static SIPHASH_IV_0: EndianWrapper<'static, u64> = EndianWrapper(&[0x736f6d6570736575]);
+pub static SIPHASH_IV_1: EndianWrapper<'static, u64> = EndianWrapper(&[0x646f72616e646f6d]);
+pub static SIPHASH_IV_2: EndianWrapper<'static, u64> = EndianWrapper(&[0x6c7967656e657261]);
+pub static SIPHASH_IV_3: EndianWrapper<'static, u64> = EndianWrapper(&[0x7465646279746573]);
+
+
+// Jenkins lookup3 - the initial value of the internal state.  Note that this
+// is also a popular "magic" debugging value, and it's reported on its own (see
+// `scanner::UNPAIRED`), so expect false positives.
+pub static LOOKUP3_INIT: EndianWrapper<'static, u32> = EndianWrapper(&[0xdeadbeef]);
+
+
+// Mersenne Twister (MT19937) - the `mag01` array, and the tempering masks.
+pub static MT19937_MAG01: EndianWrapper<'static, u32> = EndianWrapper(&[0x00000000, 0x9908b0df]);
+pub static MT19937_TEMPER_B: EndianWrapper<'static, u32> = EndianWrapper(&[0x9d2c5680]);
+pub static MT19937_TEMPER_C: EndianWrapper<'static, u32> = EndianWrapper(&[0xefc60000]);
+pub static MT19937_INIT_MULT: EndianWrapper<'static, u32> = EndianWrapper(&[0x6c078965]);
+
+// Mersenne Twister (MT19937-64)
+pub static MT19937_64_MAG01: EndianWrapper<'static, u64> = EndianWrapper(&[0x0000000000000000, 0xb5026f5aa96619e9]);
+pub static MT19937_64_TEMPER_B: EndianWrapper<'static, u64> = EndianWrapper(&[0x71d67fffeda60000]);
+pub static MT19937_64_TEMPER_C: EndianWrapper<'static, u64> = EndianWrapper(&[0xfff7eee000000000]);
+
+
+// PCG - the default 64-bit LCG multiplier and increment.  The multiplier is
+// also Knuth's MMIX LCG multiplier, and is used by MT19937-64 for seeding.
+pub static PCG_MULT: EndianWrapper<'static, u64> = EndianWrapper(&[0x5851f42d4c957f2d]);
+pub static PCG_INCREMENT: EndianWrapper<'static, u64> = EndianWrapper(&[0x14057b7ef767814f]);
+
+
+// xorshift* and SplitMix64 multipliers.
+pub static XORSHIFT64_STAR_MULT: EndianWrapper<'static, u64> = EndianWrapper(&[0x2545f4914f6cdd1d]);
+pub static XORSHIFT1024_STAR_MULT: EndianWrapper<'static, u64> = EndianWrapper(&[0x106689d45497fdb5]);
+pub static SPLITMIX64_GAMMA: EndianWrapper<'static, u64> = EndianWrapper(&[0x9e3779b97f4a7c15]);
+pub static SPLITMIX64_MIX_1: EndianWrapper<'static, u64> = EndianWrapper(&[0xbf58476d1ce4e5b9]);
+pub static SPLITMIX64_MIX_2: EndianWrapper<'static, u64> = EndianWrapper(&[0x94d049bb133111eb]);
@@ -0,0 +1,267 @@
+use std::cmp::Ordering;
+
+use super::{crc, crypto, hash};
+
+
+/// The result of checking one table against its definition.
+pub struct Check {
+    /// The table's variable name, or the name of a CRC model.
+    pub name: &'static str,
+
+    /// What's wrong with the table, if anything.
+    pub error: Option<String>,
+}
+
+
+/// Compares a table with the one generated from its definition.
+fn compare(name: &'static str, table: &[u64], generated: &[u64]) -> Check {
+    let error = if table.len() != generated.len() {
+        Some(format!("has {} entries, expected {}", table.len(), generated.len()))
+    } else {
+        table.iter().zip(generated).position(|(a, b)| a != b).map(|i| {
+            format!("entry {} is {:#x}, expected {:#x}", i, table[i], generated[i])
+        })
+    };
+
+    Check {
+        name:  name,
+        error: error,
+    }
+}
+
+
+/// Widens a table so that tables of different types can be compared.
+fn widen<T: Copy + Into<u64>>(table: &[T]) -> Vec<u64> {
+    table.iter().map(|&x| x.into()).collect()
+}
+
+
+/// Multiplies two elements of GF(2^8), using the AES polynomial.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut ret = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            ret ^= a;
+        }
+        a = if a & 0x80 != 0 { (a << 1) ^ 0x1b } else { a << 1 };
+        b >>= 1;
+    }
+    ret
+}
+
+
+/// Generates the AES S-box: the multiplicative inverse in GF(2^8), followed by
+/// the affine transformation.
+pub fn aes_sbox() -> Vec<u8> {
+    (0..256).map(|x| {
+        let x = x as u8;
+        let inv = if x == 0 { 0 } else { (1..256).map(|y| y as u8).find(|&y| gf_mul(x, y) == 1).unwrap() };
+        inv ^ inv.rotate_left(1) ^ inv.rotate_left(2) ^ inv.rotate_left(3) ^ inv.rotate_left(4) ^ 0x63
+    }).collect()
+}
+
+
+/// Generates the inverse AES S-box.
+pub fn aes_inv_sbox() -> Vec<u8> {
+    let mut ret = vec![0; 256];
+    for (i, &s) in aes_sbox().iter().enumerate() {
+        ret[s as usize] = i as u8;
+    }
+    ret
+}
+
+
+/// Packs four bytes into a word, most significant first.
+fn word(a: u8, b: u8, c: u8, d: u8) -> u32 {
+    ((a as u32) << 24) | ((b as u32) << 16) | ((c as u32) << 8) | d as u32
+}
+
+
+/// Generates one of the encryption tables used by table-driven AES: `Te0` to
+/// `Te3` combine the S-box with MixColumns, each rotated by another byte, and
+/// `Te4` is the S-box in every byte.
+pub fn aes_te(n: u32) -> Vec<u32> {
+    aes_sbox().into_iter().map(|s| {
+        if n == 4 {
+            word(s, s, s, s)
+        } else {
+            word(gf_mul(s, 2), s, s, gf_mul(s, 3)).rotate_right(8 * n)
+        }
+    }).collect()
+}
+
+
+/// Generates one of the decryption tables, like `aes_te` but with the inverse
+/// S-box and InvMixColumns.
+pub fn aes_td(n: u32) -> Vec<u32> {
+    aes_inv_sbox().into_iter().map(|s| {
+        if n == 4 {
+            word(s, s, s, s)
+        } else {
+            word(gf_mul(s, 0x0e), gf_mul(s, 0x09), gf_mul(s, 0x0d), gf_mul(s, 0x0b)).rotate_right(8 * n)
+        }
+    }).collect()
+}
+
+
+/// Multiplies two numbers stored as little-endian 32-bit limbs.
+fn bignum_mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut ret = vec![0; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = ret[i + j] as u64 + x as u64 * y as u64 + carry;
+            ret[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        ret[i + b.len()] = carry as u32;
+    }
+    ret
+}
+
+
+/// Compares two numbers stored as little-endian 32-bit limbs.
+fn bignum_cmp(a: &[u32], b: &[u32]) -> Ordering {
+    let len = if a.len() > b.len() { a.len() } else { b.len() };
+    for i in (0..len).rev() {
+        let x = a.get(i).cloned().unwrap_or(0);
+        let y = b.get(i).cloned().unwrap_or(0);
+        if x != y {
+            return x.cmp(&y);
+        }
+    }
+    Ordering::Equal
+}
+
+
+/// Returns the first 64 bits of the fractional part of the cube root of `n`,
+/// exactly.  Floating point isn't precise enough for this, so the root of
+/// `n * 2^192` is found a bit at a time.
+fn cbrt_frac(n: u32) -> u64 {
+    let target = [0, 0, 0, 0, 0, 0, n];
+    let mut root = [0u32; 3];
+
+    // The integer part of the root fits in 8 bits for any n that we use.
+    for bit in (0..72).rev() {
+        root[bit / 32] |= 1 << (bit % 32);
+        let cube = bignum_mul(&bignum_mul(&root, &root), &root);
+        if bignum_cmp(&cube, &target) == Ordering::Greater {
+            root[bit / 32] &= !(1 << (bit % 32));
+        }
+    }
+
+    ((root[1] as u64) << 32) | root[0] as u64
+}
+
+
+/// Returns the first `count` prime numbers.
+fn primes(count: usize) -> Vec<u32> {
+    let mut ret = vec![];
+    let mut n = 2;
+    while ret.len() < count {
+        if ret.iter().all(|&p| n % p != 0) {
+            ret.push(n);
+        }
+        n += 1;
+    }
+    ret
+}
+
+
+/// Generates the SHA-512 round constants: the first 64 bits of the fractional
+/// parts of the cube roots of the first 80 primes.
+pub fn sha512_k() -> Vec<u64> {
+    primes(80).into_iter().map(cbrt_frac).collect()
+}
+
+
+/// Generates the SHA-256 round constants, which are the first 32 bits of the
+/// first 64 SHA-512 constants.
+pub fn sha256_k() -> Vec<u32> {
+    sha512_k().into_iter().take(64).map(|k| (k >> 32) as u32).collect()
+}
+
+
+/// Generates the MD5 sine table.
+pub fn md5_t() -> Vec<u32> {
+    (1..65).map(|i| ((i as f64).sin().abs() * 4294967296.0) as u32).collect()
+}
+
+
+/// Generates the Keccak-f[1600] round constants from the LFSR described in the
+/// specification.
+pub fn keccak_rc() -> Vec<u64> {
+    let mut r: u8 = 1;
+    (0..24).map(|_| {
+        let mut rc = 0;
+        for j in 0..7 {
+            if r & 1 != 0 {
+                rc ^= 1 << ((1 << j) - 1);
+            }
+            r = if r & 0x80 != 0 { (r << 1) ^ 0x71 } else { r << 1 };
+        }
+        rc
+    }).collect()
+}
+
+
+/// Checks every table that can be generated from its definition.  CRC tables
+/// are always generated, so their models are checked against their published
+/// check values instead.
+pub fn run() -> Vec<Check> {
+    let mut ret = vec![
+        compare("hash::SHA256_CONSTS", &widen(hash::SHA256_CONSTS.0), &widen(&sha256_k())),
+        compare("hash::SHA512_CONSTS", &widen(hash::SHA512_CONSTS.0), &sha512_k()),
+        compare("hash::MD5_CONSTS",    &widen(hash::MD5_CONSTS.0),    &widen(&md5_t())),
+        compare("hash::KECCAK_CONSTS", &widen(hash::KECCAK_CONSTS.0), &keccak_rc()),
+    ];
+
+    let te = [&crypto::RIJNDAEL_TE0, &crypto::RIJNDAEL_TE1, &crypto::RIJNDAEL_TE2,
+              &crypto::RIJNDAEL_TE3, &crypto::RIJNDAEL_TE4];
+    let td = [&crypto::RIJNDAEL_TD0, &crypto::RIJNDAEL_TD1, &crypto::RIJNDAEL_TD2,
+              &crypto::RIJNDAEL_TD3, &crypto::RIJNDAEL_TD4];
+    let te_names = ["crypto::RIJNDAEL_TE0", "crypto::RIJNDAEL_TE1", "crypto::RIJNDAEL_TE2",
+                    "crypto::RIJNDAEL_TE3", "crypto::RIJNDAEL_TE4"];
+    let td_names = ["crypto::RIJNDAEL_TD0", "crypto::RIJNDAEL_TD1", "crypto::RIJNDAEL_TD2",
+                    "crypto::RIJNDAEL_TD3", "crypto::RIJNDAEL_TD4"];
+    for n in 0..5 {
+        ret.push(compare(te_names[n], &widen(te[n].0), &widen(&aes_te(n as u32))));
+    }
+    for n in 0..5 {
+        ret.push(compare(td_names[n], &widen(td[n].0), &widen(&aes_td(n as u32))));
+    }
+    ret.push(compare("crypto::RIJNDAEL_SBOX",     &widen(crypto::RIJNDAEL_SBOX.0),     &widen(&aes_sbox())));
+    ret.push(compare("crypto::RIJNDAEL_INV_SBOX", &widen(crypto::RIJNDAEL_INV_SBOX.0), &widen(&aes_inv_sbox())));
+
+    for model in crc::MODELS {
+        let sum = model.checksum(b"123456789");
+        ret.push(Check {
+            name:  model.name,
+            error: if sum == model.check {
+                None
+            } else {
+                Some(format!("check value is {:#x}, expected {:#x}", sum, model.check))
+            },
+        });
+    }
+
+    ret
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_generators() {
+    assert_eq!(&aes_sbox()[..4], &[0x63, 0x7c, 0x77, 0x7b]);
+    assert_eq!(aes_inv_sbox()[0], 0x52);
+    assert_eq!(cbrt_frac(2), 0x428a2f98d728ae22);
+    assert_eq!(md5_t()[0], 0xd76aa478);
+    assert_eq!(keccak_rc()[1], 0x8082);
+}
+
+#[test]
+fn test_tables_match_definitions() {
+    for check in run() {
+        assert!(check.error.is_none(), "{}: {}", check.name, check.error.unwrap());
+    }
+}
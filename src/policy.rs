@@ -0,0 +1,186 @@
+use std::ascii::AsciiExt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use toml;
+
+use algorithms;
+
+
+/// What to do when an algorithm is found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        match &name.to_lowercase()[..] {
+            "allow" => Some(Action::Allow),
+            "warn"  => Some(Action::Warn),
+            "deny"  => Some(Action::Deny),
+            _       => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Action::Allow => "allow",
+            Action::Warn  => "warn",
+            Action::Deny  => "deny",
+        }
+    }
+}
+
+
+/// Says which algorithms are allowed, read from a TOML file like:
+///
+/// ```toml
+/// default = "allow"
+///
+/// [status]
+/// broken = "deny"
+/// legacy = "warn"
+///
+/// [families]
+/// DH = "warn"
+///
+/// [algorithms]
+/// DH-1024 = "deny"
+/// ```
+///
+/// The most specific entry wins: an algorithm's own entry, then its family's,
+/// then its security status's, and then the default.  Names are matched
+/// without regard to case.
+pub struct Policy {
+    default: Action,
+    algorithms: BTreeMap<String, Action>,
+    families: BTreeMap<String, Action>,
+    statuses: BTreeMap<String, Action>,
+}
+
+
+/// Reads a table of names to actions, lowercasing the names.
+fn parse_actions(table: &toml::Table, section: &str) -> Result<BTreeMap<String, Action>, String> {
+    let mut ret = BTreeMap::new();
+    for (name, value) in table {
+        match value.as_str().and_then(Action::from_name) {
+            Some(a) => { ret.insert(name.to_lowercase(), a); },
+            None    => return Err(format!("[{}] {}: expected \"allow\", \"warn\" or \"deny\"", section, name)),
+        }
+    }
+    Ok(ret)
+}
+
+
+impl Policy {
+    pub fn parse(source: &str) -> Result<Policy, String> {
+        let mut parser = toml::Parser::new(source);
+        let table = match parser.parse() {
+            Some(t) => t,
+            None    => {
+                let errors = parser.errors.iter().map(|e| {
+                    let (line, col) = parser.to_linecol(e.lo);
+                    format!("{}:{}: {}", line + 1, col + 1, e.desc)
+                }).collect::<Vec<_>>();
+                return Err(errors.join("; "));
+            },
+        };
+
+        let mut ret = Policy {
+            default:    Action::Allow,
+            algorithms: BTreeMap::new(),
+            families:   BTreeMap::new(),
+            statuses:   BTreeMap::new(),
+        };
+
+        for (key, value) in &table {
+            match (&key[..], value.as_table()) {
+                ("algorithms", Some(t)) => ret.algorithms = try!(parse_actions(t, key)),
+                ("families", Some(t))   => ret.families = try!(parse_actions(t, key)),
+                ("status", Some(t))     => {
+                    ret.statuses = try!(parse_actions(t, key));
+                    for status in ret.statuses.keys() {
+                        if !["broken", "legacy", "acceptable"].contains(&&status[..]) {
+                            return Err(format!("[status] {}: expected broken, legacy or acceptable", status));
+                        }
+                    }
+                },
+                ("default", None) => {
+                    ret.default = try!(value.as_str().and_then(Action::from_name)
+                        .ok_or("default: expected \"allow\", \"warn\" or \"deny\"".to_string()));
+                },
+                _ => return Err(format!("unknown key: {}", key)),
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns what to do about the given algorithm, along with the entry in
+    /// the policy that decided it.
+    pub fn action(&self, algorithm: &str) -> (Action, String) {
+        if let Some(&a) = self.algorithms.get(&algorithm.to_lowercase()) {
+            return (a, format!("algorithm {}", algorithm));
+        }
+
+        if let Some(info) = algorithms::lookup(algorithm) {
+            if let Some(&a) = self.families.get(&info.family.to_lowercase()) {
+                return (a, format!("family {}", info.family));
+            }
+            if let Some(&a) = self.statuses.get(info.status.name()) {
+                return (a, format!("status {}", info.status.name()));
+            }
+        }
+
+        (self.default, "default".to_string())
+    }
+}
+
+
+/// Loads the policy in the given file.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Policy, String> {
+    let mut source = String::new();
+    try!(fs::File::open(path.as_ref())
+        .and_then(|mut f| f.read_to_string(&mut source))
+        .map_err(|e| e.to_string()));
+
+    Policy::parse(&source)
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_policy() {
+    let policy = Policy::parse(r#"
+default = "allow"
+
+[status]
+broken = "deny"
+legacy = "warn"
+
+[families]
+dh = "warn"
+
+[algorithms]
+DH-1024 = "deny"
+"ML-KEM/Kyber" = "warn"
+"#).unwrap();
+
+    assert_eq!(policy.action("DES"), (Action::Deny, "status broken".to_string()));
+    assert_eq!(policy.action("pkcs md5"), (Action::Deny, "status broken".to_string()));
+    assert_eq!(policy.action("Blowfish"), (Action::Warn, "status legacy".to_string()));
+    assert_eq!(policy.action("DH-1024"), (Action::Deny, "algorithm DH-1024".to_string()));
+    assert_eq!(policy.action("DH-2048"), (Action::Warn, "family DH".to_string()));
+    assert_eq!(policy.action("ML-KEM/Kyber"), (Action::Warn, "algorithm ML-KEM/Kyber".to_string()));
+    assert_eq!(policy.action("SHA-256"), (Action::Allow, "default".to_string()));
+    assert_eq!(policy.action("MurmurHash3"), (Action::Allow, "default".to_string()));
+
+    assert!(Policy::parse("[status]\nbroken = \"block\"\n").is_err());
+    assert!(Policy::parse("[status]\nweak = \"deny\"\n").is_err());
+    assert!(Policy::parse("defualt = \"deny\"\n").is_err());
+}
@@ -0,0 +1,156 @@
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+
+
+const PAGE_SIZE: usize = 4096;
+const CHUNK_SIZE: usize = 256 * PAGE_SIZE;
+
+
+/// A region of a process's address space, from `/proc/<pid>/maps`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mapping {
+    pub start: u64,
+    pub end: u64,
+    pub perms: String,
+    pub offset: u64,
+
+    /// The file backing this mapping, or a pseudo-name like `[heap]`, or empty
+    /// for anonymous mappings.
+    pub path: String,
+}
+
+
+impl Mapping {
+    pub fn readable(&self) -> bool {
+        self.perms.starts_with('r')
+    }
+
+    /// Returns the name to report for this mapping.
+    pub fn name(&self) -> &str {
+        if self.path.is_empty() {
+            "[anon]"
+        } else {
+            &self.path
+        }
+    }
+}
+
+
+/// Parses the contents of a `/proc/<pid>/maps` file.  Lines that can't be
+/// parsed are ignored.
+pub fn parse_maps(maps: &str) -> Vec<Mapping> {
+    let mut ret = vec![];
+
+    for line in maps.lines() {
+        // address perms offset dev inode [path]
+        let mut fields = line.splitn(6, ' ');
+        let (range, perms, offset) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(r), Some(p), Some(o)) => (r, p, o),
+            _                           => continue,
+        };
+        let path = fields.nth(2).unwrap_or("").trim();
+
+        let mut bounds = range.splitn(2, '-');
+        let (start, end) = match (bounds.next(), bounds.next()) {
+            (Some(s), Some(e)) => (s, e),
+            _                  => continue,
+        };
+
+        match (u64::from_str_radix(start, 16), u64::from_str_radix(end, 16), u64::from_str_radix(offset, 16)) {
+            (Ok(s), Ok(e), Ok(o)) => ret.push(Mapping {
+                start:  s,
+                end:    e,
+                perms:  perms.to_string(),
+                offset: o,
+                path:   path.to_string(),
+            }),
+            _ => continue,
+        }
+    }
+
+    ret
+}
+
+
+/// Returns the memory mappings of the given process.
+pub fn mappings(pid: u32) -> io::Result<Vec<Mapping>> {
+    let mut maps = String::new();
+    try!(try!(fs::File::open(format!("/proc/{}/maps", pid))).read_to_string(&mut maps));
+    Ok(parse_maps(&maps))
+}
+
+
+/// Opens the memory of the given process for reading.  This requires the same
+/// permissions as attaching with ptrace.
+pub fn open_memory(pid: u32) -> io::Result<fs::File> {
+    fs::File::open(format!("/proc/{}/mem", pid))
+}
+
+
+/// Reads `len` bytes at `address`, returning an error if they can't all be
+/// read.
+fn read_at(mem: &mut fs::File, address: u64, buf: &mut [u8]) -> io::Result<()> {
+    try!(mem.seek(SeekFrom::Start(address)));
+    mem.read_exact(buf)
+}
+
+
+/// Reads the contents of a mapping.  Pages that can't be read (e.g. guard
+/// pages, or memory that's been unmapped since we read the maps) are replaced
+/// with zeroes, so that offsets in the returned data still correspond to
+/// addresses.  Returns the data and the number of pages that couldn't be read.
+pub fn read_mapping(mem: &mut fs::File, mapping: &Mapping) -> (Vec<u8>, usize) {
+    let len = (mapping.end - mapping.start) as usize;
+    let mut data = vec![0u8; len];
+    let mut unreadable = 0;
+
+    let mut off = 0;
+    while off < len {
+        let chunk = if len - off < CHUNK_SIZE { len - off } else { CHUNK_SIZE };
+        let address = mapping.start + off as u64;
+
+        if read_at(mem, address, &mut data[off..off + chunk]).is_err() {
+            // Fall back to reading a page at a time, to find out which pages
+            // are the problem.
+            let mut page = 0;
+            while page < chunk {
+                let size = if chunk - page < PAGE_SIZE { chunk - page } else { PAGE_SIZE };
+                let buf = &mut data[off + page..off + page + size];
+
+                if read_at(mem, address + page as u64, buf).is_err() {
+                    for b in buf.iter_mut() {
+                        *b = 0;
+                    }
+                    unreadable += 1;
+                }
+
+                page += size;
+            }
+        }
+
+        off += chunk;
+    }
+
+    (data, unreadable)
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_parse_maps() {
+    let maps = "55d0c0a00000-55d0c0a04000 r--p 00000000 fd:01 1234                       /usr/bin/cat\n\
+                55d0c1e2c000-55d0c1e4d000 rw-p 00000000 00:00 0                          [heap]\n\
+                7f1e2b400000-7f1e2b600000 rw-p 00000000 00:00 0 \n\
+                7f1e2b800000-7f1e2b9d6000 r-xp 00028000 fd:01 5678                       /usr/lib/libc.so.6\n";
+
+    let mappings = parse_maps(maps);
+
+    assert_eq!(mappings.len(), 4);
+    assert_eq!(mappings[0].start, 0x55d0c0a00000);
+    assert_eq!(mappings[0].end, 0x55d0c0a04000);
+    assert_eq!(mappings[0].path, "/usr/bin/cat");
+    assert_eq!(mappings[1].name(), "[heap]");
+    assert_eq!(mappings[2].name(), "[anon]");
+    assert_eq!(mappings[3].perms, "r-xp");
+    assert_eq!(mappings[3].offset, 0x28000);
+}
@@ -0,0 +1,313 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::slice;
+
+use aho_corasick::{self, Automaton, AcAutomaton};
+use libc;
+use mmap::{MemoryMap, MapOption};
+
+use endian::Endianness;
+use patterns::{Category, Pattern};
+
+
+/// Non-cryptographic constants that are only four bytes long (e.g. FNV's
+/// prime) turn up by chance in almost any large binary, so they're only
+/// reported when another constant from the same algorithm - or the same one
+/// again - is found within this many bytes.  Implementations use them
+/// together, e.g. MurmurHash3 multiplies each block by `c1` and then `c2`.
+pub const PAIR_DISTANCE: usize = 256;
+const WEAK_LEN: usize = 4;
+
+/// Short constants that are reported on their own anyway, because their
+/// algorithm has nothing else to pair them with.  These are low-confidence
+/// matches.
+pub const UNPAIRED: &'static [&'static str] = &["noncrypto::LOOKUP3_INIT"];
+
+
+/// A pattern found in some data.
+#[derive(Clone, Copy)]
+pub struct Match<'a> {
+    pub pattern: &'a Pattern,
+
+    /// The byte order that the pattern was found in.
+    pub endianness: Endianness,
+
+    /// The offset of the match from the start of the data, and its length.
+    pub offset: usize,
+    pub len: usize,
+}
+
+
+/// Searches for a set of patterns, in one or both byte orders, all at once.
+pub struct Scanner {
+    patterns: Vec<Pattern>,
+    endians: Vec<Endianness>,
+    automaton: AcAutomaton<Vec<u8>>,
+
+    /// Whether each pattern needs to be paired before it's reported.
+    weak: Vec<bool>,
+}
+
+impl Scanner {
+    pub fn new(patterns: Vec<Pattern>) -> Scanner {
+        Scanner::with_endianness(patterns, &[Endianness::LittleEndian, Endianness::BigEndian])
+    }
+
+    /// Creates a scanner that only searches for the patterns in the given byte
+    /// orders, e.g. when the target is known to be little-endian.
+    pub fn with_endianness(patterns: Vec<Pattern>, endians: &[Endianness]) -> Scanner {
+        assert!(!endians.is_empty(), "need at least one byte order to search in");
+
+        let mut bytes = vec![];
+        let mut weak = vec![];
+        for pat in &patterns {
+            // NOTE: Order matters here! See `to_match`.
+            for &e in endians {
+                bytes.push(pat.bytes.as_byte_vec(e));
+            }
+            weak.push(pat.category == Category::NonCrypto &&
+                      bytes[bytes.len() - 1].len() <= WEAK_LEN &&
+                      !UNPAIRED.contains(&pat.varname));
+        }
+
+        Scanner {
+            patterns:  patterns,
+            endians:   endians.to_vec(),
+            automaton: AcAutomaton::new(bytes),
+            weak:      weak,
+        }
+    }
+
+    /// Returns the patterns that are searched for.
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    fn to_match(&self, m: &aho_corasick::Match) -> Match {
+        // When building, we added one pattern to the automaton for each byte
+        // order, in the order that they were given.  Reverse this back to the
+        // corresponding `Pattern`.
+        let n = self.endians.len();
+        Match {
+            pattern:    &self.patterns[m.pati / n],
+            endianness: self.endians[m.pati % n],
+            offset:     m.start,
+            len:        m.end - m.start,
+        }
+    }
+
+    /// Searches the given data.
+    pub fn scan_bytes(&self, data: &[u8]) -> Vec<Match> {
+        // Reading should never fail, since we're using an in-memory buffer.
+        self.scan_reader(data).unwrap()
+    }
+
+    /// Searches everything read from the given reader, calling `f` with each
+    /// match as it's found.  The offsets are from the start of the stream, and
+    /// matches that cross the reader's buffer boundaries are still found.
+    ///
+    /// Matches that have to be paired (see `PAIR_DISTANCE`) are held back
+    /// until they are, so they may be passed to `f` after later matches.
+    pub fn scan_reader_with<'a, R, F>(&'a self, reader: R, mut f: F) -> io::Result<()>
+    where R: Read,
+          F: FnMut(Match<'a>)
+    {
+        let mut recent = Recent { matches: VecDeque::new() };
+        for m in self.automaton.stream_find(BufReader::new(reader)) {
+            let m = try!(m);
+            let weak = self.weak[m.pati / self.endians.len()];
+            recent.push(self.to_match(&m), weak, &mut f);
+        }
+        Ok(())
+    }
+
+    /// Searches everything read from the given reader, returning the matches
+    /// in order.
+    pub fn scan_reader<R: Read>(&self, reader: R) -> io::Result<Vec<Match>> {
+        let mut ret = vec![];
+        try!(self.scan_reader_with(reader, |m| ret.push(m)));
+        ret.sort_by_key(|m| m.offset);
+        Ok(ret)
+    }
+
+    /// Searches the given file, which is mapped into memory if possible.
+    /// Compressed files and archives are searched as-is; see `container::walk`
+    /// to search inside them.
+    pub fn scan_path<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<Match>> {
+        let path = path.as_ref();
+
+        // Pipes, sockets and many procfs/sysfs files can't be mapped, but we
+        // can still read them.
+        match with_file_mmap(path, |map| self.scan_bytes(map)) {
+            Ok(m)  => Ok(m),
+            Err(e) => {
+                debug!("Could not map {}, falling back to streaming: {}", path.display(), e);
+                self.scan_reader(try!(fs::File::open(path)))
+            },
+        }
+    }
+}
+
+
+/// The matches within `PAIR_DISTANCE` bytes of the latest one, which later
+/// matches can be paired with.
+struct Recent<'a> {
+    /// Each match, and whether it's been reported yet.
+    matches: VecDeque<(Match<'a>, bool)>,
+}
+
+impl<'a> Recent<'a> {
+    /// Adds a match, reporting it unless it's weak and hasn't been paired, and
+    /// reporting any weak matches that it pairs with.
+    fn push<F: FnMut(Match<'a>)>(&mut self, m: Match<'a>, weak: bool, f: &mut F) {
+        while self.matches.front().map_or(false, |r| r.0.offset + PAIR_DISTANCE < m.offset) {
+            self.matches.pop_front();
+        }
+
+        let mut paired = false;
+        for r in self.matches.iter_mut().filter(|r| r.0.pattern.algorithm == m.pattern.algorithm) {
+            paired = true;
+            if !r.1 {
+                f(r.0);
+                r.1 = true;
+            }
+        }
+
+        let report = paired || !weak;
+        if report {
+            f(m);
+        }
+        self.matches.push_back((m, report));
+    }
+}
+
+
+#[cfg(unix)]
+fn get_fd(file: &fs::File) -> libc::c_int {
+    use std::os::unix::io::AsRawFd;
+    file.as_raw_fd()
+}
+
+#[cfg(windows)]
+fn get_fd(file: &fs::File) -> libc::HANDLE {
+    use std::os::windows::io::AsRawHandle;
+    file.as_raw_handle() as libc::HANDLE
+}
+
+/// Maps the given file into memory, and calls `f` with its contents.
+pub fn with_file_mmap<P, F, T>(path: P, f: F) -> io::Result<T>
+where P: AsRef<Path>,
+      F: Fn(&[u8]) -> T
+{
+    let file = try!(fs::OpenOptions::new()
+        .read(true)
+        .open(path));
+
+    // Get the size of the file.
+    let len = try!(file.metadata()).len() as usize;
+
+    let fd = get_fd(&file);
+
+    let chunk = try!(MemoryMap::new(len, &[
+                                    MapOption::MapReadable,
+                                    MapOption::MapFd(fd),
+    ]).map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e))));
+
+    let file_data: &[u8] = unsafe {
+        slice::from_raw_parts(chunk.data() as *const _, chunk.len())
+    };
+
+    Ok(f(file_data))
+}
+
+// ----------------------------------------------------------------------
+
+#[cfg(test)]
+static TEST_TABLE: ::endian::EndianWrapper<'static, u32> = ::endian::EndianWrapper(&[0x01020304, 0x05060708]);
+
+#[test]
+fn test_scanner() {
+    let scanner = Scanner::new(vec![
+        Pattern {
+            algorithm: "Test",
+            desc:      "test::TABLE",
+            bytes:     &TEST_TABLE,
+            varname:   "test::TABLE",
+            category:  ::patterns::Category::Crypto,
+        },
+    ]);
+
+    let data = [0xFF, 0x04, 0x03, 0x02, 0x01, 0x08, 0x07, 0x06, 0x05,
+                0xFF, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+
+    let matches = scanner.scan_bytes(&data);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].pattern.varname, "test::TABLE");
+    assert_eq!(matches[0].endianness, Endianness::LittleEndian);
+    assert_eq!((matches[0].offset, matches[0].len), (1, 8));
+    assert_eq!(matches[1].endianness, Endianness::BigEndian);
+    assert_eq!((matches[1].offset, matches[1].len), (10, 8));
+
+    let streamed = scanner.scan_reader(&data[..]).unwrap();
+    assert_eq!(streamed.iter().map(|m| m.offset).collect::<Vec<_>>(), vec![1, 10]);
+
+    let big = Scanner::with_endianness(scanner.patterns().to_vec(), &[Endianness::BigEndian]);
+    let matches = big.scan_bytes(&data);
+    assert_eq!(matches.len(), 1);
+    assert_eq!((matches[0].endianness, matches[0].offset), (Endianness::BigEndian, 10));
+}
+
+#[cfg(test)]
+static TEST_C1: ::endian::EndianWrapper<'static, u32> = ::endian::EndianWrapper(&[0xcc9e2d51]);
+
+#[cfg(test)]
+static TEST_C2: ::endian::EndianWrapper<'static, u32> = ::endian::EndianWrapper(&[0x1b873593]);
+
+#[test]
+fn test_scanner_pairs() {
+    let pattern = |algorithm: &'static str, bytes: &'static ::endian::AsByteVec, varname: &'static str| Pattern {
+        algorithm: algorithm,
+        desc:      varname,
+        bytes:     bytes,
+        varname:   varname,
+        category:  Category::NonCrypto,
+    };
+    let scanner = Scanner::with_endianness(vec![pattern("Test", &TEST_C1, "test::C1"),
+                                                pattern("Test", &TEST_C2, "test::C2"),
+                                                pattern("Other", &TEST_TABLE, "test::TABLE")],
+                                           &[Endianness::LittleEndian]);
+
+    // A lone `c1`, then `c1`, an unrelated table and `c2` close together.
+    let mut data = vec![0x51, 0x2d, 0x9e, 0xcc];
+    data.extend(vec![0u8; PAIR_DISTANCE]);
+    data.extend_from_slice(&[0x51, 0x2d, 0x9e, 0xcc, 0x04, 0x03, 0x02, 0x01, 0x08, 0x07, 0x06, 0x05]);
+    data.extend_from_slice(&[0x93, 0x35, 0x87, 0x1b]);
+
+    let expected = vec![
+        ("test::C1", PAIR_DISTANCE + 4),
+        ("test::TABLE", PAIR_DISTANCE + 8),
+        ("test::C2", PAIR_DISTANCE + 16),
+    ];
+    let found = |matches: Vec<Match>| matches.iter().map(|m| (m.pattern.varname, m.offset)).collect::<Vec<_>>();
+    assert_eq!(found(scanner.scan_bytes(&data)), expected);
+
+    // Without sorting, the held-back `c1` is reported after the table.
+    let mut streamed = vec![];
+    scanner.scan_reader_with(&data[..], |m| streamed.push(m)).unwrap();
+    assert_eq!(found(streamed), vec![expected[1], expected[0], expected[2]]);
+}
+
+#[test]
+fn test_scanner_unpaired() {
+    let patterns = ::patterns::get_patterns().into_iter()
+        .filter(|p| UNPAIRED.contains(&p.varname))
+        .collect::<Vec<_>>();
+    assert_eq!(patterns.len(), UNPAIRED.len());
+    let scanner = Scanner::with_endianness(patterns, &[Endianness::LittleEndian]);
+
+    let matches = scanner.scan_bytes(&[0x00, 0xef, 0xbe, 0xad, 0xde]);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].offset, 1);
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use patterns::Category;
+
+use super::{Loaded, Signature, Table};
+
+
+/// Returns whether the source looks like C arrays of constants, rather than
+/// YARA rules.
+pub fn is_consts(source: &str) -> bool {
+    source.contains("array_info_t") || source.contains("static const")
+}
+
+
+/// Removes comments and preprocessor lines, leaving string and character
+/// literals alone.
+fn strip(source: &str) -> String {
+    let mut ret = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().map_or(false, |&c| c != '\n') {
+                    chars.next();
+                }
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                while let Some(c) = chars.next() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                ret.push(' ');
+            },
+            '"' | '\'' => {
+                ret.push(c);
+                while let Some(d) = chars.next() {
+                    ret.push(d);
+                    if d == '\\' {
+                        if let Some(e) = chars.next() {
+                            ret.push(e);
+                        }
+                    } else if d == c {
+                        break;
+                    }
+                }
+            },
+            _ => ret.push(c),
+        }
+    }
+
+    ret.lines()
+        .filter(|l| !l.trim_left().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+
+/// Returns the size of the given C type, in bytes.
+fn type_width(words: &[&str]) -> Option<usize> {
+    let words = words.iter()
+        .cloned()
+        .filter(|w| !["static", "const", "extern", "volatile", "signed", "unsigned"].contains(w))
+        .collect::<Vec<_>>();
+
+    match &words.join(" ")[..] {
+        "byte" | "char" | "uchar" | "uint8" | "uint8_t" | "int8_t" | "u8" | "BYTE" => Some(1),
+        "word16" | "short" | "ushort" | "uint16" | "uint16_t" | "int16_t" | "u16" | "WORD" => Some(2),
+        "word32" | "int" | "uint" | "long" | "ulong" | "uint32" | "uint32_t" | "int32_t" | "u32" | "DWORD" => Some(4),
+        "word64" | "long long" | "ulonglong" | "uint64" | "uint64_t" | "int64_t" | "__int64" | "u64" | "QWORD" => Some(8),
+        _ => None,
+    }
+}
+
+
+/// Parses a single value from an array initializer, e.g. `0x67452301UL`,
+/// `W64LIT(0x428a2f98d728ae22)`, `-5` or `'A'`.
+fn parse_value(s: &str, width: usize) -> Option<u64> {
+    let mut s = s.trim();
+
+    // Macros like `W64LIT(...)`.
+    if let (Some(open), true) = (s.find('('), s.ends_with(')')) {
+        s = s[open + 1..s.len() - 1].trim();
+    }
+
+    if s.len() == 3 && s.starts_with('\'') && s.ends_with('\'') {
+        return Some(s.as_bytes()[1] as u64);
+    }
+
+    let (negative, s) = if s.starts_with('-') { (true, s[1..].trim()) } else { (false, s) };
+    let s = s.trim_right_matches(|c| c == 'u' || c == 'U' || c == 'l' || c == 'L');
+
+    let value = if s.starts_with("0x") || s.starts_with("0X") {
+        try_opt!(u64::from_str_radix(&s[2..], 16).ok())
+    } else {
+        try_opt!(s.parse::<u64>().ok())
+    };
+
+    let mask = if width == 8 { !0 } else { (1u64 << (width * 8)) - 1 };
+    Some(if negative { value.wrapping_neg() & mask } else { value })
+}
+
+
+/// Parses IDA findcrypt2's `consts.cpp`, or anything similar.  Every array of
+/// integers is read, and the `non_sparse_consts` table gives the algorithm
+/// that each belongs to.  Sparse constants are only meaningful when several of
+/// them are found near each other, so those are skipped.
+pub fn parse(source: &str) -> Result<Loaded, String> {
+    let source = strip(source);
+
+    let mut arrays = BTreeMap::new();
+    let mut order = vec![];
+    let mut table: Option<Vec<(String, String)>> = None;
+    let mut skipped = 0;
+
+    for stmt in source.split(';') {
+        let eq = match stmt.find('=') {
+            Some(i) => i,
+            None    => continue,
+        };
+        let (head, body) = (&stmt[..eq], &stmt[eq + 1..]);
+        let (open, close) = match (body.find('{'), body.rfind('}')) {
+            (Some(o), Some(c)) if o < c => (o, c),
+            _                           => continue,
+        };
+        let body = &body[open + 1..close];
+
+        let bracket = match head.find('[') {
+            Some(b) => b,
+            None    => continue,
+        };
+        let mut words = head[..bracket].split_whitespace().collect::<Vec<_>>();
+        let name = match words.pop() {
+            Some(n) => n.to_string(),
+            None    => continue,
+        };
+
+        if words.contains(&"array_info_t") {
+            let sparse = name.contains("sparse") && !name.contains("non_sparse");
+            let entries = table_entries(body);
+            if sparse {
+                skipped += entries.len();
+            } else {
+                let mut t = table.take().unwrap_or(vec![]);
+                t.extend(entries);
+                table = Some(t);
+            }
+            continue;
+        }
+
+        let width = match type_width(&words) {
+            Some(w) => w,
+            None    => {
+                debug!("Skipping {}: unknown type {}", name, words.join(" "));
+                skipped += 1;
+                continue;
+            },
+        };
+
+        let values = body.split(|c| c == ',' || c == '{' || c == '}')
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| parse_value(v, width))
+            .collect::<Option<Vec<_>>>();
+        match values {
+            Some(v) => {
+                order.push(name.clone());
+                arrays.insert(name, Table { width: width, values: v });
+            },
+            None => {
+                debug!("Skipping {}: could not parse values", name);
+                skipped += 1;
+            },
+        }
+    }
+
+    // Without a table, every array is its own algorithm.
+    let table = table.unwrap_or_else(|| order.iter().map(|n| (n.clone(), n.clone())).collect());
+
+    let mut ret = Loaded { signatures: vec![], skipped: skipped };
+    for (name, algorithm) in table {
+        match arrays.remove(&name) {
+            Some(t) => ret.signatures.push(Signature {
+                algorithm: algorithm,
+                desc:      format!("findcrypt2::{}", name),
+                varname:   format!("findcrypt2::{}", name),
+                category:  Category::Crypto,
+                table:     t,
+            }),
+            None => ret.skipped += 1,
+        }
+    }
+
+    if ret.signatures.is_empty() && ret.skipped == 0 {
+        return Err("no arrays of constants found".to_string());
+    }
+    Ok(ret)
+}
+
+
+/// Parses the entries of an `array_info_t` table, which look like
+/// `{ ARR(name), "algorithm" }`.
+fn table_entries(body: &str) -> Vec<(String, String)> {
+    let mut ret = vec![];
+
+    for entry in body.split("ARR(").skip(1) {
+        let name = match entry.find(')') {
+            Some(i) => entry[..i].trim().to_string(),
+            None    => continue,
+        };
+        let algorithm = entry.splitn(3, '"').nth(1);
+
+        if let Some(algorithm) = algorithm {
+            ret.push((name, algorithm.to_string()));
+        }
+    }
+
+    ret
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_parse_consts() {
+    let source = r#"
+// FindCrypt - find constants used in crypto algorithms
+#include "findcrypt.hpp"
+
+static const word32 MD5_T[] = {
+  0xd76aa478L, 0xe8c7b756L, 0x242070dbL, 0xc1bdceeeL, /* ... */
+};
+
+static const word64 SHA512_K[2] = { W64LIT(0x428a2f98d728ae22), W64LIT(0x7137449123ef65cd) };
+
+static const byte DES_sbox[2][4] = { { 14, 4, 13, 1 }, { 2, 15, 11, 8 } };
+
+static const word32 SHA1_h[] = { 0x67452301, 0xefcdab89 };
+
+const array_info_t non_sparse_consts[] =
+{
+  { ARR(MD5_T),     "MD5"     },
+  { ARR(SHA512_K),  "SHA-512" },
+  { ARR(DES_sbox),  "DES"     },
+  { NULL, 0, 0, NULL }
+};
+
+const array_info_t sparse_consts[] =
+{
+  { ARR(SHA1_h),    "SHA-1"   },
+  { NULL, 0, 0, NULL }
+};
+"#;
+
+    assert!(is_consts(source));
+    let loaded = parse(source).unwrap();
+
+    assert_eq!(loaded.skipped, 1);
+    assert_eq!(loaded.signatures.len(), 3);
+
+    assert_eq!(loaded.signatures[0].algorithm, "MD5");
+    assert_eq!(loaded.signatures[0].varname, "findcrypt2::MD5_T");
+    assert_eq!(loaded.signatures[0].table.width, 4);
+    assert_eq!(loaded.signatures[0].table.values, vec![0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee]);
+
+    assert_eq!(loaded.signatures[1].table.width, 8);
+    assert_eq!(loaded.signatures[1].table.values, vec![0x428a2f98d728ae22, 0x7137449123ef65cd]);
+
+    assert_eq!(loaded.signatures[2].table.width, 1);
+    assert_eq!(loaded.signatures[2].table.values, vec![14, 4, 13, 1, 2, 15, 11, 8]);
+}
@@ -0,0 +1,125 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use endian::{AsByteVec, Endianness, EndianWrapper};
+use patterns::{Category, Pattern};
+
+mod consts;
+mod yara;
+
+
+/// Signatures shorter than this match far too often to be useful.
+const MIN_LEN: usize = 4;
+
+
+/// A table of constants loaded from a signature file, stored as integers of
+/// the given width so that both byte orders can be searched for.
+pub struct Table {
+    pub width: usize,
+    pub values: Vec<u64>,
+}
+
+
+impl AsByteVec for Table {
+    fn as_byte_vec(&self, e: Endianness) -> Vec<u8> {
+        match self.width {
+            1 => {
+                let v = self.values.iter().map(|&x| x as u8).collect::<Vec<_>>();
+                EndianWrapper(&v).as_byte_vec(e)
+            },
+            2 => {
+                let v = self.values.iter().map(|&x| x as u16).collect::<Vec<_>>();
+                EndianWrapper(&v).as_byte_vec(e)
+            },
+            4 => {
+                let v = self.values.iter().map(|&x| x as u32).collect::<Vec<_>>();
+                EndianWrapper(&v).as_byte_vec(e)
+            },
+            _ => EndianWrapper(&self.values).as_byte_vec(e),
+        }
+    }
+
+    fn element_width(&self) -> usize {
+        self.width
+    }
+}
+
+
+/// A signature read from a file, before it's turned into a `Pattern`.
+pub struct Signature {
+    pub algorithm: String,
+    pub desc: String,
+    pub varname: String,
+    pub category: Category,
+    pub table: Table,
+}
+
+
+/// The signatures read from a file, and the number of entries in it that
+/// couldn't be converted - e.g. because they need several separate strings to
+/// match, or use wildcards.
+pub struct Loaded {
+    pub signatures: Vec<Signature>,
+    pub skipped: usize,
+}
+
+
+/// Patterns hold `'static` references, since the built-in ones are compiled
+/// in.  Signatures are only loaded once, at startup, so it's fine to leak them.
+fn leak_str(s: String) -> &'static str {
+    unsafe { &*Box::into_raw(s.into_boxed_str()) }
+}
+
+
+fn leak_table(t: Table) -> &'static AsByteVec {
+    let b: Box<AsByteVec> = Box::new(t);
+    unsafe { &*Box::into_raw(b) }
+}
+
+
+/// Parses the contents of a signature file, which is either a set of YARA
+/// rules (e.g. findcrypt-yara's `findcrypt3.rules`, or the output of
+/// `export-yara`) or C source with arrays of constants (e.g. IDA findcrypt2's
+/// `consts.cpp`).
+pub fn parse(source: &str) -> Result<Loaded, String> {
+    let loaded = if consts::is_consts(source) {
+        try!(consts::parse(source))
+    } else {
+        try!(yara::parse(source))
+    };
+
+    let mut ret = Loaded { signatures: vec![], skipped: loaded.skipped };
+    for sig in loaded.signatures {
+        if sig.table.values.len() * sig.table.width < MIN_LEN {
+            debug!("Skipping {}: too short", sig.varname);
+            ret.skipped += 1;
+        } else {
+            ret.signatures.push(sig);
+        }
+    }
+    Ok(ret)
+}
+
+
+/// Loads the signatures in the given file as patterns.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Pattern>> {
+    let path = path.as_ref();
+
+    let mut source = String::new();
+    try!(try!(fs::File::open(path)).read_to_string(&mut source));
+
+    let loaded = try!(parse(&source).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)));
+    if loaded.skipped > 0 {
+        warn!("{}: skipped {} signature(s) that can't be searched for as a single table",
+              path.display(), loaded.skipped);
+    }
+
+    Ok(loaded.signatures.into_iter().map(|sig| Pattern {
+        algorithm: leak_str(sig.algorithm),
+        desc:      leak_str(sig.desc),
+        bytes:     leak_table(sig.table),
+        varname:   leak_str(sig.varname),
+        category:  sig.category,
+    }).collect())
+}
@@ -0,0 +1,523 @@
+use std::collections::BTreeMap;
+
+use patterns::Category;
+
+use super::{Loaded, Signature, Table};
+
+
+/// A value in a rule's `meta` section.
+enum Meta {
+    Str(String),
+    Int(u64),
+    Other,
+}
+
+
+/// A string from a rule's `strings` section, or `None` if it's something that
+/// can't be searched for as plain bytes (a regular expression, a hex string
+/// with wildcards or jumps, a `nocase` string, ...).
+struct Str {
+    id: String,
+    bytes: Option<Vec<u8>>,
+}
+
+
+struct Rule {
+    name: String,
+    meta: BTreeMap<String, Meta>,
+    strings: Vec<Str>,
+    condition: String,
+}
+
+
+/// Just enough of a lexer to pull rules out of a YARA source file.
+struct Lexer<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+
+impl<'a> Lexer<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.src.get(self.pos + n).cloned()
+    }
+
+    /// Skips whitespace and comments.
+    fn skip(&mut self) {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(c), _) if (c as char).is_whitespace() => self.pos += 1,
+                (Some(b'/'), Some(b'/')) => {
+                    while self.peek().map_or(false, |c| c != b'\n') {
+                        self.pos += 1;
+                    }
+                },
+                (Some(b'/'), Some(b'*')) => {
+                    self.pos += 2;
+                    while self.pos < self.src.len() && !(self.peek() == Some(b'*') && self.peek_at(1) == Some(b'/')) {
+                        self.pos += 1;
+                    }
+                    self.pos += 2;
+                },
+                _ => return,
+            }
+        }
+    }
+
+    fn eof(&mut self) -> bool {
+        self.skip();
+        self.pos >= self.src.len()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        self.skip();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at offset {}", c as char, self.pos))
+        }
+    }
+
+    /// Reads an identifier, including a leading `$` for string identifiers.
+    fn ident(&mut self) -> Result<String, String> {
+        self.skip();
+        let start = self.pos;
+        while self.peek().map_or(false, |c| (c as char).is_alphanumeric() || c == b'_' || c == b'$') {
+            self.pos += 1;
+        }
+
+        if self.pos == start {
+            Err(format!("expected an identifier at offset {}", self.pos))
+        } else {
+            Ok(String::from_utf8_lossy(&self.src[start..self.pos]).into_owned())
+        }
+    }
+
+    /// Reads a quoted text string, handling escapes.
+    fn text(&mut self) -> Result<Vec<u8>, String> {
+        try!(self.expect(b'"'));
+
+        let mut ret = vec![];
+        loop {
+            match self.peek() {
+                None       => return Err("unterminated string".to_string()),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(ret);
+                },
+                Some(b'\\') => {
+                    let escaped = self.peek_at(1);
+                    self.pos += 2;
+                    match escaped {
+                        Some(b'n') => ret.push(b'\n'),
+                        Some(b'r') => ret.push(b'\r'),
+                        Some(b't') => ret.push(b'\t'),
+                        Some(b'x') => {
+                            let hex = String::from_utf8_lossy(&self.src[self.pos..::std::cmp::min(self.pos + 2, self.src.len())]).into_owned();
+                            ret.push(try!(u8::from_str_radix(&hex, 16).map_err(|_| "invalid escape in string".to_string())));
+                            self.pos += 2;
+                        },
+                        Some(c) => ret.push(c),
+                        None    => return Err("unterminated string".to_string()),
+                    }
+                },
+                Some(c) => {
+                    ret.push(c);
+                    self.pos += 1;
+                },
+            }
+        }
+    }
+
+    /// Reads a hex string, returning `None` if it has wildcards, jumps or
+    /// alternatives.
+    fn hex(&mut self) -> Result<Option<Vec<u8>>, String> {
+        try!(self.expect(b'{'));
+
+        let mut digits = vec![];
+        let mut plain = true;
+        loop {
+            self.skip();
+            match self.peek() {
+                None       => return Err("unterminated hex string".to_string()),
+                Some(b'}') => {
+                    self.pos += 1;
+                    break;
+                },
+                Some(c) => {
+                    match (c as char).to_digit(16) {
+                        Some(d) => digits.push(d as u8),
+                        None    => plain = false,
+                    }
+                    self.pos += 1;
+                },
+            }
+        }
+
+        if !plain || digits.len() % 2 != 0 {
+            return Ok(None);
+        }
+        Ok(Some(digits.chunks(2).map(|p| p[0] << 4 | p[1]).collect()))
+    }
+
+    /// Skips over a regular expression.
+    fn regex(&mut self) -> Result<(), String> {
+        try!(self.expect(b'/'));
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => return Err("unterminated regular expression".to_string()),
+                Some(b'\\') => self.pos += 2,
+                Some(b'/')  => {
+                    self.pos += 1;
+                    return Ok(());
+                },
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// Reads the rest of a rule's condition, up to the closing brace of the
+    /// rule.
+    fn condition(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        loop {
+            self.skip();
+            match self.peek() {
+                None       => return Err("unterminated rule".to_string()),
+                Some(b'}') => break,
+                Some(b'"') => {
+                    try!(self.text());
+                },
+                Some(_) => self.pos += 1,
+            }
+        }
+
+        let ret = String::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+        self.pos += 1;
+        Ok(ret)
+    }
+
+    fn rule(&mut self) -> Result<Rule, String> {
+        let mut rule = Rule {
+            name:      try!(self.ident()),
+            meta:      BTreeMap::new(),
+            strings:   vec![],
+            condition: String::new(),
+        };
+
+        // Tags
+        self.skip();
+        if self.peek() == Some(b':') {
+            self.pos += 1;
+            while { self.skip(); self.peek() != Some(b'{') } {
+                try!(self.ident());
+            }
+        }
+        try!(self.expect(b'{'));
+
+        loop {
+            let section = try!(self.ident());
+            try!(self.expect(b':'));
+
+            match &section[..] {
+                "meta"      => try!(self.meta(&mut rule)),
+                "strings"   => try!(self.strings(&mut rule)),
+                "condition" => {
+                    rule.condition = try!(self.condition());
+                    return Ok(rule);
+                },
+                _ => return Err(format!("unknown section '{}' in rule {}", section, rule.name)),
+            }
+        }
+    }
+
+    /// Returns whether the next thing is the start of a new section.
+    fn at_section(&mut self) -> bool {
+        self.skip();
+        let start = self.pos;
+        let at = self.ident().is_ok() && { self.skip(); self.peek() == Some(b':') };
+        self.pos = start;
+        at
+    }
+
+    fn meta(&mut self, rule: &mut Rule) -> Result<(), String> {
+        while !self.at_section() {
+            let key = try!(self.ident());
+            try!(self.expect(b'='));
+            self.skip();
+
+            let value = if self.peek() == Some(b'"') {
+                Meta::Str(String::from_utf8_lossy(&try!(self.text())).into_owned())
+            } else {
+                if self.peek() == Some(b'-') {
+                    self.pos += 1;
+                }
+                let value = try!(self.ident());
+                match value.parse() {
+                    Ok(n)  => Meta::Int(n),
+                    Err(_) => Meta::Other,
+                }
+            };
+            rule.meta.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn strings(&mut self, rule: &mut Rule) -> Result<(), String> {
+        while !self.at_section() {
+            let id = try!(self.ident());
+            try!(self.expect(b'='));
+            self.skip();
+
+            let (bytes, is_text) = match self.peek() {
+                Some(b'"') => (Some(try!(self.text())), true),
+                Some(b'{') => (try!(self.hex()), false),
+                Some(b'/') => {
+                    try!(self.regex());
+                    (None, false)
+                },
+                _ => return Err(format!("invalid string {} in rule {}", id, rule.name)),
+            };
+
+            // Modifiers
+            let (mut ascii, mut wide, mut supported) = (false, false, true);
+            while !self.at_section() && self.peek() != Some(b'$') {
+                let modifier = try!(self.ident());
+                match &modifier[..] {
+                    "ascii"                => ascii = true,
+                    "wide"                 => wide = true,
+                    "fullword" | "private" => {},
+                    _                      => supported = false,
+                }
+
+                // Some modifiers take arguments, e.g. `xor(1-255)`.
+                self.skip();
+                if self.peek() == Some(b'(') {
+                    while self.peek().map_or(false, |c| c != b')') {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+            }
+
+            let bytes = if supported { bytes } else { None };
+            if is_text && wide {
+                if ascii {
+                    rule.strings.push(Str { id: id.clone(), bytes: bytes.clone() });
+                }
+                let wide_bytes = bytes.map(|b| b.iter().flat_map(|&c| vec![c, 0]).collect());
+                rule.strings.push(Str { id: format!("{}_wide", id), bytes: wide_bytes });
+            } else {
+                rule.strings.push(Str { id: id, bytes: bytes });
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Returns the strings that, on their own, satisfy the rule's condition, or
+/// `None` if the condition can't be satisfied by any single string - e.g.
+/// `4 of them`, or something that uses a YARA module.
+fn sufficient_strings(rule: &Rule) -> Option<Vec<&Str>> {
+    let words = rule.condition.split_whitespace().collect::<Vec<_>>();
+    let all = || Some(rule.strings.iter().collect());
+
+    match &words.join(" ")[..] {
+        "any of them" | "1 of them" | "any of ($*)" | "1 of ($*)" => all(),
+        "all of them" | "all of ($*)" if rule.strings.len() == 1 => all(),
+        _ => {
+            // A single string, or several joined with `or`.
+            let mut ret = vec![];
+            for (i, word) in words.iter().enumerate() {
+                if i % 2 == 1 {
+                    if *word != "or" {
+                        return None;
+                    }
+                } else {
+                    ret.push(try_opt!(rule.strings.iter().find(|s| s.id == *word)));
+                }
+            }
+
+            if ret.is_empty() { None } else { Some(ret) }
+        },
+    }
+}
+
+
+/// Converts rules into signatures.  Rules written by `export-yara` record the
+/// element width of the table, so those are turned back into the same table
+/// rather than into a pattern for each byte order.  Elements wider than eight
+/// bytes (i.e. big numbers) can't be stored in a table, so each byte order is
+/// kept as a separate pattern.
+fn convert(rule: &Rule, out: &mut Loaded) {
+    let strings = match sufficient_strings(rule) {
+        Some(s) => s,
+        None    => {
+            debug!("Skipping rule {}: condition needs more than one string", rule.name);
+            out.skipped += 1;
+            return;
+        },
+    };
+
+    let meta_str = |key: &str| match rule.meta.get(key) {
+        Some(&Meta::Str(ref s)) => Some(s.clone()),
+        _                       => None,
+    };
+    let width = match rule.meta.get("element_width") {
+        Some(&Meta::Int(w)) if w == 1 || w == 2 || w == 4 || w == 8 => Some(w as usize),
+        _                                                            => None,
+    };
+    let category = match meta_str("category") {
+        Some(ref c) if c == "non-crypto" => Category::NonCrypto,
+        Some(ref c) if c == "context"    => Category::Context,
+        _                                => Category::Crypto,
+    };
+
+    for s in strings {
+        let bytes = match s.bytes {
+            Some(ref b) => b,
+            None        => {
+                debug!("Skipping {} in rule {}: not a plain string", s.id, rule.name);
+                out.skipped += 1;
+                continue;
+            },
+        };
+
+        let table = match width {
+            // The little-endian string has all we need.
+            Some(_) if s.id == "$be" => continue,
+            Some(w) if bytes.len() % w == 0 => Table {
+                width:  w,
+                values: bytes.chunks(w)
+                    .map(|c| c.iter().rev().fold(0u64, |v, &b| v << 8 | b as u64))
+                    .collect(),
+            },
+            _ => Table {
+                width:  1,
+                values: bytes.iter().map(|&b| b as u64).collect(),
+            },
+        };
+
+        let varname = match meta_str("varname") {
+            Some(v) => v,
+            None if rule.strings.len() == 1 => format!("yara::{}", rule.name),
+            None    => format!("yara::{}_{}", rule.name, s.id.trim_left_matches('$')),
+        };
+
+        out.signatures.push(Signature {
+            algorithm: meta_str("algorithm").unwrap_or(rule.name.clone()),
+            desc:      meta_str("desc").or(meta_str("description")).unwrap_or(varname.clone()),
+            varname:   varname,
+            category:  category,
+            table:     table,
+        });
+    }
+}
+
+
+pub fn parse(source: &str) -> Result<Loaded, String> {
+    let mut lexer = Lexer { src: source.as_bytes(), pos: 0 };
+    let mut ret = Loaded { signatures: vec![], skipped: 0 };
+
+    while !lexer.eof() {
+        let keyword = try!(lexer.ident());
+        match &keyword[..] {
+            "import" | "include" => {
+                try!(lexer.text());
+            },
+            "private" | "global" => {},
+            "rule" => {
+                let rule = try!(lexer.rule());
+                convert(&rule, &mut ret);
+            },
+            _ => return Err(format!("unexpected '{}' at offset {}", keyword, lexer.pos)),
+        }
+    }
+
+    Ok(ret)
+}
+
+// ----------------------------------------------------------------------
+
+#[test]
+fn test_parse_findcrypt3() {
+    let source = r#"
+import "pe"
+
+rule RijnDael_AES_CHAR
+{	meta:
+		author = "_pusher_"
+		description = "RijnDael AES (check2) [char]"
+	strings:
+		$c0 = { 63 7C 77 7B F2 6B 6F C5 30 01 67 2B FE D7 AB 76 }
+	condition:
+		$c0
+}
+
+rule BLOWFISH_Constants {
+	strings:
+		$c0 = { D1310BA6 }
+		$c1 = { A60B31D1 }
+	condition:
+		2 of them
+}
+
+rule Crypt_Strings : api
+{
+	strings:
+		$a1 = "CryptEncrypt" ascii wide // comment
+		$a2 = /Crypt[A-Z]+/
+	condition:
+		any of them
+}
+"#;
+
+    let loaded = parse(source).unwrap();
+
+    // The Blowfish rule and the regular expression are skipped.
+    assert_eq!(loaded.skipped, 2);
+    assert_eq!(loaded.signatures.len(), 3);
+
+    assert_eq!(loaded.signatures[0].algorithm, "RijnDael_AES_CHAR");
+    assert_eq!(loaded.signatures[0].desc, "RijnDael AES (check2) [char]");
+    assert_eq!(loaded.signatures[0].varname, "yara::RijnDael_AES_CHAR");
+    assert_eq!(&loaded.signatures[0].table.values[..4], &[0x63, 0x7C, 0x77, 0x7B]);
+
+    assert_eq!(loaded.signatures[1].varname, "yara::Crypt_Strings_a1");
+    assert_eq!(loaded.signatures[2].varname, "yara::Crypt_Strings_a1_wide");
+    assert_eq!(loaded.signatures[2].table.values.len(), 24);
+}
+
+#[test]
+fn test_parse_exported() {
+    let source = r#"
+rule findcrypt_hash_TEST : findcrypt non_crypto
+{
+    meta:
+        algorithm = "Test"
+        desc = "hash::TEST"
+        varname = "hash::TEST"
+        category = "non-crypto"
+        element_width = 4
+    strings:
+        $le = { 04 03 02 01 08 07 06 05 }
+        $be = { 01 02 03 04 05 06 07 08 }
+    condition:
+        any of them
+}
+"#;
+
+    let loaded = parse(source).unwrap();
+
+    assert_eq!(loaded.signatures.len(), 1);
+    assert_eq!(loaded.signatures[0].varname, "hash::TEST");
+    assert_eq!(loaded.signatures[0].category, Category::NonCrypto);
+    assert_eq!(loaded.signatures[0].table.width, 4);
+    assert_eq!(loaded.signatures[0].table.values, vec![0x01020304, 0x05060708]);
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use endian::Endianness;
+use patterns::Pattern;
+
+
+/// Turns a name into a valid YARA identifier.
+fn identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if (c as u32) < 0x80 && c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .replace("__", "_")
+}
+
+
+/// Escapes a string for use in a YARA text string.
+fn quote(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"'  => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\t' => ret.push_str("\\t"),
+            c if c.is_control() || (c as u32) >= 0x80 => {
+                for b in c.to_string().bytes() {
+                    write!(ret, "\\x{:02x}", b).unwrap();
+                }
+            },
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+
+/// Returns `base`, or - if that's already been used - `base` with the lowest
+/// numeric suffix that hasn't been, and records it as used.
+fn unique_name(names: &mut BTreeSet<String>, base: String) -> String {
+    let mut name = base.clone();
+    let mut n = 1;
+    while names.contains(&name) {
+        n += 1;
+        name = format!("{}_{}", base, n);
+    }
+    names.insert(name.clone());
+    name
+}
+
+
+/// Writes a YARA hex string, split over several lines for long patterns.
+fn hex_string(out: &mut String, name: &str, bytes: &[u8]) {
+    write!(out, "        ${} = {{", name).unwrap();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        if i > 0 || bytes.len() > 16 {
+            out.push_str("\n           ");
+        }
+        for b in line {
+            write!(out, " {:02x}", b).unwrap();
+        }
+    }
+    if bytes.len() > 16 {
+        out.push_str("\n       ");
+    }
+    out.push_str(" }\n");
+}
+
+
+/// Returns YARA rules that match the given patterns, with one rule for each
+/// pattern that matches either its little- or big-endian form.  If `group` is
+/// set, there is also a rule for each algorithm that matches if any of that
+/// algorithm's rules do.
+pub fn rules(patterns: &[Pattern], group: bool) -> String {
+    let mut out = String::new();
+    let mut names = BTreeSet::new();
+    let mut families: Vec<(&str, Vec<String>)> = vec![];
+
+    for pat in patterns {
+        // Variable names are unique for the built-in patterns, but not
+        // necessarily once they've been turned into identifiers.
+        let name = unique_name(&mut names, format!("findcrypt_{}", identifier(pat.varname)));
+
+        writeln!(out, "rule {} : findcrypt {}", name, identifier(pat.category.name())).unwrap();
+        out.push_str("{\n    meta:\n");
+        writeln!(out, "        algorithm = {}", quote(pat.algorithm)).unwrap();
+        writeln!(out, "        desc = {}", quote(pat.desc)).unwrap();
+        writeln!(out, "        varname = {}", quote(pat.varname)).unwrap();
+        writeln!(out, "        category = {}", quote(pat.category.name())).unwrap();
+        writeln!(out, "        element_width = {}", pat.bytes.element_width()).unwrap();
+        out.push_str("    strings:\n");
+
+        // Tables of single bytes are the same in both byte orders.
+        let le = pat.bytes.as_byte_vec(Endianness::LittleEndian);
+        let be = pat.bytes.as_byte_vec(Endianness::BigEndian);
+        hex_string(&mut out, "le", &le);
+        if be != le {
+            hex_string(&mut out, "be", &be);
+        }
+        out.push_str("    condition:\n        any of them\n}\n\n");
+
+        let pos = match families.iter().position(|f| f.0 == pat.algorithm) {
+            Some(p) => p,
+            None    => {
+                families.push((pat.algorithm, vec![]));
+                families.len() - 1
+            },
+        };
+        families[pos].1.push(name);
+    }
+
+    if group {
+        for (algorithm, members) in families {
+            let name = unique_name(&mut names, format!("findcrypt_algorithm_{}", identifier(algorithm)));
+            writeln!(out, "rule {} : findcrypt", name).unwrap();
+            out.push_str("{\n    meta:\n");
+            writeln!(out, "        algorithm = {}", quote(algorithm)).unwrap();
+            out.push_str("    condition:\n        ");
+            out.push_str(&members.join(" or\n        "));
+            out.push_str("\n}\n\n");
+        }
+    }
+
+    out
+}
+
+// ----------------------------------------------------------------------
+
+#[cfg(test)]
+static TEST_TABLE: ::endian::EndianWrapper<'static, u32> = ::endian::EndianWrapper(&[0x01020304]);
+
+#[cfg(test)]
+static TEST_BYTES: ::endian::EndianWrapper<'static, u8> = ::endian::EndianWrapper(&[0xAA, 0xBB]);
+
+#[test]
+fn test_rules() {
+    let patterns = vec![
+        Pattern {
+            algorithm: "Test/Algo",
+            desc:      "a \"test\" table",
+            bytes:     &TEST_TABLE,
+            varname:   "test::TABLE",
+            category:  ::patterns::Category::Crypto,
+        },
+        Pattern {
+            algorithm: "Test/Algo",
+            desc:      "test::BYTES",
+            bytes:     &TEST_BYTES,
+            varname:   "test::BYTES",
+            category:  ::patterns::Category::NonCrypto,
+        },
+    ];
+
+    let rules = rules(&patterns, true);
+
+    assert!(rules.contains("rule findcrypt_test_TABLE : findcrypt crypto\n"));
+    assert!(rules.contains("        desc = \"a \\\"test\\\" table\"\n"));
+    assert!(rules.contains("        $le = { 04 03 02 01 }\n        $be = { 01 02 03 04 }\n"));
+    assert!(rules.contains("rule findcrypt_test_BYTES : findcrypt non_crypto\n"));
+    assert!(rules.contains("        $le = { aa bb }\n    condition:"));
+    assert!(rules.contains("rule findcrypt_algorithm_Test_Algo : findcrypt\n"));
+    assert!(rules.contains("        findcrypt_test_TABLE or\n        findcrypt_test_BYTES\n}"));
+}
+
+#[test]
+fn test_rules_round_trip() {
+    use endian::AsByteVec;
+
+    let builtin = ::patterns::get_patterns();
+    let find = |varname: &str| builtin.iter().find(|p| p.varname == varname).unwrap().clone();
+    let patterns = vec![find("other::DH_MODP_1024"), find("hash::SHA256_CONSTS")];
+    let (dh, sha) = (patterns[0].bytes, patterns[1].bytes);
+
+    let loaded = ::signatures::parse(&rules(&patterns, false)).unwrap();
+    let bytes = loaded.signatures.iter()
+        .map(|s| (&s.varname[..], s.table.as_byte_vec(Endianness::LittleEndian), s.table.as_byte_vec(Endianness::BigEndian)))
+        .collect::<Vec<_>>();
+
+    // Both forms of the big number are kept, and the table is rebuilt.
+    assert_eq!(bytes.len(), 3);
+    assert_eq!(bytes[0].0, "other::DH_MODP_1024");
+    assert_eq!(bytes[0].1, dh.as_byte_vec(Endianness::LittleEndian));
+    assert_eq!(bytes[1].1, dh.as_byte_vec(Endianness::BigEndian));
+    assert_eq!(bytes[2], ("hash::SHA256_CONSTS", sha.as_byte_vec(Endianness::LittleEndian), sha.as_byte_vec(Endianness::BigEndian)));
+}
+
+#[test]
+fn test_unique_names() {
+    let pattern = |algorithm, varname| Pattern {
+        algorithm: algorithm,
+        desc:      varname,
+        bytes:     &TEST_TABLE,
+        varname:   varname,
+        category:  ::patterns::Category::Crypto,
+    };
+    let patterns = vec![
+        pattern("A", "test::X"),
+        pattern("A", "test.X"),
+        pattern("A", "test::X_2"),
+        pattern("A-B", "test::Y"),
+        pattern("A B", "test::Z"),
+    ];
+
+    let rules = rules(&patterns, true);
+    let names = rules.lines()
+        .filter(|l| l.starts_with("rule "))
+        .map(|l| l.split(' ').nth(1).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(names, vec![
+        "findcrypt_test_X", "findcrypt_test_X_2", "findcrypt_test_X_2_2", "findcrypt_test_Y", "findcrypt_test_Z",
+        "findcrypt_algorithm_A", "findcrypt_algorithm_A_B", "findcrypt_algorithm_A_B_2",
+    ]);
+}
+
+/// Checks that the exported rules for every built-in pattern compile, if
+/// YARA's compiler is installed.
+#[test]
+fn test_rules_compile() {
+    use std::env;
+    use std::fs::File;
+    use std::io::{ErrorKind, Write};
+    use std::process::Command;
+
+    let dir = env::temp_dir();
+    let source = dir.join("findcrypt-test-rules.yar");
+    let compiled = source.with_extension("yarc");
+    File::create(&source).unwrap()
+        .write_all(rules(&::patterns::get_patterns(), true).as_bytes()).unwrap();
+
+    let res = Command::new("yarac").arg(&source).arg(&compiled).output();
+    let _ = ::std::fs::remove_file(&source);
+    let _ = ::std::fs::remove_file(&compiled);
+
+    match res {
+        Ok(out) => assert!(out.status.success(), "yarac failed: {}", String::from_utf8_lossy(&out.stderr)),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => {},
+        Err(e) => panic!("could not run yarac: {}", e),
+    }
+}